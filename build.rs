@@ -0,0 +1,17 @@
+use std::env;
+use std::process::Command;
+
+/// Sets `cfg(nightly)` when building with a nightly compiler, which the benchmarks need for
+/// `#![feature(test)]`.
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(nightly)");
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+    if version.contains("nightly") || version.contains("-dev") {
+        println!("cargo::rustc-cfg=nightly");
+    }
+}
@@ -0,0 +1,401 @@
+use crate::opcodes::Op;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// A module, as produced by the binary decoder (and eventually the text parser).
+///
+/// Indices are always resolved to numbers. The `id`s are the symbolic names of things,
+/// without the leading `$` - either from `$ids` in the source or from the `name` section.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Module {
+    pub id: Option<String>,
+    pub types: Vec<TypeDef>,
+    /// The explicit `rec` groups, as ranges of type indices. Types outside of them are each
+    /// in a group of their own.
+    pub rec_groups: Vec<Range<u32>>,
+    pub imports: Vec<Import>,
+    pub funcs: Vec<Func>,
+    pub tables: Vec<Table>,
+    pub memories: Vec<Memory>,
+    pub tags: Vec<Tag>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub start: Option<u32>,
+    pub elems: Vec<Elem>,
+    pub datas: Vec<Data>,
+    /// The data count section, if there was one.
+    pub data_count: Option<u32>,
+    pub customs: Vec<Custom>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    /// `(ref null func)`. The decoder always uses this rather than the equivalent
+    /// [`ValType::Ref`], and likewise for `externref` and `exnref`.
+    FuncRef,
+    ExternRef,
+    ExnRef,
+    /// Any other reference type, from the function references and GC proposals.
+    Ref(RefType),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RefType {
+    pub nullable: bool,
+    pub heap: HeapType,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HeapType {
+    Func,
+    Extern,
+    Exn,
+    Any,
+    Eq,
+    I31,
+    Struct,
+    Array,
+    None,
+    NoFunc,
+    NoExtern,
+    NoExn,
+    /// A type from the type section, by its index.
+    Type(u32),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+/// What a struct field or array element holds, which can be packed into less than an i32.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StorageType {
+    Val(ValType),
+    I8,
+    I16,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FieldType {
+    pub storage: StorageType,
+    pub mutable: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CompositeType {
+    Func(FuncType),
+    Struct(Vec<FieldType>),
+    Array(FieldType),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TypeDef {
+    pub id: Option<String>,
+    pub composite: CompositeType,
+    /// The types this is declared as a subtype of, with `sub`.
+    pub supertypes: Vec<u32>,
+    /// Whether the type can't have subtypes. Types declared without `sub` are final.
+    pub is_final: bool,
+}
+
+impl TypeDef {
+    /// A final function type with no supertypes, which is all there is without GC.
+    pub fn func(func: FuncType) -> TypeDef {
+        TypeDef {
+            id: None,
+            composite: CompositeType::Func(func),
+            supertypes: Vec::new(),
+            is_final: true,
+        }
+    }
+
+    pub fn func_type(&self) -> Option<&FuncType> {
+        match &self.composite {
+            CompositeType::Func(func) => Some(func),
+            _ => None,
+        }
+    }
+}
+
+/// The limits of a table or memory. These are 64-bit so that memory64 limits fit.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Limits {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TableType {
+    pub element: ValType,
+    pub limits: Limits,
+    /// Whether the table is indexed with i64s, from the memory64 proposal.
+    pub table64: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MemoryType {
+    pub limits: Limits,
+    pub shared: bool,
+    pub memory64: bool,
+    /// The log2 of the page size, if it's given explicitly with the custom page sizes
+    /// proposal. Otherwise pages are 64KiB, which is a log2 of 16.
+    pub page_size_log2: Option<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GlobalType {
+    pub value: ValType,
+    pub mutable: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ImportKind {
+    /// A function, with the index of its type.
+    Func(u32),
+    Table(TableType),
+    Memory(MemoryType),
+    Global(GlobalType),
+    /// A tag, with the index of its type.
+    Tag(u32),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Import {
+    pub id: Option<String>,
+    pub module: String,
+    pub name: String,
+    pub kind: ImportKind,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExportKind {
+    Func,
+    Table,
+    Memory,
+    Global,
+    Tag,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Export {
+    pub name: String,
+    pub kind: ExportKind,
+    pub index: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Func {
+    pub id: Option<String>,
+    pub type_index: u32,
+    /// The declared locals (not including the params), run-length encoded like the binary
+    /// format does so that huge counts don't need huge allocations.
+    pub locals: Vec<(u32, ValType)>,
+    /// Names for locals, keyed by their index - which does include the params.
+    pub local_ids: BTreeMap<u32, String>,
+    /// The body as a flat list of instructions, including the `end`s of blocks but not the
+    /// function's own `end`.
+    pub body: Vec<Instruction>,
+    /// Hints from the `metadata.code.branch_hint` section, keyed by the index in `body` of
+    /// the `br_if` or `if` they're on: `true` if the branch is likely to be taken.
+    pub branch_hints: BTreeMap<u32, bool>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Table {
+    pub id: Option<String>,
+    pub ty: TableType,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Memory {
+    pub id: Option<String>,
+    pub ty: MemoryType,
+}
+
+/// An exception tag, whose type's params are the values that get thrown with it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Tag {
+    pub id: Option<String>,
+    pub type_index: u32,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Global {
+    pub id: Option<String>,
+    pub ty: GlobalType,
+    /// The initialiser's constant expression, without its `end`.
+    pub init: Vec<Instruction>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElemMode {
+    Passive,
+    Declarative,
+    Active {
+        table: u32,
+        offset: Vec<Instruction>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ElemItems {
+    Functions(Vec<u32>),
+    Expressions(Vec<Vec<Instruction>>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Elem {
+    pub id: Option<String>,
+    pub mode: ElemMode,
+    pub element: ValType,
+    pub items: ElemItems,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum DataMode {
+    Passive,
+    Active {
+        memory: u32,
+        offset: Vec<Instruction>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Data {
+    pub id: Option<String>,
+    pub mode: DataMode,
+    pub bytes: Vec<u8>,
+}
+
+/// A custom section, apart from a `name` section that has been decoded into the `id`s.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Custom {
+    pub name: String,
+    /// The id of the (non-custom) section this came after, or 0 if it came before all of
+    /// them.
+    pub after: u8,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Instruction {
+    pub op: Op,
+    pub immediate: Immediate,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BlockType {
+    Empty,
+    Value(ValType),
+    /// An index into the type section, for multi-value blocks.
+    Type(u32),
+}
+
+/// One of a `try_table`'s clauses: `catch` and `catch_ref` have a tag, `catch_all` and
+/// `catch_all_ref` don't, and the `_ref` ones also push the `exnref`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Catch {
+    pub tag: Option<u32>,
+    pub reference: bool,
+    pub label: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MemArg {
+    /// The alignment as a power of two, as it's stored in the binary format.
+    pub align: u32,
+    pub offset: u64,
+    pub memory: u32,
+}
+
+/// The values of an instruction's immediates - see [`Op::immediate`] for what they mean.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Immediate {
+    None,
+    BlockType(BlockType),
+    Index(u32),
+    /// Two indices, in the order they're encoded in, e.g. `call_indirect`'s type and table.
+    Indices(u32, u32),
+    BrTable {
+        labels: Vec<u32>,
+        default: u32,
+    },
+    MemArg(MemArg),
+    MemArgLane(MemArg, u8),
+    I32(i32),
+    I64(i64),
+    /// Floats are kept as their bits so that NaN payloads survive.
+    F32(u32),
+    F64(u64),
+    V128([u8; 16]),
+    Lane(u8),
+    Shuffle([u8; 16]),
+    ValTypes(Vec<ValType>),
+    HeapType(HeapType),
+    BrOnCast {
+        label: u32,
+        from: RefType,
+        to: RefType,
+    },
+    TryTable {
+        block_type: BlockType,
+        catches: Vec<Catch>,
+    },
+}
+
+impl Module {
+    /// The number of imports of functions, which come first in the function index space.
+    pub fn imported_funcs(&self) -> usize {
+        self.imports
+            .iter()
+            .filter(|i| matches!(i.kind, ImportKind::Func(_)))
+            .count()
+    }
+
+    /// The type of a function, by its index in the function index space.
+    pub fn func_type(&self, index: u32) -> Option<&FuncType> {
+        let ty = self
+            .imports
+            .iter()
+            .filter_map(|i| match i.kind {
+                ImportKind::Func(ty) => Some(ty),
+                _ => None,
+            })
+            .chain(self.funcs.iter().map(|f| f.type_index))
+            .nth(index as usize)?;
+        self.types.get(ty as usize)?.func_type()
+    }
+
+    /// The type of a global, by its index in the global index space.
+    pub fn global_type(&self, index: u32) -> Option<&GlobalType> {
+        self.imports
+            .iter()
+            .filter_map(|i| match &i.kind {
+                ImportKind::Global(ty) => Some(ty),
+                _ => None,
+            })
+            .chain(self.globals.iter().map(|g| &g.ty))
+            .nth(index as usize)
+    }
+
+    /// The type of a memory, by its index in the memory index space.
+    pub fn memory_type(&self, index: u32) -> Option<&MemoryType> {
+        self.imports
+            .iter()
+            .filter_map(|i| match &i.kind {
+                ImportKind::Memory(ty) => Some(ty),
+                _ => None,
+            })
+            .chain(self.memories.iter().map(|m| &m.ty))
+            .nth(index as usize)
+    }
+}
@@ -0,0 +1,2382 @@
+use crate::ast::*;
+use crate::features::{Features, Proposal, Proposal::*};
+use crate::opcodes::{ImmediateKind, Op};
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+/// An error in a binary module, at `offset` bytes from the start of it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DecodeError {
+    pub message: String,
+    pub offset: usize,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at offset 0x{:x})", self.message, self.offset)
+    }
+}
+
+impl TryFrom<&[u8]> for Module {
+    type Error = DecodeError;
+
+    fn try_from(bytes: &[u8]) -> Result<Module, DecodeError> {
+        decode(bytes, Features::default())
+    }
+}
+
+/// Decodes a binary module, rejecting anything from a proposal that isn't in `features`.
+pub fn decode(bytes: &[u8], features: Features) -> Result<Module, DecodeError> {
+    header(bytes)?;
+    let mut module = Module::default();
+    let mut reader = Reader {
+        bytes,
+        pos: 8,
+        features,
+    };
+    let mut last_order = 0;
+    let mut last_id = 0;
+    let mut func_count = None;
+    let mut code_count = None;
+    let mut names = None;
+    let mut branch_hints = None;
+    let mut bodies = Vec::new();
+    while !reader.eof() {
+        let id_offset = reader.pos;
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let start = reader.pos;
+        let end = start
+            .checked_add(size)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| reader.error("length out of bounds"))?;
+        let mut section = Reader {
+            bytes: &bytes[..end],
+            pos: start,
+            features,
+        };
+        if id != 0 {
+            let order = section_order(id).ok_or_else(|| DecodeError {
+                message: "malformed section id".to_string(),
+                offset: id_offset,
+            })?;
+            if order <= last_order {
+                return Err(DecodeError {
+                    message: "section out of order".to_string(),
+                    offset: id_offset,
+                });
+            }
+            last_order = order;
+            last_id = id;
+        }
+        match id {
+            0 => {
+                let name = section.name()?;
+                let data = section.bytes(end - section.pos)?;
+                if name == "name" && names.is_none() {
+                    names = Some(data);
+                } else if name == "metadata.code.branch_hint"
+                    && branch_hints.is_none()
+                    && features.enabled(BranchHinting)
+                {
+                    branch_hints = Some((data, last_id));
+                } else {
+                    module.customs.push(Custom {
+                        name,
+                        after: last_id,
+                        data: data.to_vec(),
+                    });
+                }
+            }
+            1 => {
+                for _ in 0..section.count()? {
+                    let offset = section.pos;
+                    if section.bytes.get(offset) == Some(&0x4E) {
+                        section.require(Gc, offset)?;
+                        section.pos += 1;
+                        let start = module.types.len() as u32;
+                        let count = section.count()?;
+                        for _ in 0..count {
+                            module.types.push(section.sub_type()?);
+                        }
+                        module.rec_groups.push(start..start + count);
+                    } else {
+                        module.types.push(section.sub_type()?);
+                    }
+                }
+            }
+            2 => {
+                for _ in 0..section.count()? {
+                    let module_name = section.name()?;
+                    let name = section.name()?;
+                    let offset = section.pos;
+                    let kind = match section.byte()? {
+                        0x00 => ImportKind::Func(section.u32()?),
+                        0x01 => ImportKind::Table(section.table_type()?),
+                        0x02 => {
+                            let ty = section.memory_type()?;
+                            if module.memory_type(0).is_some() {
+                                section.require(MultiMemory, offset)?;
+                            }
+                            ImportKind::Memory(ty)
+                        }
+                        0x03 => {
+                            let ty = section.global_type()?;
+                            if ty.mutable {
+                                section.require(MutableGlobals, offset)?;
+                            }
+                            ImportKind::Global(ty)
+                        }
+                        0x04 => {
+                            section.require(ExceptionHandling, offset)?;
+                            ImportKind::Tag(section.tag_type()?)
+                        }
+                        _ => return Err(section.error_at(offset, "malformed import kind")),
+                    };
+                    module.imports.push(Import {
+                        id: None,
+                        module: module_name,
+                        name,
+                        kind,
+                    });
+                }
+            }
+            3 => {
+                let count = section.count()?;
+                func_count = Some(count);
+                for _ in 0..count {
+                    module.funcs.push(Func {
+                        id: None,
+                        type_index: section.u32()?,
+                        locals: Vec::new(),
+                        local_ids: Default::default(),
+                        body: Vec::new(),
+                        branch_hints: Default::default(),
+                    });
+                }
+            }
+            4 => {
+                for _ in 0..section.count()? {
+                    let ty = section.table_type()?;
+                    module.tables.push(Table { id: None, ty });
+                }
+            }
+            5 => {
+                for _ in 0..section.count()? {
+                    let offset = section.pos;
+                    let ty = section.memory_type()?;
+                    if module.memory_type(0).is_some() {
+                        section.require(MultiMemory, offset)?;
+                    }
+                    module.memories.push(Memory { id: None, ty });
+                }
+            }
+            6 => {
+                for _ in 0..section.count()? {
+                    let ty = section.global_type()?;
+                    let init = section.const_expression(&module)?;
+                    module.globals.push(Global { id: None, ty, init });
+                }
+            }
+            7 => {
+                for _ in 0..section.count()? {
+                    let name = section.name()?;
+                    let offset = section.pos;
+                    let kind = match section.byte()? {
+                        0x00 => ExportKind::Func,
+                        0x01 => ExportKind::Table,
+                        0x02 => ExportKind::Memory,
+                        0x03 => ExportKind::Global,
+                        0x04 => {
+                            section.require(ExceptionHandling, offset)?;
+                            ExportKind::Tag
+                        }
+                        _ => return Err(section.error_at(offset, "malformed export kind")),
+                    };
+                    let index = section.u32()?;
+                    if kind == ExportKind::Global
+                        && module.global_type(index).is_some_and(|ty| ty.mutable)
+                    {
+                        section.require(MutableGlobals, offset)?;
+                    }
+                    module.exports.push(Export { name, kind, index });
+                }
+            }
+            8 => module.start = Some(section.u32()?),
+            9 => {
+                for _ in 0..section.count()? {
+                    let elem = section.elem(&module)?;
+                    module.elems.push(elem);
+                }
+            }
+            10 => {
+                let offset = section.pos;
+                let count = section.count()?;
+                if count != func_count.unwrap_or(0) {
+                    return Err(section.error_at(
+                        offset,
+                        "function and code section have inconsistent lengths",
+                    ));
+                }
+                code_count = Some(count);
+                for i in 0..count as usize {
+                    let size = section.u32()? as usize;
+                    let body_end = section
+                        .pos
+                        .checked_add(size)
+                        .filter(|body_end| *body_end <= end)
+                        .ok_or_else(|| section.error("unexpected end"))?;
+                    bodies.push(section.pos..body_end);
+                    let mut body = Reader {
+                        bytes: &bytes[..body_end],
+                        pos: section.pos,
+                        features,
+                    };
+                    let mut total: u64 = 0;
+                    let mut locals = Vec::new();
+                    for _ in 0..body.count()? {
+                        let offset = body.pos;
+                        let n = body.u32()?;
+                        total += n as u64;
+                        if total > u32::MAX as u64 {
+                            return Err(body.error_at(offset, "too many locals"));
+                        }
+                        locals.push((n, body.val_type()?));
+                    }
+                    let caller = module.func_type(module.imported_funcs() as u32 + i as u32);
+                    let instructions = body.function_body(&module, caller)?;
+                    if body.pos != body_end {
+                        return Err(body.error("section size mismatch"));
+                    }
+                    module.funcs[i].locals = locals;
+                    module.funcs[i].body = instructions;
+                    section.pos = body_end;
+                }
+            }
+            11 => {
+                let offset = section.pos;
+                let count = section.count()?;
+                if matches!(module.data_count, Some(n) if n != count) {
+                    return Err(section.error_at(
+                        offset,
+                        "data count and data section have inconsistent lengths",
+                    ));
+                }
+                for _ in 0..count {
+                    let data = section.data(&module)?;
+                    module.datas.push(data);
+                }
+            }
+            12 => {
+                section.require(BulkMemory, id_offset)?;
+                module.data_count = Some(section.u32()?);
+            }
+            13 => {
+                section.require(ExceptionHandling, id_offset)?;
+                for _ in 0..section.count()? {
+                    let type_index = section.tag_type()?;
+                    module.tags.push(Tag {
+                        id: None,
+                        type_index,
+                    });
+                }
+            }
+            _ => unreachable!(),
+        }
+        if section.pos != end {
+            return Err(section.error("section size mismatch"));
+        }
+        reader.pos = end;
+    }
+    if func_count.unwrap_or(0) != code_count.unwrap_or(0) {
+        return Err(reader.error("function and code section have inconsistent lengths"));
+    }
+    if matches!(module.data_count, Some(n) if n != 0 && module.datas.is_empty()) {
+        return Err(reader.error("data count and data section have inconsistent lengths"));
+    }
+    if let Some(data) = names {
+        // the name section is only a hint, so if it's malformed it just stays as it is
+        if let Some(names) = decode_names(data) {
+            for (kind, index, name) in names {
+                set_id(&mut module, kind, index, name);
+            }
+        } else {
+            module.customs.push(Custom {
+                name: "name".to_string(),
+                after: last_id,
+                data: data.to_vec(),
+            });
+        }
+    }
+    if let Some((data, after)) = branch_hints {
+        // likewise for branch hints, which can't change what the module does
+        match decode_branch_hints(bytes, data, &module, &bodies) {
+            Some(hints) => {
+                for (func, hints) in hints {
+                    module.funcs[func].branch_hints = hints;
+                }
+            }
+            None => module.customs.push(Custom {
+                name: "metadata.code.branch_hint".to_string(),
+                after,
+                data: data.to_vec(),
+            }),
+        }
+    }
+    Ok(module)
+}
+
+/// Where a section is in a binary module, as returned by [`sections`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Section {
+    pub id: u8,
+    /// The name of a custom section.
+    pub name: Option<String>,
+    /// The whole section, including its id and size.
+    pub range: Range<usize>,
+    /// The section's contents, after its size - and for a custom section, after its name too.
+    pub contents: Range<usize>,
+}
+
+/// Splits a binary module into its sections, without decoding their contents.
+pub fn sections(bytes: &[u8]) -> Result<Vec<Section>, DecodeError> {
+    header(bytes)?;
+    let mut reader = Reader {
+        bytes,
+        pos: 8,
+        features: Features::all(),
+    };
+    let mut sections = Vec::new();
+    while !reader.eof() {
+        let start = reader.pos;
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let end = reader
+            .pos
+            .checked_add(size)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(|| reader.error("length out of bounds"))?;
+        let name = match id {
+            0 => {
+                let mut section = Reader {
+                    bytes: &bytes[..end],
+                    pos: reader.pos,
+                    features: reader.features,
+                };
+                let name = section.name()?;
+                reader.pos = section.pos;
+                Some(name)
+            }
+            _ if section_order(id).is_none() => {
+                return Err(reader.error_at(start, "malformed section id"));
+            }
+            _ => None,
+        };
+        sections.push(Section {
+            id,
+            name,
+            range: start..end,
+            contents: reader.pos..end,
+        });
+        reader.pos = end;
+    }
+    Ok(sections)
+}
+
+/// The byte range of each function body (including its locals) in a code section.
+pub fn function_bodies(bytes: &[u8], code: &Section) -> Result<Vec<Range<usize>>, DecodeError> {
+    let mut reader = Reader {
+        bytes: &bytes[..code.contents.end],
+        pos: code.contents.start,
+        features: Features::all(),
+    };
+    let mut bodies = Vec::new();
+    for _ in 0..reader.count()? {
+        let size = reader.u32()? as usize;
+        let start = reader.pos;
+        reader.bytes(size)?;
+        bodies.push(start..reader.pos);
+    }
+    Ok(bodies)
+}
+
+fn header(bytes: &[u8]) -> Result<(), DecodeError> {
+    if bytes.len() < 4 || bytes[..4] != *b"\0asm" {
+        return Err(DecodeError {
+            message: "magic header not detected".to_string(),
+            offset: 0,
+        });
+    }
+    if bytes.len() < 8 {
+        return Err(DecodeError {
+            message: "unexpected end".to_string(),
+            offset: bytes.len(),
+        });
+    }
+    if bytes[4..8] != [1, 0, 0, 0] {
+        return Err(DecodeError {
+            message: "unknown binary version".to_string(),
+            offset: 4,
+        });
+    }
+    Ok(())
+}
+
+/// Where a section has to come, relative to the others; custom sections can go anywhere.
+fn section_order(id: u8) -> Option<u8> {
+    // the tag section goes between the memory and global sections, and the data count
+    // section between the element and code sections
+    const ORDER: [u8; 13] = [1, 2, 3, 4, 5, 13, 6, 7, 8, 9, 12, 10, 11];
+    let position = ORDER.iter().position(|order| *order == id)?;
+    Some(position as u8 + 1)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    features: Features,
+}
+
+impl<'a> Reader<'a> {
+    fn eof(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn error(&self, message: &str) -> DecodeError {
+        self.error_at(self.pos, message)
+    }
+
+    fn error_at(&self, offset: usize, message: &str) -> DecodeError {
+        DecodeError {
+            message: message.to_string(),
+            offset,
+        }
+    }
+
+    fn require(&self, proposal: Proposal, offset: usize) -> Result<(), DecodeError> {
+        self.features
+            .require(proposal)
+            .map_err(|proposal| DecodeError {
+                message: format!("the `{}` proposal is not enabled", proposal),
+                offset,
+            })
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        match self.bytes.get(self.pos) {
+            Some(byte) => {
+                self.pos += 1;
+                Ok(*byte)
+            }
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        match self
+            .pos
+            .checked_add(n)
+            .filter(|end| *end <= self.bytes.len())
+        {
+            Some(end) => {
+                let bytes = &self.bytes[self.pos..end];
+                self.pos = end;
+                Ok(bytes)
+            }
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.bytes(N)?);
+        Ok(array)
+    }
+
+    /// Reads a LEB128 integer of the given width. Encodings longer than `ceil(bits / 7)` bytes
+    /// are rejected as "integer representation too long", and unused bits in the last byte
+    /// that aren't zero (or, for signed integers, a sign extension) as "integer too large".
+    fn leb(&mut self, bits: u32, signed: bool) -> Result<u64, DecodeError> {
+        let max_bytes = bits.div_ceil(7);
+        let mut result: u64 = 0;
+        let mut shift = 0;
+        for i in 0..max_bytes {
+            let offset = self.pos;
+            let byte = self.byte()?;
+            result |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 != 0 {
+                continue;
+            }
+            if i == max_bytes - 1 {
+                let used = bits - 7 * (max_bytes - 1);
+                let unused = (byte & 0x7f) >> (used - signed as u32);
+                let all_ones = 0x7f >> (used - signed as u32);
+                if unused != 0 && !(signed && unused == all_ones) {
+                    return Err(self.error_at(offset, "integer too large"));
+                }
+            }
+            if signed && shift < 64 && byte & 0x40 != 0 {
+                result |= !0 << shift;
+            }
+            return Ok(result);
+        }
+        Err(self.error_at(self.pos - 1, "integer representation too long"))
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(self.leb(32, false)? as u32)
+    }
+
+    fn u64(&mut self) -> Result<u64, DecodeError> {
+        self.leb(64, false)
+    }
+
+    /// The length of a vector, which is checked against the number of bytes left so that a
+    /// bogus length can't make anything allocate loads of memory.
+    fn count(&mut self) -> Result<u32, DecodeError> {
+        let offset = self.pos;
+        let count = self.u32()?;
+        if count as usize > self.bytes.len() - self.pos {
+            return Err(self.error_at(offset, "unexpected end"));
+        }
+        Ok(count)
+    }
+
+    fn name(&mut self) -> Result<String, DecodeError> {
+        let len = self.u32()? as usize;
+        let offset = self.pos;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| self.error_at(offset, "malformed UTF-8 encoding"))
+    }
+
+    fn val_type(&mut self) -> Result<ValType, DecodeError> {
+        let offset = self.pos;
+        let ty = match self.byte()? {
+            0x7F => ValType::I32,
+            0x7E => ValType::I64,
+            0x7D => ValType::F32,
+            0x7C => ValType::F64,
+            0x7B => {
+                self.require(Simd, offset)?;
+                ValType::V128
+            }
+            0x70 => {
+                self.require(ReferenceTypes, offset)?;
+                ValType::FuncRef
+            }
+            0x6F => {
+                self.require(ReferenceTypes, offset)?;
+                ValType::ExternRef
+            }
+            0x63 | 0x64 | 0x69..=0x74 => {
+                self.pos = offset;
+                self.ref_type()?
+            }
+            _ => return Err(self.error_at(offset, "malformed value type")),
+        };
+        Ok(ty)
+    }
+
+    fn val_types(&mut self) -> Result<Vec<ValType>, DecodeError> {
+        let count = self.count()?;
+        let mut types = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            types.push(self.val_type()?);
+        }
+        Ok(types)
+    }
+
+    /// Reads a reference type, which is either `ref`/`ref null` and a heap type or one of the
+    /// shorthands for a nullable abstract one, like `funcref`.
+    fn ref_type(&mut self) -> Result<ValType, DecodeError> {
+        let offset = self.pos;
+        let (nullable, heap) = match self.bytes.get(offset) {
+            Some(prefix @ (0x63 | 0x64)) => {
+                self.require(FunctionReferences, offset)?;
+                self.pos += 1;
+                (*prefix == 0x63, self.heap_type()?)
+            }
+            Some(0x69..=0x74) => (true, self.heap_type()?),
+            Some(_) => return Err(self.error_at(offset, "malformed reference type")),
+            None => return Err(self.error("unexpected end")),
+        };
+        Ok(match (nullable, heap) {
+            (true, HeapType::Func) => ValType::FuncRef,
+            (true, HeapType::Extern) => {
+                self.require(ReferenceTypes, offset)?;
+                ValType::ExternRef
+            }
+            (true, HeapType::Exn) => ValType::ExnRef,
+            (nullable, heap) => ValType::Ref(RefType { nullable, heap }),
+        })
+    }
+
+    /// Reads a heap type, which is either an abstract one or a type index as an s33.
+    fn heap_type(&mut self) -> Result<HeapType, DecodeError> {
+        let offset = self.pos;
+        let (heap, proposal) = match self.bytes.get(offset) {
+            Some(0x70) => (HeapType::Func, None),
+            Some(0x6F) => (HeapType::Extern, None),
+            Some(0x69) => (HeapType::Exn, Some(ExceptionHandling)),
+            Some(0x74) => (HeapType::NoExn, Some(ExceptionHandling)),
+            Some(0x6E) => (HeapType::Any, Some(Gc)),
+            Some(0x6D) => (HeapType::Eq, Some(Gc)),
+            Some(0x6C) => (HeapType::I31, Some(Gc)),
+            Some(0x6B) => (HeapType::Struct, Some(Gc)),
+            Some(0x6A) => (HeapType::Array, Some(Gc)),
+            Some(0x71) => (HeapType::None, Some(Gc)),
+            Some(0x72) => (HeapType::NoExtern, Some(Gc)),
+            Some(0x73) => (HeapType::NoFunc, Some(Gc)),
+            _ => {
+                let index = self.leb(33, true)? as i64;
+                if index < 0 {
+                    return Err(self.error_at(offset, "malformed heap type"));
+                }
+                self.require(FunctionReferences, offset)?;
+                return Ok(HeapType::Type(index as u32));
+            }
+        };
+        if let Some(proposal) = proposal {
+            self.require(proposal, offset)?;
+        }
+        self.pos += 1;
+        Ok(heap)
+    }
+
+    /// Reads a type section entry, which is a composite type with an optional `sub` or
+    /// `sub final` before it listing its supertypes.
+    fn sub_type(&mut self) -> Result<TypeDef, DecodeError> {
+        let offset = self.pos;
+        let (supertypes, is_final) = match self.bytes.get(offset) {
+            Some(kind @ (0x4F | 0x50)) => {
+                self.require(Gc, offset)?;
+                let is_final = *kind == 0x4F;
+                self.pos += 1;
+                let count = self.count()?;
+                let mut supertypes = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    supertypes.push(self.u32()?);
+                }
+                (supertypes, is_final)
+            }
+            _ => (Vec::new(), true),
+        };
+        let offset = self.pos;
+        let composite = match self.byte()? {
+            0x60 => {
+                let params = self.val_types()?;
+                let results_offset = self.pos;
+                let results = self.val_types()?;
+                if results.len() > 1 {
+                    self.require(MultiValue, results_offset)?;
+                }
+                CompositeType::Func(FuncType { params, results })
+            }
+            0x5F => {
+                self.require(Gc, offset)?;
+                let count = self.count()?;
+                let mut fields = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    fields.push(self.field_type()?);
+                }
+                CompositeType::Struct(fields)
+            }
+            0x5E => {
+                self.require(Gc, offset)?;
+                CompositeType::Array(self.field_type()?)
+            }
+            _ => return Err(self.error_at(offset, "malformed function type")),
+        };
+        Ok(TypeDef {
+            id: None,
+            composite,
+            supertypes,
+            is_final,
+        })
+    }
+
+    fn field_type(&mut self) -> Result<FieldType, DecodeError> {
+        let storage = match self.bytes.get(self.pos) {
+            Some(0x78) => StorageType::I8,
+            Some(0x77) => StorageType::I16,
+            _ => StorageType::Val(self.val_type()?),
+        };
+        if let StorageType::I8 | StorageType::I16 = storage {
+            self.pos += 1;
+        }
+        Ok(FieldType {
+            storage,
+            mutable: self.mutability()?,
+        })
+    }
+
+    /// Reads limits, returning the flags byte along with them so that the caller can pick out
+    /// the bits that are specific to tables or memories.
+    fn limits(&mut self, memory: bool) -> Result<(Limits, u8), DecodeError> {
+        let offset = self.pos;
+        let flags = self.byte()?;
+        let allowed = if memory { 0x0F } else { 0x05 };
+        if flags & !allowed != 0 {
+            return Err(self.error_at(offset, "malformed limits flags"));
+        }
+        let shared = flags & 0x02 != 0;
+        let is64 = flags & 0x04 != 0;
+        if shared {
+            self.require(Threads, offset)?;
+        }
+        if is64 {
+            self.require(Memory64, offset)?;
+        }
+        if flags & 0x08 != 0 {
+            self.require(CustomPageSizes, offset)?;
+        }
+        let min = self.limit(is64)?;
+        let max = if flags & 0x01 != 0 {
+            Some(self.limit(is64)?)
+        } else {
+            None
+        };
+        if shared && max.is_none() {
+            return Err(self.error_at(offset, "shared memory must have maximum"));
+        }
+        Ok((Limits { min, max }, flags))
+    }
+
+    fn limit(&mut self, is64: bool) -> Result<u64, DecodeError> {
+        if is64 {
+            self.u64()
+        } else {
+            self.u32().map(u64::from)
+        }
+    }
+
+    fn table_type(&mut self) -> Result<TableType, DecodeError> {
+        let element = self.ref_type()?;
+        let (limits, flags) = self.limits(false)?;
+        let table64 = flags & 0x04 != 0;
+        Ok(TableType {
+            element,
+            limits,
+            table64,
+        })
+    }
+
+    fn memory_type(&mut self) -> Result<MemoryType, DecodeError> {
+        let (limits, flags) = self.limits(true)?;
+        let page_size_log2 = if flags & 0x08 != 0 {
+            let offset = self.pos;
+            let log2 = self.u32()?;
+            if log2 != 0 && log2 != 16 {
+                return Err(self.error_at(offset, "invalid custom page size"));
+            }
+            Some(log2)
+        } else {
+            None
+        };
+        Ok(MemoryType {
+            limits,
+            shared: flags & 0x02 != 0,
+            memory64: flags & 0x04 != 0,
+            page_size_log2,
+        })
+    }
+
+    /// A tag's type, which is an attribute (that's always 0, for exceptions) and a type index.
+    fn tag_type(&mut self) -> Result<u32, DecodeError> {
+        let offset = self.pos;
+        if self.byte()? != 0x00 {
+            return Err(self.error_at(offset, "malformed tag attribute"));
+        }
+        self.u32()
+    }
+
+    fn global_type(&mut self) -> Result<GlobalType, DecodeError> {
+        let value = self.val_type()?;
+        let mutable = self.mutability()?;
+        Ok(GlobalType { value, mutable })
+    }
+
+    fn mutability(&mut self) -> Result<bool, DecodeError> {
+        let offset = self.pos;
+        match self.byte()? {
+            0x00 => Ok(false),
+            0x01 => Ok(true),
+            _ => Err(self.error_at(offset, "malformed mutability")),
+        }
+    }
+
+    fn elem(&mut self, module: &Module) -> Result<Elem, DecodeError> {
+        let offset = self.pos;
+        let flags = self.u32()?;
+        if flags > 7 {
+            return Err(self.error_at(offset, "malformed elements segment kind"));
+        }
+        if flags != 0 {
+            self.require(BulkMemory, offset)?;
+        }
+        let mode = match flags & 0x03 {
+            0x01 => ElemMode::Passive,
+            0x03 => ElemMode::Declarative,
+            _ => {
+                let table = if flags & 0x02 != 0 { self.u32()? } else { 0 };
+                let offset = self.const_expression(module)?;
+                ElemMode::Active { table, offset }
+            }
+        };
+        let uses_expressions = flags & 0x04 != 0;
+        let element = if flags & 0x03 == 0 {
+            ValType::FuncRef
+        } else if uses_expressions {
+            self.ref_type()?
+        } else {
+            let offset = self.pos;
+            if self.byte()? != 0x00 {
+                return Err(self.error_at(offset, "malformed elements segment kind"));
+            }
+            ValType::FuncRef
+        };
+        let count = self.count()?;
+        let items = if uses_expressions {
+            let mut exprs = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                exprs.push(self.const_expression(module)?);
+            }
+            ElemItems::Expressions(exprs)
+        } else {
+            let mut funcs = Vec::with_capacity(count as usize);
+            for _ in 0..count {
+                funcs.push(self.u32()?);
+            }
+            ElemItems::Functions(funcs)
+        };
+        Ok(Elem {
+            id: None,
+            mode,
+            element,
+            items,
+        })
+    }
+
+    fn data(&mut self, module: &Module) -> Result<Data, DecodeError> {
+        let offset = self.pos;
+        let mode = match self.u32()? {
+            0 => DataMode::Active {
+                memory: 0,
+                offset: self.const_expression(module)?,
+            },
+            1 => {
+                self.require(BulkMemory, offset)?;
+                DataMode::Passive
+            }
+            2 => {
+                self.require(BulkMemory, offset)?;
+                DataMode::Active {
+                    memory: self.u32()?,
+                    offset: self.const_expression(module)?,
+                }
+            }
+            _ => return Err(self.error_at(offset, "malformed data segment kind")),
+        };
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?.to_vec();
+        Ok(Data {
+            id: None,
+            mode,
+            bytes,
+        })
+    }
+
+    /// Reads a constant expression up to and including its `end`, checking that each of its
+    /// instructions is allowed in one.
+    fn const_expression(&mut self, module: &Module) -> Result<Vec<Instruction>, DecodeError> {
+        let mut instructions = Vec::new();
+        loop {
+            let offset = self.pos;
+            let instruction = self.instruction(module)?;
+            match (instruction.op, &instruction.immediate) {
+                (Op::End, _) => return Ok(instructions),
+                (
+                    Op::I32Const
+                    | Op::I64Const
+                    | Op::F32Const
+                    | Op::F64Const
+                    | Op::V128Const
+                    | Op::RefNull
+                    | Op::RefFunc
+                    | Op::StructNew
+                    | Op::StructNewDefault
+                    | Op::ArrayNew
+                    | Op::ArrayNewDefault
+                    | Op::ArrayNewFixed
+                    | Op::RefI31
+                    | Op::AnyConvertExtern
+                    | Op::ExternConvertAny,
+                    _,
+                ) => {}
+                (
+                    Op::I32Add | Op::I32Sub | Op::I32Mul | Op::I64Add | Op::I64Sub | Op::I64Mul,
+                    _,
+                ) => self.require(ExtendedConst, offset)?,
+                (Op::GlobalGet, Immediate::Index(index)) => {
+                    // before GC, only imported globals can be used
+                    let imported = module
+                        .imports
+                        .iter()
+                        .filter(|i| matches!(i.kind, ImportKind::Global(_)))
+                        .count();
+                    if *index as usize >= imported && !self.features.enabled(Gc) {
+                        return Err(self.error_at(offset, "unknown global"));
+                    }
+                    match module.global_type(*index) {
+                        Some(ty) if ty.mutable => {
+                            return Err(self.error_at(offset, "constant expression required"));
+                        }
+                        Some(_) => {}
+                        None => return Err(self.error_at(offset, "unknown global")),
+                    }
+                }
+                _ => return Err(self.error_at(offset, "constant expression required")),
+            }
+            instructions.push(instruction);
+        }
+    }
+
+    /// Reads the instructions of the body of a function of type `caller` up to and including
+    /// the `end` that closes it. Any tail calls have to return the same results as `caller`.
+    fn function_body(
+        &mut self,
+        module: &Module,
+        caller: Option<&FuncType>,
+    ) -> Result<Vec<Instruction>, DecodeError> {
+        let mut depth = 0;
+        let mut instructions = Vec::new();
+        loop {
+            let offset = self.pos;
+            let instruction = self.instruction(module)?;
+            match instruction.op {
+                Op::Block | Op::Loop | Op::If | Op::Try | Op::TryTable => depth += 1,
+                Op::End if depth == 0 => return Ok(instructions),
+                // `delegate` ends a `try` instead of an `end`
+                Op::Delegate if depth == 0 => {
+                    return Err(self.error_at(offset, "delegate outside of a try"));
+                }
+                Op::End | Op::Delegate => depth -= 1,
+                _ => {}
+            }
+            let callee = match (instruction.op, &instruction.immediate) {
+                (Op::ReturnCall, Immediate::Index(func)) => module.func_type(*func),
+                (Op::ReturnCallIndirect, Immediate::Indices(ty, _))
+                | (Op::ReturnCallRef, Immediate::Index(ty)) => {
+                    module.types.get(*ty as usize).and_then(TypeDef::func_type)
+                }
+                _ => None,
+            };
+            if let (Some(caller), Some(callee)) = (caller, callee) {
+                if callee.results != caller.results {
+                    return Err(self.error_at(
+                        offset,
+                        "type mismatch: tail call results don't match the caller's",
+                    ));
+                }
+            }
+            instructions.push(instruction);
+        }
+    }
+
+    fn instruction(&mut self, module: &Module) -> Result<Instruction, DecodeError> {
+        let offset = self.pos;
+        let byte = self.byte()?;
+        let op = if let 0xFB..=0xFE = byte {
+            let code = self.u32()?;
+            Op::from_code(Some(byte), code).ok_or_else(|| {
+                self.error_at(offset, &format!("unknown opcode 0x{:02x} {}", byte, code))
+            })?
+        } else {
+            Op::from_code(None, byte as u32)
+                .ok_or_else(|| self.error_at(offset, &format!("unknown opcode 0x{:02x}", byte)))?
+        };
+        if let Some(proposal) = op.proposal() {
+            self.require(proposal, offset)?;
+        }
+        if op == Op::ReturnCallRef {
+            self.require(FunctionReferences, offset)?;
+        }
+        if matches!(
+            op,
+            Op::MemoryInit | Op::DataDrop | Op::ArrayNewData | Op::ArrayInitData
+        ) && module.data_count.is_none()
+        {
+            return Err(self.error_at(offset, "data count section required"));
+        }
+        let immediate = match op.immediate() {
+            ImmediateKind::None => Immediate::None,
+            ImmediateKind::BlockType => Immediate::BlockType(self.block_type()?),
+            ImmediateKind::Label
+            | ImmediateKind::Func
+            | ImmediateKind::Local
+            | ImmediateKind::Global
+            | ImmediateKind::Table
+            | ImmediateKind::Data
+            | ImmediateKind::Elem
+            | ImmediateKind::Tag
+            | ImmediateKind::Type => Immediate::Index(self.u32()?),
+            ImmediateKind::TypeField
+            | ImmediateKind::TypeData
+            | ImmediateKind::TypeElem
+            | ImmediateKind::TypeType
+            | ImmediateKind::ArrayNewFixed => {
+                let first = self.u32()?;
+                Immediate::Indices(first, self.u32()?)
+            }
+            ImmediateKind::Memory => Immediate::Index(self.memory_index()?),
+            ImmediateKind::BrTable => {
+                let count = self.count()?;
+                let mut labels = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    labels.push(self.u32()?);
+                }
+                Immediate::BrTable {
+                    labels,
+                    default: self.u32()?,
+                }
+            }
+            ImmediateKind::CallIndirect => {
+                let type_index = self.u32()?;
+                let table = if self.features.enabled(ReferenceTypes) {
+                    self.u32()?
+                } else {
+                    self.zero_byte()?
+                };
+                Immediate::Indices(type_index, table)
+            }
+            ImmediateKind::MemArg => Immediate::MemArg(self.mem_arg(module, op)?),
+            ImmediateKind::MemArgLane => {
+                let mem_arg = self.mem_arg(module, op)?;
+                Immediate::MemArgLane(mem_arg, self.byte()?)
+            }
+            ImmediateKind::I32 => Immediate::I32(self.leb(32, true)? as i32),
+            ImmediateKind::I64 => Immediate::I64(self.leb(64, true)? as i64),
+            ImmediateKind::F32 => Immediate::F32(u32::from_le_bytes(self.array()?)),
+            ImmediateKind::F64 => Immediate::F64(u64::from_le_bytes(self.array()?)),
+            ImmediateKind::V128 => Immediate::V128(self.array()?),
+            ImmediateKind::Lane => Immediate::Lane(self.byte()?),
+            ImmediateKind::Shuffle => Immediate::Shuffle(self.array()?),
+            ImmediateKind::SelectTypes => Immediate::ValTypes(self.val_types()?),
+            ImmediateKind::HeapType | ImmediateKind::RefType => {
+                Immediate::HeapType(self.heap_type()?)
+            }
+            ImmediateKind::BrOnCast => {
+                let offset = self.pos;
+                let flags = self.byte()?;
+                if flags > 0x03 {
+                    return Err(self.error_at(offset, "malformed br_on_cast flags"));
+                }
+                let label = self.u32()?;
+                let from = RefType {
+                    nullable: flags & 0x01 != 0,
+                    heap: self.heap_type()?,
+                };
+                let to = RefType {
+                    nullable: flags & 0x02 != 0,
+                    heap: self.heap_type()?,
+                };
+                Immediate::BrOnCast { label, from, to }
+            }
+            ImmediateKind::MemoryInit => {
+                let data = self.u32()?;
+                Immediate::Indices(data, self.memory_index()?)
+            }
+            ImmediateKind::MemoryCopy => {
+                let dst = self.memory_index()?;
+                Immediate::Indices(dst, self.memory_index()?)
+            }
+            ImmediateKind::TableInit | ImmediateKind::TableCopy => {
+                let first = self.u32()?;
+                Immediate::Indices(first, self.u32()?)
+            }
+            ImmediateKind::AtomicFence => {
+                self.zero_byte()?;
+                Immediate::None
+            }
+            ImmediateKind::TryTable => {
+                let block_type = self.block_type()?;
+                let count = self.count()?;
+                let mut catches = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let offset = self.pos;
+                    let kind = self.byte()?;
+                    let tag = match kind {
+                        0x00 | 0x01 => Some(self.u32()?),
+                        0x02 | 0x03 => None,
+                        _ => return Err(self.error_at(offset, "malformed catch clause")),
+                    };
+                    catches.push(Catch {
+                        tag,
+                        reference: kind & 0x01 != 0,
+                        label: self.u32()?,
+                    });
+                }
+                Immediate::TryTable {
+                    block_type,
+                    catches,
+                }
+            }
+        };
+        Ok(Instruction { op, immediate })
+    }
+
+    fn zero_byte(&mut self) -> Result<u32, DecodeError> {
+        let offset = self.pos;
+        if self.byte()? != 0 {
+            return Err(self.error_at(offset, "zero byte expected"));
+        }
+        Ok(0)
+    }
+
+    /// Before multi-memory, memory indices are a single zero byte rather than a LEB128.
+    fn memory_index(&mut self) -> Result<u32, DecodeError> {
+        if self.features.enabled(MultiMemory) {
+            self.u32()
+        } else {
+            self.zero_byte()
+        }
+    }
+
+    /// Reads a memarg, checking its alignment against `op`'s natural one: it can't be any
+    /// bigger, and atomics have to use exactly that. The offset has to fit in the memory's
+    /// address type too.
+    fn mem_arg(&mut self, module: &Module, op: Op) -> Result<MemArg, DecodeError> {
+        let align_offset = self.pos;
+        let mut align = self.u32()?;
+        let mut memory = 0;
+        // bit 6 of the alignment says that there's a memory index
+        if align & 0x40 != 0 && self.features.enabled(MultiMemory) {
+            align &= !0x40;
+            memory = self.u32()?;
+        }
+        let offset_start = self.pos;
+        let offset = if self.features.enabled(Memory64) {
+            self.u64()?
+        } else {
+            self.u32()? as u64
+        };
+        let memory64 = module.memory_type(memory).is_some_and(|ty| ty.memory64);
+        if !memory64 && offset > u32::MAX as u64 {
+            return Err(self.error_at(offset_start, "offset out of range"));
+        }
+        let natural = op.natural_alignment().unwrap_or(0);
+        if op.prefix() == Some(0xFE) && align != natural {
+            return Err(self.error_at(align_offset, "alignment must be exactly natural"));
+        }
+        if align > natural {
+            return Err(self.error_at(align_offset, "alignment must not be larger than natural"));
+        }
+        Ok(MemArg {
+            align,
+            offset,
+            memory,
+        })
+    }
+
+    fn block_type(&mut self) -> Result<BlockType, DecodeError> {
+        let offset = self.pos;
+        match self.bytes.get(self.pos) {
+            Some(0x40) => {
+                self.pos += 1;
+                Ok(BlockType::Empty)
+            }
+            Some(0x63 | 0x64 | 0x69..=0x7F) => Ok(BlockType::Value(self.val_type()?)),
+            _ => {
+                let index = self.leb(33, true)? as i64;
+                if index < 0 {
+                    return Err(self.error_at(offset, "malformed block type"));
+                }
+                self.require(MultiValue, offset)?;
+                Ok(BlockType::Type(index as u32))
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum NameKind {
+    Module,
+    Func,
+    Local(u32),
+    Type,
+    Table,
+    Memory,
+    Global,
+    Elem,
+    Data,
+    Tag,
+}
+
+/// Reads the `name` section's subsections, or returns `None` if they're malformed.
+fn decode_names(data: &[u8]) -> Option<Vec<(NameKind, u32, String)>> {
+    let mut reader = Reader {
+        bytes: data,
+        pos: 0,
+        features: Features::default(),
+    };
+    let mut names = Vec::new();
+    while !reader.eof() {
+        let id = reader.byte().ok()?;
+        let size = reader.u32().ok()? as usize;
+        let end = reader.pos.checked_add(size)?;
+        let mut sub = Reader {
+            bytes: data.get(..end)?,
+            pos: reader.pos,
+            features: reader.features,
+        };
+        let kind = match id {
+            0 => {
+                names.push((NameKind::Module, 0, sub.name().ok()?));
+                None
+            }
+            1 => Some(NameKind::Func),
+            2 => {
+                for _ in 0..sub.count().ok()? {
+                    let func = sub.u32().ok()?;
+                    for _ in 0..sub.count().ok()? {
+                        let index = sub.u32().ok()?;
+                        names.push((NameKind::Local(func), index, sub.name().ok()?));
+                    }
+                }
+                None
+            }
+            4 => Some(NameKind::Type),
+            5 => Some(NameKind::Table),
+            6 => Some(NameKind::Memory),
+            7 => Some(NameKind::Global),
+            8 => Some(NameKind::Elem),
+            9 => Some(NameKind::Data),
+            11 => Some(NameKind::Tag),
+            _ => {
+                sub.pos = end;
+                None
+            }
+        };
+        if let Some(kind) = kind {
+            for _ in 0..sub.count().ok()? {
+                let index = sub.u32().ok()?;
+                names.push((kind, index, sub.name().ok()?));
+            }
+        }
+        if sub.pos != end {
+            return None;
+        }
+        reader.pos = end;
+    }
+    Some(names)
+}
+
+/// Reads the `metadata.code.branch_hint` section, and works out which instruction of its
+/// function each hint is on from its byte offset, which is from the start of the function's
+/// body in `bodies` (i.e. its locals). Returns `None` if the section is malformed, or if a hint
+/// isn't on a `br_if` or `if`.
+fn decode_branch_hints(
+    bytes: &[u8],
+    data: &[u8],
+    module: &Module,
+    bodies: &[Range<usize>],
+) -> Option<Vec<(usize, BTreeMap<u32, bool>)>> {
+    let mut reader = Reader {
+        bytes: data,
+        pos: 0,
+        features: Features::all(),
+    };
+    let imported = module.imported_funcs();
+    let mut funcs = Vec::new();
+    let mut last_func = None;
+    for _ in 0..reader.count().ok()? {
+        // functions and the hints within them have to be in increasing order
+        let func = reader.u32().ok()? as usize;
+        if last_func.is_some_and(|last| func <= last) {
+            return None;
+        }
+        last_func = Some(func);
+        let body = bodies.get(func.checked_sub(imported)?)?;
+        let mut body_reader = Reader {
+            bytes: &bytes[..body.end],
+            pos: body.start,
+            features: Features::all(),
+        };
+        for _ in 0..body_reader.count().ok()? {
+            body_reader.u32().ok()?;
+            body_reader.val_type().ok()?;
+        }
+        let mut hints = BTreeMap::new();
+        let mut index = 0;
+        for _ in 0..reader.count().ok()? {
+            let offset = body.start.checked_add(reader.u32().ok()? as usize)?;
+            if reader.u32().ok()? != 1 {
+                return None;
+            }
+            let taken = match reader.byte().ok()? {
+                0 => false,
+                1 => true,
+                _ => return None,
+            };
+            while body_reader.pos < offset {
+                body_reader.instruction(module).ok()?;
+                index += 1;
+            }
+            if body_reader.pos != offset {
+                return None;
+            }
+            let instruction = body_reader.instruction(module).ok()?;
+            if !matches!(instruction.op, Op::BrIf | Op::If) {
+                return None;
+            }
+            hints.insert(index, taken);
+            index += 1;
+        }
+        funcs.push((func - imported, hints));
+    }
+    reader.eof().then_some(funcs)
+}
+
+/// Finds the import that `index` refers to in an index space that starts with imports, or
+/// otherwise where `index` is in the module's own definitions.
+fn import_id(
+    imports: &mut [Import],
+    matches: fn(&ImportKind) -> bool,
+    index: usize,
+) -> Result<&mut Option<String>, usize> {
+    let mut imports = imports.iter_mut().filter(|i| matches(&i.kind));
+    let mut count = 0;
+    for import in imports.by_ref() {
+        if count == index {
+            return Ok(&mut import.id);
+        }
+        count += 1;
+    }
+    Err(index - count)
+}
+
+fn set_id(module: &mut Module, kind: NameKind, index: u32, name: String) {
+    let index = index as usize;
+    let id = match kind {
+        NameKind::Module => Some(&mut module.id),
+        NameKind::Type => module.types.get_mut(index).map(|t| &mut t.id),
+        NameKind::Elem => module.elems.get_mut(index).map(|e| &mut e.id),
+        NameKind::Data => module.datas.get_mut(index).map(|d| &mut d.id),
+        NameKind::Func => {
+            match import_id(
+                &mut module.imports,
+                |k| matches!(k, ImportKind::Func(_)),
+                index,
+            ) {
+                Ok(id) => Some(id),
+                Err(i) => module.funcs.get_mut(i).map(|f| &mut f.id),
+            }
+        }
+        NameKind::Table => {
+            match import_id(
+                &mut module.imports,
+                |k| matches!(k, ImportKind::Table(_)),
+                index,
+            ) {
+                Ok(id) => Some(id),
+                Err(i) => module.tables.get_mut(i).map(|t| &mut t.id),
+            }
+        }
+        NameKind::Memory => {
+            match import_id(
+                &mut module.imports,
+                |k| matches!(k, ImportKind::Memory(_)),
+                index,
+            ) {
+                Ok(id) => Some(id),
+                Err(i) => module.memories.get_mut(i).map(|m| &mut m.id),
+            }
+        }
+        NameKind::Global => {
+            match import_id(
+                &mut module.imports,
+                |k| matches!(k, ImportKind::Global(_)),
+                index,
+            ) {
+                Ok(id) => Some(id),
+                Err(i) => module.globals.get_mut(i).map(|g| &mut g.id),
+            }
+        }
+        NameKind::Tag => {
+            match import_id(
+                &mut module.imports,
+                |k| matches!(k, ImportKind::Tag(_)),
+                index,
+            ) {
+                Ok(id) => Some(id),
+                Err(i) => module.tags.get_mut(i).map(|t| &mut t.id),
+            }
+        }
+        NameKind::Local(func) => {
+            let imported = module.imported_funcs();
+            if let Some(func) = (func as usize)
+                .checked_sub(imported)
+                .and_then(|f| module.funcs.get_mut(f))
+            {
+                func.local_ids.insert(index as u32, name);
+            }
+            return;
+        }
+    };
+    if let Some(id) = id {
+        *id = Some(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    /// A module made of the given sections, each of which is `(id, contents)`.
+    fn wasm(sections: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        for (id, contents) in sections {
+            bytes.push(*id);
+            bytes.push(contents.len() as u8);
+            bytes.extend_from_slice(contents);
+        }
+        bytes
+    }
+
+    /// A module with a single `[] -> []` function, whose body is `body` (without any locals
+    /// or the final `end`), with the given sections between the function and code sections.
+    fn func(sections: &[(u8, &[u8])], body: &[u8]) -> Vec<u8> {
+        let mut code = vec![1, body.len() as u8 + 2, 0];
+        code.extend_from_slice(body);
+        code.push(0x0b);
+        let mut all: Vec<(u8, &[u8])> = vec![(1, b"\x01\x60\x00\x00"), (3, b"\x01\x00")];
+        all.extend_from_slice(sections);
+        all.push((10, &code));
+        wasm(&all)
+    }
+
+    fn error(bytes: &[u8]) -> (String, usize) {
+        let err = Module::try_from(bytes).unwrap_err();
+        (err.message, err.offset)
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(Module::try_from(&wasm(&[])[..]), Ok(Module::default()));
+    }
+
+    #[test]
+    fn header() {
+        assert_eq!(
+            error(b"\0asn\x01\0\0\0"),
+            ("magic header not detected".to_string(), 0)
+        );
+        assert_eq!(
+            error(b"\0asm\x02\0\0\0"),
+            ("unknown binary version".to_string(), 4)
+        );
+        assert_eq!(error(b"\0asm\x01"), ("unexpected end".to_string(), 5));
+    }
+
+    #[test]
+    fn add() {
+        // (func $add (export "add") (param $a i32) (param $b i32) (result i32)
+        //   (local f64) local.get 0 local.get 1 i32.add)
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x02\x7f\x7f\x01\x7f"),
+            (3, b"\x01\x00"),
+            (7, b"\x01\x03add\x00\x00"),
+            (10, b"\x01\x09\x01\x01\x7c\x20\x00\x20\x01\x6a\x0b"),
+            (
+                0,
+                b"\x04name\x01\x06\x01\x00\x03add\x02\x09\x01\x00\x02\x00\x01a\x01\x01b",
+            ),
+        ]);
+        let module = Module::try_from(&bytes[..]).unwrap();
+        assert_eq!(
+            module.types[0],
+            TypeDef::func(FuncType {
+                params: vec![ValType::I32, ValType::I32],
+                results: vec![ValType::I32],
+            })
+        );
+        assert_eq!(
+            module.exports,
+            vec![Export {
+                name: "add".to_string(),
+                kind: ExportKind::Func,
+                index: 0,
+            }]
+        );
+        let func = &module.funcs[0];
+        assert_eq!(func.id.as_deref(), Some("add"));
+        assert_eq!(func.local_ids.get(&1).map(String::as_str), Some("b"));
+        assert_eq!(func.locals, vec![(1, ValType::F64)]);
+        assert_eq!(
+            func.body,
+            vec![
+                Instruction {
+                    op: Op::LocalGet,
+                    immediate: Immediate::Index(0),
+                },
+                Instruction {
+                    op: Op::LocalGet,
+                    immediate: Immediate::Index(1),
+                },
+                Instruction {
+                    op: Op::I32Add,
+                    immediate: Immediate::None,
+                },
+            ]
+        );
+        assert!(module.customs.is_empty());
+    }
+
+    #[test]
+    fn blocks_and_immediates() {
+        // block (result i32) i32.const -1 br_if 0 i64.const 0x80 drop end
+        // br_table 0 1 0 f32.const 1 drop i32.load offset=8 align=2 end
+        let body = b"\x00\x02\x7f\x41\x7f\x0d\x00\x42\x80\x01\x1a\x0b\
+            \x0e\x02\x00\x01\x00\x43\x00\x00\x80\x3f\x1a\x28\x02\x08";
+        let mut code = vec![1, body.len() as u8 + 1];
+        code.extend_from_slice(body);
+        code.push(0x0b);
+        let bytes = wasm(&[(1, b"\x01\x60\x00\x00"), (3, b"\x01\x00"), (10, &code)]);
+        let module = Module::try_from(&bytes[..]).unwrap();
+        let ops: Vec<_> = module.funcs[0].body.iter().map(|i| i.op).collect();
+        assert_eq!(
+            ops,
+            vec![
+                Op::Block,
+                Op::I32Const,
+                Op::BrIf,
+                Op::I64Const,
+                Op::Drop,
+                Op::End,
+                Op::BrTable,
+                Op::F32Const,
+                Op::Drop,
+                Op::I32Load,
+            ]
+        );
+        let body = &module.funcs[0].body;
+        assert_eq!(
+            body[0].immediate,
+            Immediate::BlockType(BlockType::Value(ValType::I32))
+        );
+        assert_eq!(body[1].immediate, Immediate::I32(-1));
+        assert_eq!(body[3].immediate, Immediate::I64(0x80));
+        assert_eq!(
+            body[6].immediate,
+            Immediate::BrTable {
+                labels: vec![0, 1],
+                default: 0,
+            }
+        );
+        assert_eq!(body[7].immediate, Immediate::F32(0x3f80_0000));
+        assert_eq!(
+            body[9].immediate,
+            Immediate::MemArg(MemArg {
+                align: 2,
+                offset: 8,
+                memory: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn leb128() {
+        // a type section whose count takes 6 bytes
+        let bytes = wasm(&[(1, b"\x80\x80\x80\x80\x80\x00")]);
+        assert_eq!(
+            error(&bytes),
+            ("integer representation too long".to_string(), 14)
+        );
+        // and one with bits set beyond 32
+        let bytes = wasm(&[(1, b"\x80\x80\x80\x80\x70")]);
+        assert_eq!(error(&bytes), ("integer too large".to_string(), 14));
+
+        let mut reader = Reader {
+            bytes: b"\x7f\xff\xff\xff\xff\x07\x80\x80\x80\x80\x78\xff\xff\xff\xff\x0f",
+            pos: 0,
+            features: Features::default(),
+        };
+        assert_eq!(reader.leb(32, true).unwrap() as i32, -1);
+        assert_eq!(reader.leb(32, true).unwrap() as i32, i32::MAX);
+        assert_eq!(reader.leb(32, true).unwrap() as i32, i32::MIN);
+        assert_eq!(
+            reader.leb(32, true),
+            Err(DecodeError {
+                message: "integer too large".to_string(),
+                offset: 15,
+            })
+        );
+    }
+
+    #[test]
+    fn section_errors() {
+        let bytes = wasm(&[(1, b"\x00\x00")]);
+        assert_eq!(error(&bytes), ("section size mismatch".to_string(), 11));
+        let bytes = wasm(&[(3, b"\x00"), (1, b"\x00")]);
+        assert_eq!(error(&bytes), ("section out of order".to_string(), 11));
+        let bytes = wasm(&[(1, b"\x00"), (1, b"\x00")]);
+        assert_eq!(error(&bytes), ("section out of order".to_string(), 11));
+        let bytes = wasm(&[(14, b"\x00")]);
+        assert_eq!(error(&bytes), ("malformed section id".to_string(), 8));
+        let mut bytes = wasm(&[(1, b"\x00")]);
+        bytes[9] = 5;
+        assert_eq!(error(&bytes), ("length out of bounds".to_string(), 10));
+        let bytes = wasm(&[(1, b"\x01\x60\x00\x00"), (3, b"\x01\x00")]);
+        assert_eq!(
+            error(&bytes),
+            (
+                "function and code section have inconsistent lengths".to_string(),
+                18
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x01\x00"),
+            (10, b"\x01\x04\x00\x01\x27\x0b"),
+        ]);
+        assert_eq!(error(&bytes), ("unknown opcode 0x27".to_string(), 24));
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x01\x00"),
+            (10, b"\x01\x05\x00\xfc\x12\x0b\x0b"),
+        ]);
+        assert_eq!(error(&bytes), ("unknown opcode 0xfc 18".to_string(), 23));
+    }
+
+    #[test]
+    fn disabled_proposal() {
+        // i32.const 0 i32.extend8_s drop
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x01\x00"),
+            (10, b"\x01\x06\x00\x41\x00\xc0\x1a\x0b"),
+        ]);
+        assert!(Module::try_from(&bytes[..]).is_ok());
+        let err = decode(&bytes, Features::v1()).unwrap_err();
+        assert_eq!(
+            err.message,
+            "the `sign-extension-ops` proposal is not enabled"
+        );
+        assert_eq!(err.offset, 25);
+    }
+
+    #[test]
+    fn imports_and_names() {
+        // (import "env" "f" (func $f)) (import "env" "g" (global $g (mut i32)))
+        // (func $h) (memory $m 1 2)
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (2, b"\x02\x03env\x01f\x00\x00\x03env\x01g\x03\x7f\x01"),
+            (3, b"\x01\x00"),
+            (5, b"\x01\x01\x01\x02"),
+            (10, b"\x01\x02\x00\x0b"),
+            (
+                0,
+                b"\x04name\x01\x07\x02\x00\x01f\x01\x01h\x06\x04\x01\x00\x01m\x07\x04\x01\x00\x01g",
+            ),
+        ]);
+        let module = Module::try_from(&bytes[..]).unwrap();
+        assert_eq!(module.imports[0].id.as_deref(), Some("f"));
+        assert_eq!(module.imports[1].id.as_deref(), Some("g"));
+        assert_eq!(
+            module.imports[1].kind,
+            ImportKind::Global(GlobalType {
+                value: ValType::I32,
+                mutable: true,
+            })
+        );
+        assert_eq!(module.funcs[0].id.as_deref(), Some("h"));
+        assert_eq!(module.memories[0].id.as_deref(), Some("m"));
+        assert_eq!(
+            module.memories[0].ty.limits,
+            Limits {
+                min: 1,
+                max: Some(2),
+            }
+        );
+        assert_eq!(
+            decode(&bytes, Features::mvp()).unwrap_err().message,
+            "the `mutable-global` proposal is not enabled"
+        );
+    }
+
+    #[test]
+    fn mutable_global_export() {
+        // (global (mut i32) (i32.const 0)) (export "g" (global 0))
+        let bytes = wasm(&[(6, b"\x01\x7f\x01\x41\x00\x0b"), (7, b"\x01\x01g\x03\x00")]);
+        let module = decode(&bytes, Features::v1()).unwrap();
+        assert_eq!(module.exports[0].kind, ExportKind::Global);
+        let err = decode(&bytes, Features::mvp()).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            ("the `mutable-global` proposal is not enabled", 21)
+        );
+
+        // an immutable global can always be exported
+        let bytes = wasm(&[(6, b"\x01\x7f\x00\x41\x00\x0b"), (7, b"\x01\x01g\x03\x00")]);
+        assert!(decode(&bytes, Features::mvp()).is_ok());
+    }
+
+    #[test]
+    fn sign_extension_and_trunc_sat() {
+        let bytes = func(
+            &[],
+            b"\xc0\xc1\xc2\xc3\xc4\xfc\x00\xfc\x01\xfc\x02\xfc\x03\xfc\x04\xfc\x05\xfc\x06\xfc\x07",
+        );
+        let module = Module::try_from(&bytes[..]).unwrap();
+        let ops: Vec<Op> = module.funcs[0].body.iter().map(|i| i.op).collect();
+        assert_eq!(
+            ops,
+            [
+                Op::I32Extend8S,
+                Op::I32Extend16S,
+                Op::I64Extend8S,
+                Op::I64Extend16S,
+                Op::I64Extend32S,
+                Op::I32TruncSatF32S,
+                Op::I32TruncSatF32U,
+                Op::I32TruncSatF64S,
+                Op::I32TruncSatF64U,
+                Op::I64TruncSatF32S,
+                Op::I64TruncSatF32U,
+                Op::I64TruncSatF64S,
+                Op::I64TruncSatF64U,
+            ]
+        );
+
+        let err = decode(&bytes, Features::v1().with(SaturatingFloatToInt)).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            ("the `sign-extension-ops` proposal is not enabled", 23)
+        );
+        let err = decode(&bytes, Features::v1().with(SignExtension)).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            (
+                "the `nontrapping-float-to-int-conversions` proposal is not enabled",
+                28
+            )
+        );
+    }
+
+    #[test]
+    fn wide_arithmetic() {
+        let bytes = func(&[], b"\xfc\x13\xfc\x14\xfc\x15\xfc\x16");
+        let module = decode(&bytes, Features::all()).unwrap();
+        let ops: Vec<Op> = module.funcs[0].body.iter().map(|i| i.op).collect();
+        assert_eq!(
+            ops,
+            [
+                Op::I64Add128,
+                Op::I64Sub128,
+                Op::I64MulWideS,
+                Op::I64MulWideU
+            ]
+        );
+        let err = decode(&bytes, Features::v3()).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            ("the `wide-arithmetic` proposal is not enabled", 23)
+        );
+    }
+
+    #[test]
+    fn branch_hints() {
+        // i32.const 0 br_if 0 i32.const 1 if end, with hints at the br_if and the if
+        let hints = |hints: &[u8]| {
+            let mut custom = vec![25];
+            custom.extend_from_slice(b"metadata.code.branch_hint\x01\x00");
+            custom.extend_from_slice(hints);
+            func(&[(0, &custom)], b"\x41\x00\x0d\x00\x41\x01\x04\x40\x0b")
+        };
+        let bytes = hints(b"\x02\x03\x01\x01\x07\x01\x00");
+        let module = decode(&bytes, Features::all()).unwrap();
+        assert_eq!(
+            module.funcs[0].branch_hints,
+            BTreeMap::from([(1, true), (3, false)])
+        );
+        assert!(module.customs.is_empty());
+
+        // without the proposal, it's just a custom section
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert!(module.funcs[0].branch_hints.is_empty());
+        assert_eq!(module.customs[0].name, "metadata.code.branch_hint");
+
+        // as it is if a hint isn't at the start of a `br_if` or `if`
+        for bad in [
+            &b"\x01\x04\x01\x01"[..],
+            b"\x01\x05\x01\x01",
+            b"\x01\x03\x02\x01",
+        ] {
+            let module = decode(&hints(bad), Features::all()).unwrap();
+            assert!(module.funcs[0].branch_hints.is_empty());
+            assert_eq!(module.customs[0].after, 3);
+        }
+    }
+
+    #[test]
+    fn malformed_name_section_is_kept() {
+        let bytes = wasm(&[(0, b"\x04name\x01\x09\x01")]);
+        let module = Module::try_from(&bytes[..]).unwrap();
+        assert_eq!(module.customs[0].name, "name");
+    }
+
+    #[test]
+    fn custom_sections() {
+        let bytes = wasm(&[(0, b"\x03one\x01"), (1, b"\x00"), (0, b"\x03two")]);
+        let module = Module::try_from(&bytes[..]).unwrap();
+        assert_eq!(
+            module.customs,
+            vec![
+                Custom {
+                    name: "one".to_string(),
+                    after: 0,
+                    data: vec![1],
+                },
+                Custom {
+                    name: "two".to_string(),
+                    after: 1,
+                    data: vec![],
+                },
+            ]
+        );
+        let bytes = wasm(&[(0, b"\x01\xff")]);
+        assert_eq!(error(&bytes), ("malformed UTF-8 encoding".to_string(), 11));
+    }
+
+    #[test]
+    fn segments() {
+        // (elem (i32.const 0) func 0) (elem func 0) (elem declare funcref (ref.func 0))
+        // (data (i32.const 8) "hi") (data "!")
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x01\x00"),
+            (4, b"\x01\x70\x00\x01"),
+            (5, b"\x01\x00\x01"),
+            (
+                9,
+                b"\x03\x00\x41\x00\x0b\x01\x00\x01\x00\x01\x00\x07\x70\x01\xd2\x00\x0b",
+            ),
+            (12, b"\x02"),
+            (10, b"\x01\x02\x00\x0b"),
+            (11, b"\x02\x00\x41\x08\x0b\x02hi\x01\x01!"),
+        ]);
+        let module = Module::try_from(&bytes[..]).unwrap();
+        assert_matches!(
+            &module.elems[0],
+            Elem {
+                mode: ElemMode::Active { table: 0, offset },
+                items: ElemItems::Functions(funcs),
+                ..
+            } if offset.len() == 1 && *funcs == vec![0]
+        );
+        assert_eq!(module.elems[1].mode, ElemMode::Passive);
+        assert_matches!(
+            &module.elems[2],
+            Elem {
+                mode: ElemMode::Declarative,
+                element: ValType::FuncRef,
+                items: ElemItems::Expressions(exprs),
+                ..
+            } if exprs[0][0].op == Op::RefFunc
+        );
+        assert_eq!(module.data_count, Some(2));
+        assert_eq!(module.datas[0].bytes, b"hi");
+        assert_eq!(module.datas[1].mode, DataMode::Passive);
+    }
+
+    #[test]
+    fn const_expressions() {
+        let error = |bytes: &[u8], features| {
+            let err = decode(bytes, features).unwrap_err();
+            (err.message, err.offset)
+        };
+        // (global i32 (i32.add (i32.const 1) (i32.const 2)))
+        let bytes = wasm(&[(6, b"\x01\x7f\x00\x41\x01\x41\x02\x6a\x0b")]);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert_eq!(module.globals[0].init[2].op, Op::I32Add);
+        assert_eq!(
+            error(&bytes, Features::v2()),
+            (
+                "the `extended-const` proposal is not enabled".to_string(),
+                17
+            )
+        );
+
+        // (global i32 (nop))
+        let bytes = wasm(&[(6, b"\x01\x7f\x00\x01\x0b")]);
+        assert_eq!(
+            error(&bytes, Features::all()),
+            ("constant expression required".to_string(), 13)
+        );
+
+        // (global i32 (i32.const 0)) (global i32 (global.get 0))
+        let bytes = wasm(&[(6, b"\x02\x7f\x00\x41\x00\x0b\x7f\x00\x23\x00\x0b")]);
+        assert!(decode(&bytes, Features::v3()).is_ok());
+        assert_eq!(
+            error(&bytes, Features::v2()),
+            ("unknown global".to_string(), 18)
+        );
+
+        // (global (mut i32) (i32.const 0)) (global i32 (global.get 0))
+        let bytes = wasm(&[(6, b"\x02\x7f\x01\x41\x00\x0b\x7f\x00\x23\x00\x0b")]);
+        assert_eq!(
+            error(&bytes, Features::v3()),
+            ("constant expression required".to_string(), 18)
+        );
+    }
+
+    #[test]
+    fn data_count_required() {
+        // data.drop 0
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x01\x00"),
+            (10, b"\x01\x05\x00\xfc\x09\x00\x0b"),
+        ]);
+        assert_eq!(
+            error(&bytes),
+            ("data count section required".to_string(), 23)
+        );
+    }
+
+    #[test]
+    fn multi_memory() {
+        // i32.const 0 i32.load $1 offset=4 drop
+        let bytes = wasm(&[
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x01\x00"),
+            (5, b"\x02\x00\x01\x00\x01"),
+            (10, b"\x01\x09\x00\x41\x00\x28\x42\x01\x04\x1a\x0b"),
+        ]);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert_eq!(
+            module.funcs[0].body[1].immediate,
+            Immediate::MemArg(MemArg {
+                align: 2,
+                offset: 4,
+                memory: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn atomics() {
+        let error = |bytes: &[u8]| {
+            let err = decode(bytes, Features::all()).unwrap_err();
+            (err.message, err.offset)
+        };
+        // (memory 1 2 shared)
+        let memory: &[u8] = b"\x01\x03\x01\x02";
+        // i32.const 0 i32.atomic.load offset=4 drop
+        let bytes = func(&[(5, memory)], b"\x41\x00\xfe\x10\x02\x04\x1a");
+        let module = decode(&bytes, Features::all()).unwrap();
+        assert!(module.memories[0].ty.shared);
+        assert_eq!(
+            module.funcs[0].body[1].immediate,
+            Immediate::MemArg(MemArg {
+                align: 2,
+                offset: 4,
+                memory: 0,
+            })
+        );
+        assert_eq!(
+            decode(&bytes, Features::v3()).unwrap_err().message,
+            "the `threads` proposal is not enabled"
+        );
+        // the same with align=2
+        let bytes = func(&[(5, memory)], b"\x41\x00\xfe\x10\x01\x04\x1a");
+        assert_eq!(
+            error(&bytes),
+            ("alignment must be exactly natural".to_string(), 33)
+        );
+        // i32.const 0 i32.load16_u align=4 drop
+        let bytes = func(&[(5, memory)], b"\x41\x00\x2f\x02\x00\x1a");
+        assert_eq!(
+            error(&bytes),
+            ("alignment must not be larger than natural".to_string(), 32)
+        );
+        // (memory 1 shared)
+        let bytes = wasm(&[(5, b"\x01\x02\x01")]);
+        assert_eq!(
+            error(&bytes),
+            ("shared memory must have maximum".to_string(), 11)
+        );
+    }
+
+    #[test]
+    fn exceptions() {
+        // (type (func)) (type (func (param i32))) (import "m" "t" (tag $t (type 1)))
+        // (func block (result exnref) try_table (catch_all_ref 0) i32.const 7 throw 1 end
+        //   unreachable end throw_ref)
+        // (tag (type 1)) (export "e" (tag 1))
+        let body = b"\x02\x69\x1f\x40\x01\x03\x00\x41\x07\x08\x01\x0b\x00\x0b\x0a";
+        let mut code = vec![1, body.len() as u8 + 2, 0];
+        code.extend_from_slice(body);
+        code.push(0x0b);
+        let bytes = wasm(&[
+            (1, b"\x02\x60\x00\x00\x60\x01\x7f\x00"),
+            (2, b"\x01\x01m\x01t\x04\x00\x01"),
+            (3, b"\x01\x00"),
+            (13, b"\x01\x00\x01"),
+            (7, b"\x01\x01e\x04\x01"),
+            (10, &code),
+            (0, b"\x04name\x0b\x04\x01\x00\x01t"),
+        ]);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert_eq!(module.imports[0].kind, ImportKind::Tag(1));
+        assert_eq!(module.imports[0].id.as_deref(), Some("t"));
+        assert_eq!(
+            module.tags,
+            vec![Tag {
+                id: None,
+                type_index: 1,
+            }]
+        );
+        assert_eq!(module.exports[0].kind, ExportKind::Tag);
+        let body = &module.funcs[0].body;
+        assert_eq!(
+            body[0].immediate,
+            Immediate::BlockType(BlockType::Value(ValType::ExnRef))
+        );
+        assert_eq!(
+            body[1].immediate,
+            Immediate::TryTable {
+                block_type: BlockType::Empty,
+                catches: vec![Catch {
+                    tag: None,
+                    reference: true,
+                    label: 0,
+                }],
+            }
+        );
+        assert_eq!(body[3].op, Op::Throw);
+        assert_eq!(body[7].op, Op::ThrowRef);
+        assert_eq!(
+            decode(&bytes, Features::v2()).unwrap_err().message,
+            "the `exception-handling` proposal is not enabled"
+        );
+
+        // the tag section goes before the global section
+        let bytes = wasm(&[(6, b"\x00"), (13, b"\x00")]);
+        assert_eq!(
+            decode(&bytes, Features::v3()).unwrap_err().message,
+            "section out of order"
+        );
+    }
+
+    #[test]
+    fn legacy_exceptions() {
+        // try catch 0 catch_all end try delegate 0
+        let body = b"\x06\x40\x01\x07\x00\x01\x19\x01\x0b\x06\x40\x01\x18\x00";
+        let bytes = func(&[(13, b"\x01\x00\x00")], body);
+        let module = decode(&bytes, Features::all()).unwrap();
+        let ops: Vec<_> = module.funcs[0].body.iter().map(|i| i.op).collect();
+        assert_eq!(
+            ops,
+            vec![
+                Op::Try,
+                Op::Nop,
+                Op::Catch,
+                Op::Nop,
+                Op::CatchAll,
+                Op::Nop,
+                Op::End,
+                Op::Try,
+                Op::Nop,
+                Op::Delegate,
+            ]
+        );
+        assert_eq!(
+            decode(&bytes, Features::v3()).unwrap_err().message,
+            "the `legacy-exception-handling` proposal is not enabled"
+        );
+        let bytes = func(&[], b"\x18\x00");
+        assert_eq!(
+            decode(&bytes, Features::all()).unwrap_err().message,
+            "delegate outside of a try"
+        );
+    }
+
+    #[test]
+    fn tail_calls() {
+        // (type (func (result i32))) (type (func)) (table 1 funcref)
+        // (func (type 0) i32.const 0 return_call_indirect (type 0))
+        // (func (type $ty) return_call 0) (func (type 0) return_call_ref 0)
+        let calls = |ty: &[u8]| {
+            let funcs = [b"\x03\x00".as_slice(), ty, b"\x00"].concat();
+            wasm(&[
+                (1, b"\x02\x60\x00\x01\x7f\x60\x00\x00"),
+                (3, &funcs),
+                (4, b"\x01\x70\x00\x01"),
+                (
+                    10,
+                    b"\x03\x07\x00\x41\x00\x13\x00\x00\x0b\x04\x00\x12\x00\x0b\x04\x00\x15\x00\x0b",
+                ),
+            ])
+        };
+        let bytes = calls(b"\x00");
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert_eq!(module.funcs[0].body[1].immediate, Immediate::Indices(0, 0));
+        assert_eq!(
+            module.funcs[2].body[0],
+            Instruction {
+                op: Op::ReturnCallRef,
+                immediate: Immediate::Index(0),
+            }
+        );
+        assert_eq!(
+            decode(&bytes, Features::v2().with(TailCall))
+                .unwrap_err()
+                .message,
+            "the `function-references` proposal is not enabled"
+        );
+        assert_eq!(
+            decode(&bytes, Features::v2()).unwrap_err().message,
+            "the `tail-call` proposal is not enabled"
+        );
+        // the second function returns nothing, but calls one that returns an i32
+        let err = decode(&calls(b"\x01"), Features::v3()).unwrap_err();
+        assert_eq!(
+            err.message,
+            "type mismatch: tail call results don't match the caller's"
+        );
+        assert_eq!(err.offset, 43);
+    }
+
+    #[test]
+    fn memory64() {
+        // i64.const 0 i32.load offset=0x1_0000_0000 drop
+        let body = b"\x42\x00\x28\x02\x80\x80\x80\x80\x10\x1a";
+        // (memory i64 1)
+        let bytes = func(&[(5, b"\x01\x04\x01")], body);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert!(module.memories[0].ty.memory64);
+        assert_eq!(
+            module.funcs[0].body[1].immediate,
+            Immediate::MemArg(MemArg {
+                align: 2,
+                offset: 1 << 32,
+                memory: 0,
+            })
+        );
+        assert_eq!(
+            decode(&bytes, Features::v2()).unwrap_err().message,
+            "the `memory64` proposal is not enabled"
+        );
+        // (memory 1)
+        let bytes = func(&[(5, b"\x01\x00\x01")], body);
+        let err = decode(&bytes, Features::v3()).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            ("offset out of range", 32)
+        );
+
+        // (table i64 1 funcref)
+        let bytes = wasm(&[(4, b"\x01\x70\x04\x01")]);
+        let table = decode(&bytes, Features::v3()).unwrap().tables[0].ty;
+        assert!(table.table64);
+        assert_eq!(table.limits, Limits { min: 1, max: None });
+    }
+
+    #[test]
+    fn custom_page_sizes() {
+        // (memory 1 (pagesize 1)) (memory 1 2 (pagesize 65536))
+        let bytes = wasm(&[(5, b"\x02\x08\x01\x00\x09\x01\x02\x10")]);
+        let module = decode(&bytes, Features::all()).unwrap();
+        assert_eq!(module.memories[0].ty.page_size_log2, Some(0));
+        assert_eq!(
+            module.memories[1].ty,
+            MemoryType {
+                limits: Limits {
+                    min: 1,
+                    max: Some(2),
+                },
+                shared: false,
+                memory64: false,
+                page_size_log2: Some(16),
+            }
+        );
+        let err = decode(&bytes, Features::v3()).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            ("the `custom-page-sizes` proposal is not enabled", 11)
+        );
+
+        let bytes = wasm(&[(5, b"\x01\x08\x01\x03")]);
+        let err = decode(&bytes, Features::all()).unwrap_err();
+        assert_eq!(
+            (err.message.as_str(), err.offset),
+            ("invalid custom page size", 13)
+        );
+    }
+
+    #[test]
+    fn multiple_memories() {
+        // (import "m" "m" (memory 1)) (memory 1)
+        let bytes = wasm(&[(2, b"\x01\x01m\x01m\x02\x00\x01"), (5, b"\x01\x00\x01")]);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert_eq!(module.memory_type(1), Some(&module.memories[0].ty));
+        let err = decode(&bytes, Features::v2()).unwrap_err();
+        assert_eq!(err.message, "the `multi-memory` proposal is not enabled");
+        assert_eq!(err.offset, 21);
+    }
+
+    #[test]
+    fn gc_types() {
+        // (rec (type (sub (struct (field (mut i8)) (field (ref null 1)))))
+        //   (type (sub final 0 (struct))))
+        // (type (func (result (ref 0)))) (type (array (mut i32)))
+        let types = b"\x03\x4e\x02\x50\x00\x5f\x02\x78\x01\x63\x01\x00\x4f\x01\x00\x5f\x00\
+            \x60\x00\x01\x64\x00\x5e\x7f\x01";
+        let bytes = wasm(&[(1, types)]);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        assert_eq!(module.rec_groups, vec![0..2]);
+        assert_eq!(
+            module.types[0],
+            TypeDef {
+                id: None,
+                composite: CompositeType::Struct(vec![
+                    FieldType {
+                        storage: StorageType::I8,
+                        mutable: true,
+                    },
+                    FieldType {
+                        storage: StorageType::Val(ValType::Ref(RefType {
+                            nullable: true,
+                            heap: HeapType::Type(1),
+                        })),
+                        mutable: false,
+                    },
+                ]),
+                supertypes: vec![],
+                is_final: false,
+            }
+        );
+        assert_eq!(module.types[1].supertypes, vec![0]);
+        assert!(module.types[1].is_final);
+        assert_eq!(
+            module.types[2].func_type().unwrap().results,
+            vec![ValType::Ref(RefType {
+                nullable: false,
+                heap: HeapType::Type(0),
+            })]
+        );
+        assert_eq!(
+            module.types[3].composite,
+            CompositeType::Array(FieldType {
+                storage: StorageType::Val(ValType::I32),
+                mutable: true,
+            })
+        );
+        let err = decode(&bytes, Features::v2()).unwrap_err();
+        assert_eq!(err.message, "the `gc` proposal is not enabled");
+        assert_eq!(err.offset, 11);
+
+        // (type (func (param (ref null func) (ref extern))))
+        let bytes = wasm(&[(1, b"\x01\x60\x02\x63\x70\x64\x6f\x00")]);
+        let features = Features::v2().with(FunctionReferences);
+        let params = &decode(&bytes, features).unwrap().types[0];
+        assert_eq!(
+            params.func_type().unwrap().params,
+            vec![
+                ValType::FuncRef,
+                ValType::Ref(RefType {
+                    nullable: false,
+                    heap: HeapType::Extern,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn gc_instructions() {
+        // struct.new_default 0 ref.cast (ref 0) br_on_cast 0 anyref (ref 0) drop
+        // i32.const 5 ref.i31 i31.get_s drop i32.const 1 i32.const 2 array.new_fixed 1 2 drop
+        // ref.null none ref.as_non_null br_on_null 0 drop
+        let body = b"\xfb\x01\x00\xfb\x16\x00\xfb\x18\x01\x00\x6e\x00\x1a\
+            \x41\x05\xfb\x1c\xfb\x1d\x1a\x41\x01\x41\x02\xfb\x08\x01\x02\x1a\
+            \xd0\x71\xd4\xd5\x00\x1a";
+        let mut code = vec![1, body.len() as u8 + 2, 0];
+        code.extend_from_slice(body);
+        code.push(0x0b);
+        // (type (struct)) (type (array (mut i32))) (type (func))
+        let bytes = wasm(&[
+            (1, b"\x03\x5f\x00\x5e\x7f\x01\x60\x00\x00"),
+            (3, b"\x01\x02"),
+            (10, &code),
+        ]);
+        let module = decode(&bytes, Features::v3()).unwrap();
+        let body = &module.funcs[0].body;
+        assert_eq!(body[1].op, Op::RefCast);
+        assert_eq!(body[1].immediate, Immediate::HeapType(HeapType::Type(0)));
+        assert_eq!(
+            body[2].immediate,
+            Immediate::BrOnCast {
+                label: 0,
+                from: RefType {
+                    nullable: true,
+                    heap: HeapType::Any,
+                },
+                to: RefType {
+                    nullable: false,
+                    heap: HeapType::Type(0),
+                },
+            }
+        );
+        assert_eq!(body[5].op, Op::RefI31);
+        assert_eq!(body[10].immediate, Immediate::Indices(1, 2));
+        assert_eq!(body[12].immediate, Immediate::HeapType(HeapType::None));
+        assert_eq!(body[14].op, Op::BrOnNull);
+        assert_eq!(
+            decode(&bytes, Features::v2()).unwrap_err().message,
+            "the `gc` proposal is not enabled"
+        );
+    }
+
+    #[test]
+    fn section_layout() {
+        let bytes = wasm(&[
+            (0, b"\x03one\x01"),
+            (1, b"\x01\x60\x00\x00"),
+            (3, b"\x02\x00\x00"),
+            (10, b"\x02\x02\x00\x0b\x03\x00\x01\x0b"),
+        ]);
+        let sections = sections(&bytes).unwrap();
+        assert_eq!(
+            sections[0],
+            Section {
+                id: 0,
+                name: Some("one".to_string()),
+                range: 8..15,
+                contents: 14..15,
+            }
+        );
+        assert_eq!(sections[3].range, 26..36);
+        assert_eq!(
+            function_bodies(&bytes, &sections[3]).unwrap(),
+            vec![30..32, 33..36]
+        );
+        let mut bytes = bytes;
+        bytes.extend_from_slice(&[14, 0]);
+        assert_eq!(
+            super::sections(&bytes).unwrap_err().message,
+            "malformed section id"
+        );
+    }
+}
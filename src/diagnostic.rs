@@ -0,0 +1,471 @@
+//! Errors and warnings with spans into the source, rendered like rustc's or as JSON.
+
+use crate::json::Json;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn colour(self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+/// A span of the source with a message next to its underline. The primary label is where the
+/// problem is, and is underlined with `^`s; secondary labels point at related places (e.g.
+/// where a label was defined), and are underlined with `-`s.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Label {
+    pub span: Range<usize>,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// An error or warning, built up with [`Diagnostic::error`] and the `with_` methods:
+///
+/// ```
+/// # use w2w2::diagnostic::Diagnostic;
+/// let src = "(block $l\n  br $m)";
+/// let diagnostic = Diagnostic::error("unknown label `$m`")
+///     .with_label(15..17, "not found")
+///     .with_secondary_label(7..9, "the only label in scope")
+///     .with_help("did you mean `$l`?");
+/// assert_eq!(
+///     diagnostic.render("in.wat", src, false),
+///     "error: unknown label `$m`
+///  --> in.wat:2:6
+///   |
+/// 1 | (block $l
+///   |        -- the only label in scope
+/// 2 |   br $m)
+///   |      ^^ not found
+///   |
+///   = help: did you mean `$l`?"
+/// );
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Error, message.into())
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Severity::Warning, message.into())
+    }
+
+    fn new(severity: Severity, message: String) -> Diagnostic {
+        Diagnostic {
+            severity,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    /// Adds a primary label. The message can be empty to just underline the span.
+    pub fn with_label(mut self, span: Range<usize>, message: impl Into<String>) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(
+        mut self,
+        span: Range<usize>,
+        message: impl Into<String>,
+    ) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help.push(help.into());
+        self
+    }
+
+    /// The span of the first primary label, or of the first label if none are primary.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .or_else(|| self.labels.first())
+            .map(|label| label.span.clone())
+    }
+
+    /// Renders the diagnostic like rustc does, with each labelled line of `src` (usually
+    /// [`TokenList::src`](crate::lexer::TokenList::src)) underlined, followed by the notes and
+    /// help. Spans that cover several lines are only underlined on their first line.
+    pub fn render(&self, file: &str, src: &str, colour: bool) -> String {
+        let paint = |code: &'static str| if colour { code } else { "" };
+        let (severity, blue, bold, reset) = (
+            paint(self.severity.colour()),
+            paint(BLUE),
+            paint(BOLD),
+            paint(RESET),
+        );
+        let mut out = format!(
+            "{}{}{}{}: {}{}",
+            severity,
+            self.severity.name(),
+            reset,
+            bold,
+            self.message,
+            reset
+        );
+
+        let mut labels: Vec<(Position, &Label)> = self
+            .labels
+            .iter()
+            .map(|label| (Position::of(src, &label.span), label))
+            .collect();
+        labels.sort_by_key(|(position, _)| (position.line, position.column));
+        let width = labels
+            .iter()
+            .map(|(position, _)| position.line.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        if let Some(span) = self.span() {
+            let position = Position::of(src, &span);
+            let _ = write!(
+                out,
+                "\n{}{}-->{} {}:{}:{}",
+                gutter, blue, reset, file, position.line, position.column
+            );
+        }
+        if !labels.is_empty() {
+            let _ = write!(out, "\n{} {}|{}", gutter, blue, reset);
+        }
+        let mut last_line = None;
+        for (position, label) in &labels {
+            if last_line != Some(position.line) {
+                if last_line.is_some_and(|last| position.line > last + 1) {
+                    let _ = write!(out, "\n{}...{}", blue, reset);
+                }
+                let _ = write!(
+                    out,
+                    "\n{}{:>width$} |{} {}",
+                    blue,
+                    position.line,
+                    reset,
+                    position.text,
+                    width = width
+                );
+                last_line = Some(position.line);
+            }
+            let (marker, colour) = match label.primary {
+                true => ('^', severity),
+                false => ('-', blue),
+            };
+            let _ = write!(
+                out,
+                "\n{} {}|{} {}{}{}",
+                gutter,
+                blue,
+                reset,
+                " ".repeat(position.column - 1),
+                colour,
+                core::iter::repeat_n(marker, position.width).collect::<String>()
+            );
+            if !label.message.is_empty() {
+                let _ = write!(out, " {}", label.message);
+            }
+            out += reset;
+        }
+
+        if !self.notes.is_empty() || !self.help.is_empty() {
+            if !labels.is_empty() {
+                let _ = write!(out, "\n{} {}|{}", gutter, blue, reset);
+            }
+            let notes = self.notes.iter().map(|note| ("note", note));
+            for (kind, text) in notes.chain(self.help.iter().map(|help| ("help", help))) {
+                let _ = write!(
+                    out,
+                    "\n{} {}={} {}{}:{} {}",
+                    gutter, blue, reset, bold, kind, reset, text
+                );
+            }
+        }
+        out
+    }
+
+    /// Renders the diagnostic as a single line of JSON, for editors and other tools. Each
+    /// label has its byte range and its 1-based start and end lines and columns (counted in
+    /// characters).
+    pub fn to_json(&self, file: &str, src: &str) -> String {
+        self.json(file, src).to_string()
+    }
+
+    /// The diagnostic as a JSON value, as rendered by [`Diagnostic::to_json`].
+    pub fn json(&self, file: &str, src: &str) -> Json {
+        let labels = self.labels.iter().map(|label| {
+            let start = Position::of(src, &label.span);
+            let end = Position::of(src, &(label.span.end..label.span.end));
+            Json::object([
+                ("start", label.span.start.into()),
+                ("end", label.span.end.into()),
+                ("line", start.line.into()),
+                ("column", start.column.into()),
+                ("end_line", end.line.into()),
+                ("end_column", end.column.into()),
+                ("message", label.message.as_str().into()),
+                ("primary", label.primary.into()),
+            ])
+        });
+        let strings =
+            |list: &[String]| Json::Array(list.iter().map(|s| s.as_str().into()).collect());
+        Json::object([
+            ("severity", self.severity.name().into()),
+            ("message", self.message.as_str().into()),
+            ("file", file.into()),
+            ("labels", Json::Array(labels.collect())),
+            ("notes", strings(&self.notes)),
+            ("help", strings(&self.help)),
+        ])
+    }
+}
+
+/// The diagnostics from a run, shared between the stages so that each can carry on after an
+/// error and report every independent problem rather than only the first. Once `limit` errors
+/// have been pushed it's full, and stages should stop early rather than produce more - a
+/// final error saying so is added when it's turned into a list.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Diagnostics {
+    list: Vec<Diagnostic>,
+    errors: usize,
+    limit: Option<usize>,
+}
+
+impl Diagnostics {
+    /// `None` means no limit.
+    pub fn new(limit: Option<usize>) -> Diagnostics {
+        Diagnostics {
+            list: Vec::new(),
+            errors: 0,
+            limit,
+        }
+    }
+
+    /// Adds a diagnostic, unless it's an error and the limit has been reached.
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        if diagnostic.severity == Severity::Error {
+            if self.is_full() {
+                return;
+            }
+            self.errors += 1;
+        }
+        self.list.push(diagnostic);
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.limit.is_some_and(|limit| self.errors >= limit)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors != 0
+    }
+
+    pub fn into_vec(mut self) -> Vec<Diagnostic> {
+        if self.is_full() {
+            self.list.push(Diagnostic::error(format!(
+                "stopping after {} errors",
+                self.errors
+            )));
+        }
+        self.list
+    }
+}
+
+/// Where a span starts, and the line it's on.
+struct Position<'a> {
+    line: usize,
+    column: usize,
+    /// The line's text, without its line ending.
+    text: &'a str,
+    /// The number of characters to underline - at least 1, so that empty spans (e.g. at the
+    /// end of the file) still get a caret.
+    width: usize,
+}
+
+impl Position<'_> {
+    fn of<'a>(src: &'a str, span: &Range<usize>) -> Position<'a> {
+        let mut start = span.start.min(src.len());
+        while !src.is_char_boundary(start) {
+            start -= 1;
+        }
+        let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+        let mut end = span.end.clamp(start, line_end);
+        while !src.is_char_boundary(end) {
+            end += 1;
+        }
+        Position {
+            line: src[..start].matches('\n').count() + 1,
+            column: src[line_start..start].chars().count() + 1,
+            text: src[line_start..line_end].trim_end_matches('\r'),
+            width: src[start..end].chars().count().max(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "(module\n  (func $f\n    br 1\n    call $g))";
+
+    #[test]
+    fn render() {
+        let diagnostic = Diagnostic::error("unknown function `$g`")
+            .with_label(37..39, "not found")
+            .with_secondary_label(16..18, "a function with a similar name")
+            .with_note("functions are looked up by their $id")
+            .with_help("did you mean `$f`?");
+        assert_eq!(
+            diagnostic.render("in.wat", SRC, false),
+            "error: unknown function `$g`
+ --> in.wat:4:10
+  |
+2 |   (func $f
+  |         -- a function with a similar name
+...
+4 |     call $g))
+  |          ^^ not found
+  |
+  = note: functions are looked up by their $id
+  = help: did you mean `$f`?"
+        );
+        assert_eq!(
+            diagnostic.render("in.wat", SRC, true),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: unknown function `$g`\x1b[0m
+ \x1b[1;34m-->\x1b[0m in.wat:4:10
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m2 |\x1b[0m   (func $f
+  \x1b[1;34m|\x1b[0m         \x1b[1;34m-- a function with a similar name\x1b[0m
+\x1b[1;34m...\x1b[0m
+\x1b[1;34m4 |\x1b[0m     call $g))
+  \x1b[1;34m|\x1b[0m          \x1b[1;31m^^ not found\x1b[0m
+  \x1b[1;34m|\x1b[0m
+  \x1b[1;34m=\x1b[0m \x1b[1mnote:\x1b[0m functions are looked up by their $id
+  \x1b[1;34m=\x1b[0m \x1b[1mhelp:\x1b[0m did you mean `$f`?"
+        );
+    }
+
+    #[test]
+    fn render_edge_cases() {
+        // several labels on one line, an empty span at the end, and a span over several lines
+        let diagnostic = Diagnostic::warning("odd")
+            .with_label(SRC.len()..SRC.len(), "")
+            .with_secondary_label(32..36, "first")
+            .with_secondary_label(23..25, "second");
+        assert_eq!(
+            diagnostic.render("in.wat", SRC, false),
+            "warning: odd
+ --> in.wat:4:14
+  |
+3 |     br 1
+  |     -- second
+4 |     call $g))
+  |     ---- first
+  |              ^"
+        );
+        assert_eq!(
+            Diagnostic::error("no parser").render("in.wat", SRC, false),
+            "error: no parser"
+        );
+        assert_eq!(
+            Diagnostic::error("no parser")
+                .with_note("sorry")
+                .render("in.wat", SRC, false),
+            "error: no parser\n  = note: sorry"
+        );
+        assert_eq!(
+            Diagnostic::error("big")
+                .with_label(1..SRC.len(), "")
+                .render("in.wat", SRC, false),
+            "error: big\n --> in.wat:1:2\n  |\n1 | (module\n  |  ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn limit() {
+        let mut diagnostics = Diagnostics::new(Some(2));
+        diagnostics.push(Diagnostic::warning("a"));
+        diagnostics.push(Diagnostic::error("b"));
+        assert!(!diagnostics.is_full());
+        diagnostics.push(Diagnostic::error("c"));
+        assert!(diagnostics.is_full());
+        diagnostics.push(Diagnostic::error("d"));
+        diagnostics.push(Diagnostic::warning("e"));
+        let messages: Vec<_> = diagnostics
+            .into_vec()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(messages, ["a", "b", "c", "e", "stopping after 2 errors"]);
+
+        let mut diagnostics = Diagnostics::new(None);
+        assert!(!diagnostics.has_errors());
+        diagnostics.push(Diagnostic::error("a"));
+        assert!(diagnostics.has_errors() && !diagnostics.is_full());
+    }
+
+    #[test]
+    fn json() {
+        let diagnostic = Diagnostic::error("unknown token `\"x`")
+            .with_label(23..25, "here")
+            .with_help("check\tthis");
+        assert_eq!(
+            diagnostic.to_json("dir\\in.wat", SRC),
+            "{\"severity\":\"error\",\"message\":\"unknown token `\\\"x`\",\
+             \"file\":\"dir\\\\in.wat\",\"labels\":[{\"start\":23,\"end\":25,\"line\":3,\
+             \"column\":5,\"end_line\":3,\"end_column\":7,\"message\":\"here\",\
+             \"primary\":true}],\"notes\":[],\"help\":[\"check\\tthis\"]}"
+        );
+    }
+}
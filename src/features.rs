@@ -0,0 +1,225 @@
+use core::fmt;
+
+/// A post-MVP proposal that can be switched on or off in a [`Features`] set.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Proposal {
+    MutableGlobals,
+    SignExtension,
+    SaturatingFloatToInt,
+    MultiValue,
+    ReferenceTypes,
+    BulkMemory,
+    Simd,
+    RelaxedSimd,
+    TailCall,
+    ExtendedConst,
+    FunctionReferences,
+    Gc,
+    ExceptionHandling,
+    LegacyExceptionHandling,
+    Memory64,
+    MultiMemory,
+    Threads,
+    CustomPageSizes,
+    WideArithmetic,
+    BranchHinting,
+}
+
+use Proposal::*;
+
+impl Proposal {
+    pub const ALL: [Proposal; 20] = [
+        MutableGlobals,
+        SignExtension,
+        SaturatingFloatToInt,
+        MultiValue,
+        ReferenceTypes,
+        BulkMemory,
+        Simd,
+        RelaxedSimd,
+        TailCall,
+        ExtendedConst,
+        FunctionReferences,
+        Gc,
+        ExceptionHandling,
+        LegacyExceptionHandling,
+        Memory64,
+        MultiMemory,
+        Threads,
+        CustomPageSizes,
+        WideArithmetic,
+        BranchHinting,
+    ];
+
+    /// The proposal's name as used by the spec repositories, e.g. `sign-extension-ops`.
+    pub fn name(self) -> &'static str {
+        match self {
+            MutableGlobals => "mutable-global",
+            SignExtension => "sign-extension-ops",
+            SaturatingFloatToInt => "nontrapping-float-to-int-conversions",
+            MultiValue => "multi-value",
+            ReferenceTypes => "reference-types",
+            BulkMemory => "bulk-memory-operations",
+            Simd => "simd",
+            RelaxedSimd => "relaxed-simd",
+            TailCall => "tail-call",
+            ExtendedConst => "extended-const",
+            FunctionReferences => "function-references",
+            Gc => "gc",
+            ExceptionHandling => "exception-handling",
+            LegacyExceptionHandling => "legacy-exception-handling",
+            Memory64 => "memory64",
+            MultiMemory => "multi-memory",
+            Threads => "threads",
+            CustomPageSizes => "custom-page-sizes",
+            WideArithmetic => "wide-arithmetic",
+            BranchHinting => "branch-hinting",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Proposal> {
+        Proposal::ALL.into_iter().find(|p| p.name() == name)
+    }
+
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+impl fmt::Display for Proposal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The set of proposals that are allowed for a compilation target.
+///
+/// The presets follow the published spec versions; anything else can be built up from
+/// [`Features::mvp`] with [`Features::with`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Features(u32);
+
+impl Features {
+    /// No proposals at all - just the original MVP.
+    pub fn mvp() -> Features {
+        Features(0)
+    }
+
+    /// WebAssembly 1.0, which is the MVP plus importing & exporting mutable globals.
+    pub fn v1() -> Features {
+        Features::mvp().with(MutableGlobals)
+    }
+
+    /// WebAssembly 2.0, which adds sign-extension operators, non-trapping float-to-int
+    /// conversions, multi-value, reference types, bulk memory operations and SIMD.
+    pub fn v2() -> Features {
+        Features::v1()
+            .with(SignExtension)
+            .with(SaturatingFloatToInt)
+            .with(MultiValue)
+            .with(ReferenceTypes)
+            .with(BulkMemory)
+            .with(Simd)
+    }
+
+    /// WebAssembly 3.0, which adds relaxed SIMD, tail calls, extended constant expressions,
+    /// typed function references, GC, exception handling (with `exnref`, not the legacy
+    /// `try`), memory64 and multiple memories.
+    pub fn v3() -> Features {
+        Features::v2()
+            .with(RelaxedSimd)
+            .with(TailCall)
+            .with(ExtendedConst)
+            .with(FunctionReferences)
+            .with(Gc)
+            .with(ExceptionHandling)
+            .with(Memory64)
+            .with(MultiMemory)
+    }
+
+    /// Every proposal that w2w2 knows about, including ones that aren't standardised yet.
+    pub fn all() -> Features {
+        Proposal::ALL
+            .into_iter()
+            .fold(Features::mvp(), Features::with)
+    }
+
+    pub fn with(self, proposal: Proposal) -> Features {
+        Features(self.0 | proposal.bit())
+    }
+
+    pub fn without(self, proposal: Proposal) -> Features {
+        Features(self.0 & !proposal.bit())
+    }
+
+    pub fn enabled(&self, proposal: Proposal) -> bool {
+        self.0 & proposal.bit() != 0
+    }
+
+    /// Returns the proposal back as the error if it isn't enabled, so that callers can
+    /// attach it to a diagnostic at the offending location.
+    pub fn require(&self, proposal: Proposal) -> Result<(), Proposal> {
+        if self.enabled(proposal) {
+            Ok(())
+        } else {
+            Err(proposal)
+        }
+    }
+}
+
+impl Default for Features {
+    fn default() -> Features {
+        Features::v2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_cumulative() {
+        for p in Proposal::ALL {
+            if Features::v1().enabled(p) {
+                assert!(Features::v2().enabled(p));
+            }
+            if Features::v2().enabled(p) {
+                assert!(Features::v3().enabled(p));
+            }
+            assert!(Features::all().enabled(p));
+            assert!(!Features::mvp().enabled(p));
+        }
+    }
+
+    #[test]
+    fn old_embedded_target() {
+        let features = Features::mvp().with(MutableGlobals).with(SignExtension);
+        assert_eq!(features.require(SignExtension), Ok(()));
+        assert_eq!(
+            features.require(SaturatingFloatToInt),
+            Err(SaturatingFloatToInt)
+        );
+        assert_eq!(features.without(SignExtension), Features::v1());
+    }
+
+    #[test]
+    fn unstandardised_proposals() {
+        for p in [
+            Threads,
+            LegacyExceptionHandling,
+            CustomPageSizes,
+            WideArithmetic,
+            BranchHinting,
+        ] {
+            assert!(!Features::v3().enabled(p));
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for p in Proposal::ALL {
+            assert_eq!(Proposal::from_name(p.name()), Some(p));
+        }
+        assert_eq!(Proposal::from_name("not-a-proposal"), None);
+    }
+}
@@ -0,0 +1,873 @@
+use crate::lexer::{Token, TokenList};
+use crate::opcodes::Op;
+use crate::printer::Style;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Indent {
+    Spaces(usize),
+    Tabs,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FormatOptions {
+    pub indent: Indent,
+    /// The style to rewrite function bodies in, or `None` to keep each instruction the way
+    /// it was written.
+    pub style: Option<Style>,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            indent: Indent::Spaces(2),
+            style: None,
+        }
+    }
+}
+
+/// Reformats text format source, keeping all of its comments and (single) blank lines.
+///
+/// This works on the token stream rather than a parsed module, so it also formats `.wast`
+/// scripts and source that doesn't validate. Formatting already formatted source with the
+/// same options gives back the same source.
+///
+/// When folding instructions, only ones whose operand count is known from the opcode alone
+/// get their operands nested inside them - so e.g. the arguments of a `call` stay as separate
+/// instructions before it.
+pub fn format(src: &str, options: FormatOptions) -> String {
+    let tokens = with_line_breaks(src, TokenList::from(src.to_string()));
+    let (nodes, _) = tree(&mut tokens.into_iter(), false);
+    let mut writer = Writer {
+        out: String::new(),
+        options,
+        depth: 0,
+        line_start: true,
+        after_line_comment: false,
+        blank: false,
+    };
+    writer.sequence(&nodes);
+    if !writer.out.is_empty() {
+        writer.out.push('\n');
+    }
+    writer.out
+}
+
+/// Pairs each token with how many line breaks came before it, which the lexer doesn't keep.
+/// The start of the source counts as a line break.
+fn with_line_breaks(src: &str, tokens: TokenList) -> Vec<(Token, usize)> {
+    let spans = tokens.spans().to_vec();
+    let mut end = 0;
+    tokens
+        .list()
+        .into_iter()
+        .zip(spans)
+        .map(|(token, span)| {
+            let breaks = src[end..span.start].matches('\n').count() + usize::from(end == 0);
+            end = span.end;
+            (token, breaks)
+        })
+        .collect()
+}
+
+enum Node {
+    Atom(Token),
+    List {
+        items: Vec<Node>,
+        /// Whether the list had its `)` before the end of the source.
+        closed: bool,
+    },
+    Comment {
+        text: String,
+        /// A `;;` comment, which has to be followed by a line break.
+        line: bool,
+        /// Whether the comment started its line, rather than trailing after something.
+        own_line: bool,
+    },
+    /// One or more blank lines.
+    Blank,
+}
+
+fn tree(tokens: &mut impl Iterator<Item = (Token, usize)>, in_list: bool) -> (Vec<Node>, bool) {
+    let mut nodes = Vec::new();
+    while let Some((token, breaks)) = tokens.next() {
+        if breaks > 1 && !nodes.is_empty() {
+            nodes.push(Node::Blank);
+        }
+        nodes.push(match token {
+            Token::RParen if in_list => return (nodes, true),
+            Token::LParen => {
+                let (items, closed) = tree(tokens, true);
+                Node::List { items, closed }
+            }
+            Token::Comment {
+                string, multiline, ..
+            } => Node::Comment {
+                text: string,
+                line: !multiline,
+                own_line: breaks > 0,
+            },
+            token => Node::Atom(token),
+        });
+    }
+    (nodes, false)
+}
+
+/// The keyword at the start of a list, e.g. `func` or `i32.add`.
+fn head(items: &[Node]) -> Option<&str> {
+    match items.first()? {
+        Node::Atom(Token::Instruction(keyword)) | Node::Atom(Token::Annotation(keyword)) => {
+            Some(keyword)
+        }
+        _ => None,
+    }
+}
+
+fn list_head(node: &Node) -> Option<&str> {
+    match node {
+        Node::List { items, .. } => head(items),
+        _ => None,
+    }
+}
+
+/// Keywords that are immediates of instructions rather than instructions themselves.
+fn is_immediate_keyword(keyword: &str) -> bool {
+    keyword.starts_with("offset=")
+        || keyword.starts_with("align=")
+        || [
+            "i8x16", "i16x8", "i32x4", "i64x2", "f32x4", "f64x2", "func", "extern", "any", "eq",
+            "i31", "struct", "array", "none", "nofunc", "noextern", "exn", "noexn",
+        ]
+        .contains(&keyword)
+}
+
+/// Lists that are immediates of instructions, e.g. `call_indirect (type $t)`.
+const IMMEDIATE_LISTS: [&str; 8] = [
+    "type",
+    "param",
+    "result",
+    "ref",
+    "catch",
+    "catch_ref",
+    "catch_all",
+    "catch_all_ref",
+];
+
+const BLOCKS: [&str; 4] = ["block", "loop", "if", "try_table"];
+
+/// The parts of a function before its body.
+const FUNC_HEADER: [&str; 5] = ["export", "import", "type", "param", "result"];
+
+/// Where a function's body starts, after its header and locals.
+fn func_body_start(items: &[Node]) -> usize {
+    let mut locals = false;
+    for (i, item) in items.iter().enumerate().skip(1) {
+        match item {
+            Node::Atom(Token::Identifier(_)) | Node::Blank | Node::Comment { .. } => {}
+            Node::List { items, .. } => match head(items) {
+                Some(keyword) if !locals && FUNC_HEADER.contains(&keyword) => {}
+                Some("local") => locals = true,
+                _ => return i,
+            },
+            _ => return i,
+        }
+    }
+    items.len()
+}
+
+/// Whether a list has to be written over several lines.
+fn needs_break(items: &[Node]) -> bool {
+    match head(items) {
+        Some("module") => true,
+        Some("func")
+            if items[func_body_start(items)..]
+                .iter()
+                .any(|item| !matches!(item, Node::Blank))
+                || items.iter().any(|item| list_head(item) == Some("local")) =>
+        {
+            true
+        }
+        _ => items.iter().any(|item| match item {
+            Node::Comment { line, .. } => *line,
+            Node::List { items, .. } => needs_break(items),
+            _ => false,
+        }),
+    }
+}
+
+/// A function body's instructions, parsed from either the flat or the folded syntax.
+enum Item<'a> {
+    Instr(Instr<'a>),
+    /// Comments, blank lines and anything that isn't recognisably an instruction.
+    Other(&'a Node),
+}
+
+struct Instr<'a> {
+    name: &'a str,
+    immediates: Vec<&'a Node>,
+    folded: bool,
+    /// Folded operands, which for an `if` are its condition.
+    operands: Vec<Item<'a>>,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Plain,
+    Block {
+        body: Vec<Item<'a>>,
+        /// The label after the `end`, if there was one.
+        end: Option<&'a Node>,
+    },
+    If {
+        then: Vec<Item<'a>>,
+        /// The label after the `else` (if there was one) and the else branch.
+        otherwise: Option<(Option<&'a Node>, Vec<Item<'a>>)>,
+        end: Option<&'a Node>,
+    },
+}
+
+impl Instr<'_> {
+    /// How many values this leaves on the stack, if that can be worked out from just the
+    /// instruction.
+    fn results(&self) -> Option<usize> {
+        if let Kind::Plain = self.kind {
+            return Op::from_name(self.name)?
+                .arity()
+                .map(|(_, results)| results);
+        }
+        let mut results = 0;
+        for immediate in &self.immediates {
+            if let Node::List { items, .. } = immediate {
+                match head(items) {
+                    Some("result") => {
+                        results += items[1..]
+                            .iter()
+                            .filter(|item| matches!(item, Node::Atom(_) | Node::List { .. }))
+                            .count()
+                    }
+                    Some("param") | Some("type") => return None,
+                    _ => {}
+                }
+            }
+        }
+        Some(results)
+    }
+}
+
+/// Parses instructions up to an `end` or `else`, which is consumed and returned too.
+fn items<'a>(nodes: &'a [Node], pos: &mut usize) -> (Vec<Item<'a>>, Option<&'a str>) {
+    let mut items = Vec::new();
+    while let Some(node) = nodes.get(*pos) {
+        *pos += 1;
+        let item = match node {
+            Node::Atom(Token::Instruction(name)) if name == "end" || name == "else" => {
+                return (items, Some(name));
+            }
+            Node::Atom(Token::Instruction(name)) if !is_immediate_keyword(name) => {
+                let immediates = immediates(nodes, pos);
+                let kind = if BLOCKS.contains(&name.as_str()) {
+                    let (body, terminator) = self::items(nodes, pos);
+                    if name == "if" {
+                        let (then, otherwise) = match terminator {
+                            Some("else") => {
+                                let label = label(nodes, pos);
+                                (body, Some((label, self::items(nodes, pos).0)))
+                            }
+                            _ => (body, None),
+                        };
+                        Kind::If {
+                            then,
+                            otherwise,
+                            end: label(nodes, pos),
+                        }
+                    } else {
+                        Kind::Block {
+                            body,
+                            end: label(nodes, pos),
+                        }
+                    }
+                } else {
+                    Kind::Plain
+                };
+                Item::Instr(Instr {
+                    name,
+                    immediates,
+                    folded: false,
+                    operands: Vec::new(),
+                    kind,
+                })
+            }
+            node => folded(node).unwrap_or(Item::Other(node)),
+        };
+        items.push(item);
+    }
+    (items, None)
+}
+
+/// Parses a sequence of instructions where there shouldn't be any `end`s or `else`s, e.g. a
+/// folded block's body.
+fn sequence(nodes: &[Node]) -> Vec<Item<'_>> {
+    let mut pos = 0;
+    let mut sequence = Vec::new();
+    while pos < nodes.len() {
+        let (mut items, terminator) = items(nodes, &mut pos);
+        sequence.append(&mut items);
+        if terminator.is_some() {
+            sequence.push(Item::Other(&nodes[pos - 1]));
+        }
+    }
+    sequence
+}
+
+fn immediates<'a>(nodes: &'a [Node], pos: &mut usize) -> Vec<&'a Node> {
+    let mut immediates = Vec::new();
+    while let Some(node) = nodes.get(*pos) {
+        let immediate = match node {
+            Node::Atom(Token::Instruction(keyword)) => is_immediate_keyword(keyword),
+            Node::Atom(Token::Identifier(_))
+            | Node::Atom(Token::NumberLiteral { .. })
+            | Node::Atom(Token::StringLiteral { .. })
+            | Node::Atom(Token::Reserved(_)) => true,
+            Node::List { .. } => list_head(node).is_some_and(|h| IMMEDIATE_LISTS.contains(&h)),
+            _ => false,
+        };
+        if !immediate {
+            break;
+        }
+        immediates.push(node);
+        *pos += 1;
+    }
+    immediates
+}
+
+/// The label that can follow an `end` or `else`.
+fn label<'a>(nodes: &'a [Node], pos: &mut usize) -> Option<&'a Node> {
+    match nodes.get(*pos) {
+        Some(node @ Node::Atom(Token::Identifier(_))) => {
+            *pos += 1;
+            Some(node)
+        }
+        _ => None,
+    }
+}
+
+/// Parses a folded instruction, or returns `None` if `node` isn't one.
+fn folded<'a>(node: &'a Node) -> Option<Item<'a>> {
+    let Node::List {
+        items: nodes,
+        closed: true,
+    } = node
+    else {
+        return None;
+    };
+    let name = match nodes.first()? {
+        Node::Atom(Token::Instruction(name)) => name.as_str(),
+        _ => return None,
+    };
+    let name = match name {
+        name if is_immediate_keyword(name)
+            || ["then", "else", "end"].contains(&name)
+            || IMMEDIATE_LISTS.contains(&name) =>
+        {
+            return None;
+        }
+        name => name,
+    };
+    let mut pos = 1;
+    let immediates = immediates(nodes, &mut pos);
+    let rest = &nodes[pos..];
+    let (operands, kind) = if name == "if" {
+        let then = rest.iter().position(|n| list_head(n) == Some("then"))?;
+        let branch = |node: &'a Node| match node {
+            Node::List {
+                items,
+                closed: true,
+            } => Some(sequence(&items[1..])),
+            _ => None,
+        };
+        let otherwise = match &rest[then + 1..] {
+            [] => None,
+            [node] if list_head(node) == Some("else") => Some((None, branch(node)?)),
+            _ => return None,
+        };
+        let kind = Kind::If {
+            then: branch(&rest[then])?,
+            otherwise,
+            end: None,
+        };
+        (sequence(&rest[..then]), kind)
+    } else if BLOCKS.contains(&name) {
+        let kind = Kind::Block {
+            body: sequence(rest),
+            end: None,
+        };
+        (Vec::new(), kind)
+    } else {
+        (sequence(rest), Kind::Plain)
+    };
+    Some(Item::Instr(Instr {
+        name,
+        immediates,
+        folded: true,
+        operands,
+        kind,
+    }))
+}
+
+/// Nests each instruction's operands inside it where possible. This never changes what the
+/// code does, since unfolding `(op a b)` always gives back `a b op`.
+fn fold(items: Vec<Item<'_>>) -> Vec<Item<'_>> {
+    let mut folded = Vec::new();
+    for item in items {
+        let Item::Instr(mut instr) = item else {
+            folded.push(item);
+            continue;
+        };
+        instr.folded = true;
+        instr.operands = fold(instr.operands);
+        let operands = match &mut instr.kind {
+            Kind::Plain => Op::from_name(instr.name)
+                .and_then(Op::arity)
+                .map_or(0, |(params, _)| params),
+            Kind::Block { body, .. } => {
+                *body = fold(core::mem::take(body));
+                0
+            }
+            Kind::If {
+                then, otherwise, ..
+            } => {
+                *then = fold(core::mem::take(then));
+                if let Some((_, otherwise)) = otherwise {
+                    *otherwise = fold(core::mem::take(otherwise));
+                }
+                1
+            }
+        };
+        if instr.operands.is_empty() && operands > 0 {
+            if let Some(start) = operand_start(&folded, operands) {
+                instr.operands = folded.split_off(start);
+            }
+        }
+        folded.push(Item::Instr(instr));
+    }
+    folded
+}
+
+/// Where the last `count` single-value instructions start, skipping over comments.
+fn operand_start(items: &[Item<'_>], count: usize) -> Option<usize> {
+    let mut found = 0;
+    for (i, item) in items.iter().enumerate().rev() {
+        match item {
+            Item::Other(Node::Comment { .. }) => {}
+            Item::Instr(instr) if instr.results() == Some(1) => {
+                found += 1;
+                if found == count {
+                    return Some(i);
+                }
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+struct Writer {
+    out: String,
+    options: FormatOptions,
+    depth: usize,
+    line_start: bool,
+    /// A `;;` comment has been written, so nothing else can go on this line.
+    after_line_comment: bool,
+    /// A blank line is due before the next line.
+    blank: bool,
+}
+
+impl Writer {
+    fn newline(&mut self) {
+        if self.out.is_empty() {
+            return;
+        }
+        self.out.push('\n');
+        if self.blank {
+            self.out.push('\n');
+            self.blank = false;
+        }
+        self.line_start = true;
+        self.after_line_comment = false;
+    }
+
+    fn indent(&mut self) {
+        if self.line_start {
+            for _ in 0..self.depth {
+                match self.options.indent {
+                    Indent::Spaces(n) => self.out.extend(core::iter::repeat_n(' ', n)),
+                    Indent::Tabs => self.out.push('\t'),
+                }
+            }
+            self.line_start = false;
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        if self.after_line_comment {
+            self.newline();
+        }
+        if !self.line_start && !self.out.ends_with('(') {
+            self.out.push(' ');
+        }
+        self.indent();
+        self.out.push_str(word);
+    }
+
+    fn close(&mut self) {
+        if self.after_line_comment {
+            self.newline();
+        }
+        self.indent();
+        self.out.push(')');
+        self.blank = false;
+    }
+
+    fn comment(&mut self, text: &str, line: bool, own_line: bool) {
+        if own_line && !self.line_start {
+            self.newline();
+        }
+        self.word(text);
+        self.after_line_comment = line;
+    }
+
+    /// Writes nodes one per line, like the top level of a file or the fields of a module.
+    fn sequence(&mut self, nodes: &[Node]) {
+        for (i, node) in nodes.iter().enumerate() {
+            match node {
+                Node::Blank => self.blank = i > 0,
+                Node::Comment {
+                    text,
+                    line,
+                    own_line,
+                } => self.comment(text, *line, *own_line),
+                node => {
+                    self.newline();
+                    self.node(node);
+                }
+            }
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Atom(token) => self.word(token.text()),
+            Node::Comment { text, line, .. } => self.comment(text, *line, false),
+            Node::Blank => {}
+            Node::List { items, closed } => {
+                self.word("(");
+                if !needs_break(items) {
+                    for item in items {
+                        self.node(item);
+                    }
+                } else if head(items) == Some("func") {
+                    self.func(items);
+                } else {
+                    self.broken(items);
+                }
+                if *closed {
+                    self.close();
+                }
+            }
+        }
+    }
+
+    /// Writes the atoms at the start of a list on the first line, and then everything else
+    /// on lines of their own.
+    fn broken(&mut self, items: &[Node]) {
+        self.depth += 1;
+        let start = items
+            .iter()
+            .position(|item| matches!(item, Node::List { .. }))
+            .unwrap_or(items.len());
+        for item in &items[..start] {
+            if let Node::Comment {
+                text,
+                line,
+                own_line,
+            } = item
+            {
+                self.comment(text, *line, *own_line);
+            } else {
+                self.node(item);
+            }
+        }
+        self.sequence(&items[start..]);
+        self.depth -= 1;
+    }
+
+    fn func(&mut self, items: &[Node]) {
+        self.depth += 1;
+        let body = func_body_start(items);
+        let mut last_local = false;
+        for item in &items[..body] {
+            match item {
+                Node::Comment {
+                    text,
+                    line,
+                    own_line,
+                } => self.comment(text, *line, *own_line),
+                Node::Blank => {}
+                item if list_head(item) == Some("local") => {
+                    if !last_local {
+                        self.newline();
+                    }
+                    self.node(item);
+                    last_local = true;
+                    continue;
+                }
+                item => self.node(item),
+            }
+            last_local = false;
+        }
+        let body = sequence(&items[body..]);
+        let body = match self.options.style {
+            Some(Style::Folded) => fold(body),
+            _ => body,
+        };
+        self.items(&body);
+        self.depth -= 1;
+    }
+
+    fn items(&mut self, items: &[Item<'_>]) {
+        for (i, item) in items.iter().enumerate() {
+            match item {
+                Item::Other(Node::Blank) => self.blank = i > 0,
+                Item::Other(Node::Comment {
+                    text,
+                    line,
+                    own_line,
+                }) => self.comment(text, *line, *own_line),
+                Item::Other(node) => {
+                    self.newline();
+                    self.node(node);
+                }
+                Item::Instr(instr) => self.instr(instr),
+            }
+        }
+    }
+
+    fn instr(&mut self, instr: &Instr<'_>) {
+        let folded = match self.options.style {
+            Some(Style::Flat) => false,
+            Some(Style::Folded) => true,
+            None => instr.folded,
+        };
+        if folded {
+            self.newline();
+            self.word("(");
+            self.word(instr.name);
+            for immediate in &instr.immediates {
+                self.node(immediate);
+            }
+            self.depth += 1;
+            self.items(&instr.operands);
+            match &instr.kind {
+                Kind::Plain => {}
+                Kind::Block { body, .. } => self.items(body),
+                Kind::If {
+                    then, otherwise, ..
+                } => {
+                    self.branch("then", then);
+                    if let Some((_, otherwise)) = otherwise {
+                        self.branch("else", otherwise);
+                    }
+                }
+            }
+            self.depth -= 1;
+            self.close();
+            return;
+        }
+
+        self.items(&instr.operands);
+        self.newline();
+        self.word(instr.name);
+        for immediate in &instr.immediates {
+            self.node(immediate);
+        }
+        match &instr.kind {
+            Kind::Plain => {}
+            Kind::Block { body, end } => {
+                self.indented(body);
+                self.end("end", *end);
+            }
+            Kind::If {
+                then,
+                otherwise,
+                end,
+            } => {
+                self.indented(then);
+                if let Some((label, otherwise)) = otherwise {
+                    self.end("else", *label);
+                    self.indented(otherwise);
+                }
+                self.end("end", *end);
+            }
+        }
+    }
+
+    fn branch(&mut self, keyword: &str, items: &[Item<'_>]) {
+        self.newline();
+        self.word("(");
+        self.word(keyword);
+        self.indented(items);
+        self.close();
+    }
+
+    fn indented(&mut self, items: &[Item<'_>]) {
+        self.depth += 1;
+        self.items(items);
+        self.depth -= 1;
+    }
+
+    fn end(&mut self, keyword: &str, label: Option<&Node>) {
+        self.newline();
+        self.word(keyword);
+        if let Some(label) = label {
+            self.node(label);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSY: &str = r#";; adds things
+(module $m   (type $t (func (param i32)
+  (result i32)))
+
+
+   (func $add (export "add") (param $a i32) (param $b i32) (result i32) (local $c i32) (local i64)
+  ;; the sum
+      local.get $a local.get $b
+  i32.add (; inline ;) ;; trailing
+  block $l (result i32) i32.const 1 br_if $l (i32.eqz (local.get $a)) drop i32.const 2 end $l
+  local.get $a if (result i32) i32.const 1 else i32.const 2 end
+  drop call $f)
+  (func $f (param i32) (result i32) (call $add (local.get 0) (i32.const 1)))
+  (global $g (mut i32) (i32.const 0))
+     (data (i32.const 0) "hi") ;; data
+)
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))
+"#;
+
+    fn comments(src: &str) -> Vec<Token> {
+        TokenList::from(src.to_string())
+            .list()
+            .into_iter()
+            .filter(|t| matches!(t, Token::Comment { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_style() {
+        assert_eq!(
+            format(MESSY, FormatOptions::default()),
+            r#";; adds things
+(module $m
+  (type $t (func (param i32) (result i32)))
+
+  (func $add (export "add") (param $a i32) (param $b i32) (result i32)
+    (local $c i32) (local i64)
+    ;; the sum
+    local.get $a
+    local.get $b
+    i32.add (; inline ;) ;; trailing
+    block $l (result i32)
+      i32.const 1
+      br_if $l
+      (i32.eqz
+        (local.get $a))
+      drop
+      i32.const 2
+    end $l
+    local.get $a
+    if (result i32)
+      i32.const 1
+    else
+      i32.const 2
+    end
+    drop
+    call $f)
+  (func $f (param i32) (result i32)
+    (call $add
+      (local.get 0)
+      (i32.const 1)))
+  (global $g (mut i32) (i32.const 0))
+  (data (i32.const 0) "hi") ;; data
+)
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))
+"#
+        );
+    }
+
+    #[test]
+    fn flat() {
+        let options = FormatOptions {
+            style: Some(Style::Flat),
+            indent: Indent::Tabs,
+        };
+        assert_eq!(
+            format(
+                "(func (if (result i32) (local.get 0) (then (i32.const 1)) (else (unreachable))))",
+                options
+            ),
+            "(func\n\tlocal.get 0\n\tif (result i32)\n\t\ti32.const 1\n\telse\n\t\tunreachable\n\tend)\n"
+        );
+    }
+
+    #[test]
+    fn folded() {
+        let options = FormatOptions {
+            style: Some(Style::Folded),
+            indent: Indent::Spaces(1),
+        };
+        assert_eq!(
+            format(
+                "(func local.get 0 ;; x\n i32.const 1 i32.add if nop end local.get 1 call 0 drop)",
+                options
+            ),
+            "(func
+ (if
+  (i32.add
+   (local.get 0) ;; x
+   (i32.const 1))
+  (then
+   (nop)))
+ (local.get 1)
+ (call 0)
+ (drop))
+"
+        );
+    }
+
+    #[test]
+    fn idempotent_and_keeps_comments() {
+        let sources = [
+            MESSY,
+            "(module (func (; a (; nested ;) comment ;) nop ;; x\n))",
+            "(module (func i32.const 1 (if (then)) end)) )",
+            "(module (func (block ;; open\n nop",
+            "(@custom \"x\" \"y\") (module (func (@metadata.code.branch_hint \"\\01\") nop))",
+            "",
+        ];
+        for src in sources {
+            for style in [None, Some(Style::Flat), Some(Style::Folded)] {
+                let options = FormatOptions {
+                    style,
+                    ..FormatOptions::default()
+                };
+                let once = format(src, options);
+                assert_eq!(format(&once, options), once, "{:?}", src);
+                assert_eq!(comments(&once), comments(src), "{:?}", src);
+            }
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use crate::features::Features;
+use crate::opcodes::Op;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::ops::Range;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
@@ -32,8 +36,41 @@ pub enum Token {
 
 use Token::*;
 
+impl Token {
+    /// The token as it appears in the source.
+    pub fn text(&self) -> &str {
+        match self {
+            LParen => "(",
+            RParen => ")",
+            Identifier(string)
+            | Annotation(string)
+            | Instruction(string)
+            | Reserved(string)
+            | Space(string)
+            | StringLiteral { string, .. }
+            | NumberLiteral { string, .. }
+            | Comment { string, .. } => string,
+        }
+    }
+
+    /// Whether the token is a string or block comment that reached the end of the source before
+    /// it was closed.
+    fn is_unterminated(&self) -> bool {
+        match self {
+            Reserved(text) => text.starts_with('"'),
+            Comment {
+                string,
+                multiline: true,
+                nested_level,
+            } => *nested_level != 0 || string.len() < 4 || !string.ends_with(";)"),
+            _ => false,
+        }
+    }
+}
+
+/// The tokens, the source, and the byte range that each token covers in the source.
 #[derive(Clone)]
-pub struct TokenList(Vec<Token>, String);
+pub struct TokenList(Vec<Token>, String, Vec<Range<usize>>);
 
 impl TokenList {
     pub fn list(self) -> Vec<Token> {
@@ -43,6 +80,140 @@ impl TokenList {
     pub fn src(self) -> String {
         self.1
     }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.0
+    }
+
+    pub fn source(&self) -> &str {
+        &self.1
+    }
+
+    /// The byte range that each token covers in the source, in the same order as the tokens.
+    pub fn spans(&self) -> &[Range<usize>] {
+        &self.2
+    }
+
+    /// Reports the tokens that can't appear in the text format, and unbalanced parentheses.
+    /// The lexer never stops at a bad token, so this finds all of them (up to the limit): an
+    /// unmatched `)` is skipped, and each `(` left open at the end is reported once.
+    pub fn errors(&self, diagnostics: &mut Diagnostics) {
+        let mut open = Vec::new();
+        for (token, span) in self.0.iter().zip(self.2.iter().cloned()) {
+            if diagnostics.is_full() {
+                return;
+            }
+            let message = match token {
+                LParen => {
+                    open.push(span);
+                    continue;
+                }
+                RParen if open.pop().is_some() => continue,
+                RParen => "unexpected `)`".to_string(),
+                Reserved(text) if text.starts_with('"') => "unterminated string".to_string(),
+                Comment { .. } if token.is_unterminated() => {
+                    "unterminated block comment".to_string()
+                }
+                Reserved(text) => format!("unknown token `{}`", text),
+                Identifier(id) if id == "$" => "empty identifier".to_string(),
+                _ => continue,
+            };
+            diagnostics.push(Diagnostic::error(message).with_label(span, ""));
+        }
+        for span in open {
+            diagnostics.push(Diagnostic::error("unclosed `(`").with_label(span, "never closed"));
+        }
+    }
+
+    /// Reports each instruction that's from a proposal that isn't in `features`, at its
+    /// keyword. Until there's a parser this only looks at instruction names, so types and
+    /// fields from a proposal (like `v128` or `shared`) aren't caught.
+    pub fn check_features(&self, features: Features, diagnostics: &mut Diagnostics) {
+        for (token, span) in self.0.iter().zip(self.2.iter().cloned()) {
+            if diagnostics.is_full() {
+                return;
+            }
+            let Instruction(keyword) = token else {
+                continue;
+            };
+            let Some(proposal) = Op::from_name(keyword).and_then(Op::proposal) else {
+                continue;
+            };
+            if !features.enabled(proposal) {
+                let message = format!("the `{}` proposal is not enabled", proposal);
+                let label = format!("`{}` is from it", keyword);
+                diagnostics.push(Diagnostic::error(message).with_label(span, label));
+            }
+        }
+    }
+
+    /// Replaces a byte range of the source with `text`, for editors, and re-lexes only the part
+    /// of it that could have changed. That starts at the token before the edit (or further back
+    /// while tokens aren't separated by whitespace), and stops at the first token after the edit
+    /// that follows whitespace, as long as nothing before it is left open - otherwise the rest
+    /// of the source is re-lexed, since e.g. an opening `"` or `(;` can change all of it.
+    ///
+    /// Returns the indices of the new tokens that replaced the old ones. The result is always
+    /// the same as lexing the new source from scratch. Only the tokens are updated - there's no
+    /// parser yet, so nothing gets re-parsed.
+    ///
+    /// # Panics
+    ///
+    /// If `range` is out of bounds of the source, or either end of it isn't on a `char`
+    /// boundary, like [`String::replace_range`].
+    pub fn edit(&mut self, range: Range<usize>, text: &str) -> Range<usize> {
+        let spans = &self.2;
+        let mut first = spans
+            .partition_point(|span| span.end < range.start)
+            .saturating_sub(1);
+        while first > 0 && spans[first - 1].end == spans[first].start {
+            first -= 1;
+        }
+        let restart = if first == 0 { 0 } else { spans[first].start };
+        let resume = (first..spans.len()).find(|&i| {
+            spans[i].start > range.end
+                && self.1.as_bytes()[spans[i].start - 1].is_ascii_whitespace()
+        });
+
+        let shift = |offset: usize| offset + text.len() - range.len();
+        self.1.replace_range(range.clone(), text);
+        let window = resume.and_then(|resume| {
+            let end = shift(spans[resume].start);
+            let window = TokenList::from(self.1[restart..end].to_string());
+            let closed = match (window.0.last(), window.2.last()) {
+                (Some(token), Some(span)) => {
+                    let gap = &self.1[restart + span.end..end];
+                    let line_comment = matches!(
+                        token,
+                        Comment {
+                            multiline: false,
+                            ..
+                        }
+                    );
+                    let ended = !line_comment || gap.contains('\n');
+                    !token.is_unterminated() && !gap.is_empty() && ended
+                }
+                _ => true,
+            };
+            closed.then_some((resume, window))
+        });
+        let (end, window) = window.unwrap_or_else(|| {
+            let rest = TokenList::from(self.1[restart..].to_string());
+            (self.0.len(), rest)
+        });
+        let count = window.0.len();
+        // the spans after the window only move by the change in length
+        for span in &mut self.2[end..] {
+            *span = shift(span.start)..shift(span.end);
+        }
+        let spans = window
+            .2
+            .into_iter()
+            .map(|span| restart + span.start..restart + span.end);
+        self.2.splice(first..end, spans);
+        self.0.splice(first..end, window.0);
+        first..first + count
+    }
 }
 
 impl From<String> for TokenList {
@@ -69,39 +240,43 @@ impl From<String> for TokenList {
             }};
         }
 
-        fn check_token(tokenlist: &mut Vec<Token>, index: usize) {
-            match tokenlist.last().unwrap_or(&Space("".to_string())) {
-                Instruction(string) => {
-                    if string.len() == 3 {
-                        let lcs = &string.to_lowercase()[..];
-                        if let "nan" | "inf" = lcs {
-                            let new = string.clone();
-                            tokenlist[index] = NumberLiteral {
-                                string: new,
-                                hex: false,
-                                exponent: false,
-                                dec_point: false,
-                                last_char_is_exponent: false,
-                                signed: false,
-                            };
-                        }
-                    }
+        fn is_nan_or_inf(string: &str) -> bool {
+            let lcs = &string.to_lowercase()[..];
+            match lcs.strip_prefix("nan:0x") {
+                Some(payload) => {
+                    !payload.is_empty()
+                        && payload.chars().all(|c| c.is_ascii_hexdigit() || c == '_')
                 }
-                Reserved(string) => {
-                    if string.len() == 4 {
-                        let lcs = &string.to_lowercase()[..];
-                        if let "+inf" | "-inf" = lcs {
-                            let new = string.clone();
-                            tokenlist[index] = NumberLiteral {
-                                string: new,
-                                hex: false,
-                                exponent: false,
-                                dec_point: false,
-                                last_char_is_exponent: false,
-                                signed: false,
-                            };
-                        }
-                    }
+                None => matches!(lcs, "nan" | "inf"),
+            }
+        }
+
+        fn check_token(tokenlist: &mut [Token], index: usize) {
+            match &tokenlist[index] {
+                Instruction(string) if is_nan_or_inf(string) => {
+                    let new = string.clone();
+                    tokenlist[index] = NumberLiteral {
+                        string: new,
+                        hex: false,
+                        exponent: false,
+                        dec_point: false,
+                        last_char_is_exponent: false,
+                        signed: false,
+                    };
+                }
+                Reserved(string)
+                    if (string.starts_with('+') || string.starts_with('-'))
+                        && is_nan_or_inf(&string[1..]) =>
+                {
+                    let new = string.clone();
+                    tokenlist[index] = NumberLiteral {
+                        string: new,
+                        hex: false,
+                        exponent: false,
+                        dec_point: false,
+                        last_char_is_exponent: false,
+                        signed: true,
+                    };
                 }
                 StringLiteral {
                     string,
@@ -109,7 +284,7 @@ impl From<String> for TokenList {
                     ..
                 } => {
                     let last_char = unsafe { string.chars().last().unwrap_unchecked() };
-                    if last_char != '"' || *last_char_escaped {
+                    if string.len() < 2 || last_char != '"' || *last_char_escaped {
                         let new = string.clone();
                         tokenlist[index] = Reserved(new);
                     }
@@ -135,7 +310,7 @@ impl From<String> for TokenList {
                             Space(string) => {
                                 string.push(ch);
                                 None
-                            },
+                            }
                             StringLiteral {
                                 string,
                                 escape_next_char,
@@ -158,7 +333,7 @@ impl From<String> for TokenList {
                             Space(string) => {
                                 string.push(ch);
                                 None
-                            },
+                            }
                             StringLiteral {
                                 string,
                                 escape_next_char,
@@ -397,17 +572,23 @@ impl From<String> for TokenList {
                             StringLiteral {
                                 string,
                                 escape_next_char,
-                                ..
+                                last_char_escaped,
                             } => {
                                 if !*escape_next_char {
+                                    *last_char_escaped = false;
                                     string.push('"');
                                     Some(Space("".to_string()))
                                 } else {
                                     *escape_next_char = false;
+                                    *last_char_escaped = true;
                                     string.push('"');
                                     None
                                 }
                             }
+                            Comment { string, .. } => {
+                                string.push('"');
+                                None
+                            }
                             _ => Some(StringLiteral {
                                 string: '"'.to_string(),
                                 escape_next_char: false,
@@ -721,7 +902,7 @@ impl From<String> for TokenList {
                             Space(_) | RParen | LParen => Some(Reserved(ch.to_string())),
                         },
                         _ => match last_token {
-                            Reserved(string) => {
+                            Comment { string, .. } | Reserved(string) => {
                                 string.push(ch);
                                 None
                             }
@@ -737,10 +918,9 @@ impl From<String> for TokenList {
                                     ch
                                 )
                             }
-                            Comment { string, .. }
-                            | Annotation(string)
-                            | Identifier(string)
-                            | Instruction(string) => last_reserved!(string, ch),
+                            Annotation(string) | Identifier(string) | Instruction(string) => {
+                                last_reserved!(string, ch)
+                            }
                             _ => Some(Reserved(ch.to_string())),
                         },
                     }
@@ -759,12 +939,30 @@ impl From<String> for TokenList {
             check_token(&mut token_list, len - 1);
         }
         token_list.retain(|t| !matches!(t, Space(_)));
-        TokenList(token_list, src)
+        let spans = find_spans(&token_list, &src);
+        TokenList(token_list, src, spans)
     }
 }
 
-#[rustversion::nightly]
+/// Finds where each token is in the source, in order.
+fn find_spans(tokens: &[Token], src: &str) -> Vec<Range<usize>> {
+    let mut pos = 0;
+    tokens
+        .iter()
+        .map(|token| {
+            let text = token.text();
+            let start = match src[pos..].find(text) {
+                Some(offset) => pos + offset,
+                None => pos,
+            };
+            pos = (start + text.len()).min(src.len());
+            start..pos
+        })
+        .collect()
+}
+
 #[cfg(test)]
+#[allow(clippy::get_first)]
 mod tests {
     use super::*;
     use assert_matches::assert_matches;
@@ -1290,10 +1488,256 @@ mod tests {
             Comment { string, .. } if *string == s
         );
     }
+
+    #[test]
+    fn atomic_memarg() {
+        let s = "i32.atomic.rmw8.cmpxchg_u offset=8 align=1".to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.clone().list().len(), 3);
+        assert_matches!(
+            l.clone().list().get(0).unwrap(),
+            Instruction(string) if *string == "i32.atomic.rmw8.cmpxchg_u"
+        );
+        assert_matches!(
+            l.clone().list().get(1).unwrap(),
+            Instruction(string) if *string == "offset=8"
+        );
+        assert_matches!(
+            l.clone().list().get(2).unwrap(),
+            Instruction(string) if *string == "align=1"
+        );
+    }
+
+    #[test]
+    fn memarg_memory_index() {
+        let s = "i64.load $mem2 offset=0x1_0000_0000".to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.clone().list().len(), 3);
+        assert_matches!(
+            l.clone().list().get(1).unwrap(),
+            Identifier(string) if *string == "$mem2"
+        );
+        assert_matches!(
+            l.clone().list().get(2).unwrap(),
+            Instruction(string) if *string == "offset=0x1_0000_0000"
+        );
+    }
+
+    #[test]
+    fn branch_hint_annotation() {
+        let s = r#"(@metadata.code.branch_hint "\01") br_if 0"#.to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.clone().list().len(), 6);
+        assert_matches!(
+            l.clone().list().get(1).unwrap(),
+            Annotation(string) if *string == "@metadata.code.branch_hint"
+        );
+        assert_matches!(
+            l.clone().list().get(2).unwrap(),
+            StringLiteral { string, .. } if *string == r#""\01""#
+        );
+        assert_matches!(l.clone().list().get(3).unwrap(), RParen);
+    }
+
+    #[test]
+    fn comment_with_quote() {
+        let s = r#";; it's a "comment", really"#.to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.clone().list().len(), 1);
+        assert_matches!(
+            l.clone().list().get(0).unwrap(),
+            Comment { string, .. } if *string == s
+        );
+        let s = r#"(; "quoted" ;)"#.to_string();
+        let l = TokenList::from(s.clone() + " x");
+        assert_eq!(l.clone().list().len(), 2);
+        assert_matches!(
+            l.clone().list().get(0).unwrap(),
+            Comment { string, .. } if *string == s
+        );
+    }
+
+    #[test]
+    fn nan_payload() {
+        let s = "(f32.const nan:0x1_f) -nan +inf".to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.clone().list().len(), 6);
+        assert_matches!(
+            l.clone().list().get(2).unwrap(),
+            NumberLiteral { string, signed: false, .. } if *string == "nan:0x1_f"
+        );
+        assert_matches!(
+            l.clone().list().get(4).unwrap(),
+            NumberLiteral { string, signed: true, .. } if *string == "-nan"
+        );
+        assert_matches!(
+            l.clone().list().get(5).unwrap(),
+            NumberLiteral { string, signed: true, .. } if *string == "+inf"
+        );
+    }
+
+    #[test]
+    fn string_ending_in_escape() {
+        let s = r#""\01" x"#.to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.clone().list().len(), 2);
+        assert_matches!(
+            l.clone().list().get(0).unwrap(),
+            StringLiteral { string, .. } if *string == r#""\01""#
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let s = "(func $f ;; hi\n  nop)".to_string();
+        let l = TokenList::from(s.clone());
+        assert_eq!(l.spans(), vec![0..1, 1..5, 6..8, 9..14, 17..20, 20..21]);
+    }
+
+    /// Checks that an edit leaves the same tokens as lexing the edited source from scratch.
+    fn check_edit(src: &str, range: Range<usize>, text: &str) {
+        let mut l = TokenList::from(src.to_string());
+        let old = l.0.clone();
+        let changed = l.edit(range.clone(), text);
+        let mut expected_src = src.to_string();
+        expected_src.replace_range(range.clone(), text);
+        let expected = TokenList::from(expected_src.clone());
+        assert_eq!(l.1, expected_src);
+        assert_eq!(
+            l.0, expected.0,
+            "{:?} with {:?} replaced by {:?}",
+            src, range, text
+        );
+        assert_eq!(l.2, expected.2);
+        // the tokens outside of the changed range are the old ones
+        let kept = l.0.len() - changed.end;
+        assert_eq!(l.0[..changed.start], old[..changed.start]);
+        assert_eq!(l.0[changed.end..], old[old.len() - kept..]);
+    }
+
+    #[test]
+    fn edit() {
+        let s = "(module (func $f (param i32) ;; hi\n  local.get 0 (; block ;) i32.const 12))";
+        check_edit(s, 0..0, "");
+        check_edit(s, 0..0, " ");
+        check_edit(s, 14..14, "oo");
+        check_edit(s, 42..43, "1");
+        check_edit(s, 8..9, "");
+        check_edit(s, 54..54, "x");
+        check_edit(s, 17..17, "\"");
+        check_edit(s, 17..17, "(;");
+        check_edit(s, 33..34, "");
+        check_edit(s, 29..31, ";)");
+        check_edit(s, 0..s.len(), "");
+        check_edit("", 0..0, "(func)");
+        check_edit("a b", 1..2, "");
+
+        let mut l = TokenList::from(s.to_string());
+        // only the `0` is re-lexed, with the `local.get` before it
+        let zero = s.find(" 0 ").unwrap() + 1;
+        assert_eq!(l.edit(zero..zero + 1, "345"), 10..12);
+        assert_matches!(&l.0[11], NumberLiteral { string, .. } if string == "345");
+    }
+
+    #[test]
+    fn edit_matches_lexing() {
+        const PIECES: &[&str] = &[
+            "(", ")", " ", "\n", "\"", "\\", "(;", ";)", ";;", "$a", "1", "0x", ".", "e", "-", "x",
+            "i32.add", "(@a", "nop", "é",
+        ];
+        let mut seed: u64 = 0x2545f4914f6cdd1d;
+        let mut random = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize % n
+        };
+        let mut src = String::from("(module (func $f (param i32) ;; hi\n  local.get 0))");
+        for _ in 0..2000 {
+            let mut boundaries = (0..=src.len()).filter(|&i| src.is_char_boundary(i));
+            let count = boundaries.clone().count();
+            let start = boundaries.nth(random(count)).unwrap();
+            let end = (start..=src.len())
+                .filter(|&i| src.is_char_boundary(i))
+                .nth(random(4))
+                .unwrap_or(start);
+            let text: String = (0..random(3))
+                .map(|_| PIECES[random(PIECES.len())])
+                .collect();
+            check_edit(&src, start..end, &text);
+            src.replace_range(start..end, &text);
+            if src.len() > 400 {
+                src.truncate(src.char_indices().nth(100).map_or(src.len(), |(i, _)| i));
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let s = "(module 1x) $ (func) (; oops".to_string();
+        let l = TokenList::from(s.clone());
+        let errors = |limit| {
+            let mut diagnostics = Diagnostics::new(limit);
+            l.errors(&mut diagnostics);
+            diagnostics
+                .into_vec()
+                .into_iter()
+                .map(|d| (d.span(), d.message))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            errors(None),
+            vec![
+                (Some(8..10), "unknown token `1x`".to_string()),
+                (Some(12..13), "empty identifier".to_string()),
+                (Some(21..28), "unterminated block comment".to_string()),
+            ]
+        );
+        assert_eq!(errors(Some(1)).len(), 2);
+
+        let s = "(module (func nop)))\n(func".to_string();
+        let messages: Vec<_> = {
+            let mut diagnostics = Diagnostics::new(None);
+            TokenList::from(s).errors(&mut diagnostics);
+            diagnostics
+                .into_vec()
+                .into_iter()
+                .map(|d| d.span())
+                .collect()
+        };
+        assert_eq!(messages, vec![Some(19..20), Some(21..22)]);
+    }
+
+    #[test]
+    fn disabled_proposals() {
+        let l = TokenList::from("(func i32.extend8_s (atomic.fence) i32.add)".to_string());
+        let errors = |features| {
+            let mut diagnostics = Diagnostics::new(None);
+            l.check_features(features, &mut diagnostics);
+            diagnostics
+                .into_vec()
+                .into_iter()
+                .map(|d| (d.span(), d.message))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            errors(Features::v1()),
+            vec![
+                (
+                    Some(6..19),
+                    "the `sign-extension-ops` proposal is not enabled".to_string()
+                ),
+                (
+                    Some(21..33),
+                    "the `threads` proposal is not enabled".to_string()
+                ),
+            ]
+        );
+        assert_eq!(errors(Features::all()), vec![]);
+    }
 }
 
-#[rustversion::nightly]
-#[cfg(test)]
+#[cfg(all(test, nightly))]
 mod benches {
     use super::*;
     use test::Bencher;
@@ -1312,6 +1756,15 @@ mod benches {
         b.iter(|| TokenList::from(HEX_EXPONENT.to_string()));
     }
 
+    #[bench]
+    fn bench_edit(b: &mut Bencher) {
+        let src = "(func $f (param i32) (result i32)\n  local.get 0\n  i32.const 1\n  i32.add)\n"
+            .repeat(1000);
+        let l = TokenList::from(src.clone());
+        let middle = src.len() / 2 + 20;
+        b.iter(|| l.clone().edit(middle..middle, " nop "));
+    }
+
     #[bench]
     fn bench_unterminated_string(b: &mut Bencher) {
         let s = r#""this should be reserved"#.to_string();
@@ -1,23 +1,29 @@
 #![no_std]
+#![cfg_attr(all(test, nightly), feature(test))]
 
 #[macro_use]
 extern crate alloc;
 
-#[rustversion::attr(nightly, feature(assert_matches, test))]
-
-#[rustversion::nightly]
-#[cfg(test)]
+#[cfg(all(test, nightly))]
 extern crate test;
 
-#[rustversion::nightly]
-#[cfg(test)]
-#[macro_use]
-extern crate assert_matches;
-
+// only on wasm32, where it keeps binaries small - native builds use the system allocator
+#[cfg(target_arch = "wasm32")]
 extern crate wee_alloc;
 
+#[cfg(target_arch = "wasm32")]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-//pub mod ast;
+pub mod ast;
+pub mod decoder;
+pub mod diagnostic;
+pub mod features;
+pub mod formatter;
+pub mod json;
 pub mod lexer;
+pub mod lsp;
+pub mod opcodes;
+pub mod printer;
+pub mod sourcemap;
+pub mod wast;
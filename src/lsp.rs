@@ -0,0 +1,1223 @@
+//! A language server for the text format. There's no parser yet, so everything here works from
+//! the lexer's tokens and the tree of parenthesised lists they form: `$id`s are matched up by
+//! the keyword or instruction before them rather than by a resolved AST, and hovers can show
+//! declared signatures and instruction types but not the operand stack.
+
+use crate::diagnostic::{Diagnostic, Diagnostics, Severity};
+use crate::json::Json;
+use crate::lexer::{Token, TokenList};
+use crate::opcodes::Op;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+/// The semantic token types, in the order the server's legend lists them.
+pub const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "operator",
+    "variable",
+    "string",
+    "number",
+    "comment",
+    "decorator",
+];
+
+/// The most diagnostics published for a single document.
+const ERROR_LIMIT: usize = 100;
+
+/// The index spaces that `$id`s name.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Namespace {
+    Func,
+    Local,
+    Global,
+    Table,
+    Memory,
+    Type,
+    Elem,
+    Data,
+    Tag,
+    Label,
+}
+
+impl Namespace {
+    /// The namespace defined by a list starting with `(keyword $id`.
+    fn of_keyword(keyword: &str) -> Option<Namespace> {
+        Some(match keyword {
+            "func" | "start" => Namespace::Func,
+            "param" | "local" => Namespace::Local,
+            "global" => Namespace::Global,
+            "table" => Namespace::Table,
+            "memory" => Namespace::Memory,
+            "type" => Namespace::Type,
+            "elem" => Namespace::Elem,
+            "data" => Namespace::Data,
+            "tag" => Namespace::Tag,
+            "block" | "loop" | "if" | "try" | "try_table" => Namespace::Label,
+            _ => return None,
+        })
+    }
+
+    /// The namespace of the `$id`s after an instruction, where it's clear from the name.
+    fn of_instruction(name: &str) -> Option<Namespace> {
+        Some(match name {
+            "call" | "return_call" | "ref.func" => Namespace::Func,
+            "call_indirect" | "return_call_indirect" => Namespace::Table,
+            "call_ref" | "return_call_ref" => Namespace::Type,
+            "throw" | "catch" => Namespace::Tag,
+            "data.drop" => Namespace::Data,
+            "elem.drop" => Namespace::Elem,
+            _ if name.starts_with("local.") => Namespace::Local,
+            _ if name.starts_with("global.") => Namespace::Global,
+            _ if name.starts_with("table.") => Namespace::Table,
+            _ if name.starts_with("struct.") || name.starts_with("array.") => Namespace::Type,
+            _ if name.starts_with("br") => Namespace::Label,
+            _ if name.starts_with("memory.") || name.contains("load") || name.contains("store") => {
+                Namespace::Memory
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// A parenthesised list of tokens.
+#[derive(Debug, PartialEq)]
+struct List {
+    /// The index of the `(` token.
+    open: usize,
+    /// The index of the `)` token, or `None` if the list is never closed.
+    close: Option<usize>,
+    parent: Option<usize>,
+    span: Range<usize>,
+}
+
+/// Where an `$id` is defined.
+#[derive(Debug, PartialEq)]
+struct Definition {
+    namespace: Namespace,
+    /// The index of the `$id` token.
+    token: usize,
+    /// The list that it names, which is `None` for flat `block $l` instructions.
+    list: Option<usize>,
+    /// The byte range it can be referred to from, for locals and labels.
+    scope: Option<Range<usize>>,
+}
+
+/// How the token indices and byte offsets after an edit move.
+#[derive(Clone, Copy)]
+struct Shift {
+    /// The first token after the re-lexed ones, before the edit.
+    tokens: usize,
+    added_tokens: usize,
+    removed_tokens: usize,
+    /// The end of the replaced bytes, before the edit.
+    bytes: usize,
+    added_bytes: usize,
+    removed_bytes: usize,
+}
+
+impl Shift {
+    fn token(&self, i: usize) -> usize {
+        match i >= self.tokens {
+            true => i + self.added_tokens - self.removed_tokens,
+            false => i,
+        }
+    }
+
+    fn offset(&self, offset: usize) -> usize {
+        match offset >= self.bytes {
+            true => offset + self.added_bytes - self.removed_bytes,
+            false => offset,
+        }
+    }
+
+    fn span(&self, span: &Range<usize>) -> Range<usize> {
+        self.offset(span.start)..self.offset(span.end)
+    }
+}
+
+/// A module field, for the document outline.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Field {
+    pub name: String,
+    /// The field's keyword, e.g. `func` or `export`.
+    pub kind: String,
+    pub span: Range<usize>,
+    /// The part of the span to select, which is its `$id` or keyword.
+    pub selection: Range<usize>,
+}
+
+/// An open text format document, lexed and indexed for the queries an editor makes. Offsets
+/// are bytes into [`Document::src`].
+pub struct Document {
+    lexed: TokenList,
+    lists: Vec<List>,
+    /// The innermost list that each token is in. A `(` is in the list it opens.
+    parents: Vec<Option<usize>>,
+    definitions: Vec<Definition>,
+}
+
+impl Document {
+    pub fn new(src: String) -> Document {
+        let mut document = Document {
+            lexed: TokenList::from(src),
+            lists: Vec::new(),
+            parents: Vec::new(),
+            definitions: Vec::new(),
+        };
+        document.index();
+        document
+    }
+
+    /// Replaces a byte range of the source with `text`. Only the tokens around the edit are
+    /// re-lexed (see [`TokenList::edit`]), and only the innermost list around them is indexed
+    /// again, with everything after it moved along - unless the edit changes which `)` closes
+    /// that list, in which case the whole document is.
+    pub fn edit(&mut self, range: Range<usize>, text: &str) {
+        let old_len = self.lexed.tokens().len();
+        let changed = self.lexed.edit(range.clone(), text);
+        let new_len = self.lexed.tokens().len();
+        let old_end = changed.end + old_len - new_len;
+        let mut list = changed.start.checked_sub(1).and_then(|i| self.parents[i]);
+        while let Some(i) = list {
+            if self.lists[i].close.is_some_and(|close| close >= old_end) {
+                break;
+            }
+            list = self.lists[i].parent;
+        }
+        let shift = Shift {
+            tokens: old_end,
+            added_tokens: new_len,
+            removed_tokens: old_len,
+            bytes: range.end,
+            added_bytes: text.len(),
+            removed_bytes: range.len(),
+        };
+        if !list.is_some_and(|list| self.reindex(list, shift)) {
+            self.index();
+        }
+    }
+
+    fn index(&mut self) {
+        let src_len = self.lexed.source().len();
+        let spans = self.lexed.spans();
+        self.lists.clear();
+        self.parents.clear();
+        let mut current = None;
+        for (i, token) in self.lexed.tokens().iter().enumerate() {
+            match token {
+                Token::LParen => {
+                    self.lists.push(List {
+                        open: i,
+                        close: None,
+                        parent: current,
+                        span: spans[i].start..src_len,
+                    });
+                    current = Some(self.lists.len() - 1);
+                    self.parents.push(current);
+                }
+                Token::RParen => {
+                    self.parents.push(current);
+                    if let Some(list) = current {
+                        self.lists[list].span.end = spans[i].end;
+                        self.lists[list].close = Some(i);
+                        current = self.lists[list].parent;
+                    }
+                }
+                _ => self.parents.push(current),
+            }
+        }
+        self.definitions = (0..spans.len())
+            .filter_map(|i| self.definition_at(i))
+            .collect();
+    }
+
+    /// Indexes the inside of a list again after an edit within it, and moves the lists and
+    /// definitions after the edit along. Returns `false` without changing anything if the list
+    /// is no longer closed by the same `)`.
+    fn reindex(&mut self, list: usize, shift: Shift) -> bool {
+        let open = self.lists[list].open;
+        let Some(old_close) = self.lists[list].close else {
+            return false;
+        };
+        let close = shift.token(old_close);
+        let tokens = self.lexed.tokens();
+        let spans = self.lexed.spans();
+        let mut depth = 0;
+        let end = (open + 1..tokens.len()).find(|&i| match tokens[i] {
+            Token::LParen => {
+                depth += 1;
+                false
+            }
+            Token::RParen if depth == 0 => true,
+            Token::RParen => {
+                depth -= 1;
+                false
+            }
+            _ => false,
+        });
+        if end != Some(close) {
+            return false;
+        }
+
+        // the lists inside it, which are numbered after it in the order they're opened
+        let mut lists: Vec<List> = Vec::new();
+        let mut parents = Vec::new();
+        let mut current = list;
+        for (i, token) in tokens.iter().enumerate().take(close).skip(open + 1) {
+            match token {
+                Token::LParen => {
+                    lists.push(List {
+                        open: i,
+                        close: None,
+                        parent: Some(current),
+                        span: spans[i].clone(),
+                    });
+                    current = list + lists.len();
+                }
+                Token::RParen => {
+                    let inner = &mut lists[current - list - 1];
+                    inner.span.end = spans[i].end;
+                    inner.close = Some(i);
+                    parents.push(Some(current));
+                    current = inner.parent.unwrap_or(list);
+                    continue;
+                }
+                _ => {}
+            }
+            parents.push(Some(current));
+        }
+        let removed = self.lists[list + 1..].partition_point(|inner| inner.open < old_close);
+        let after = list + 1 + removed;
+        let added = lists.len();
+        let shift_list = |i: usize| match i >= after {
+            true => i + added - removed,
+            false => i,
+        };
+
+        for later in &mut self.lists[after..] {
+            later.open = shift.token(later.open);
+            later.close = later.close.map(|close| shift.token(close));
+            later.parent = later.parent.map(shift_list);
+            later.span = shift.span(&later.span);
+        }
+        let mut around = Some(list);
+        while let Some(i) = around {
+            self.lists[i].close = self.lists[i].close.map(|close| shift.token(close));
+            self.lists[i].span = shift.span(&self.lists[i].span);
+            around = self.lists[i].parent;
+        }
+        self.lists.splice(list + 1..after, lists);
+        for parent in &mut self.parents[old_close..] {
+            *parent = parent.map(shift_list);
+        }
+        self.parents.splice(open + 1..old_close, parents);
+
+        for definition in &mut self.definitions {
+            definition.token = shift.token(definition.token);
+            definition.list = definition.list.map(shift_list);
+            definition.scope = definition.scope.as_ref().map(|scope| shift.span(scope));
+        }
+        let start = self.definitions.partition_point(|d| d.token <= open);
+        let end = self.definitions.partition_point(|d| d.token < close);
+        let definitions: Vec<_> = (open + 1..close)
+            .filter_map(|i| self.definition_at(i))
+            .collect();
+        self.definitions.splice(start..end, definitions);
+        true
+    }
+
+    pub fn src(&self) -> &str {
+        self.lexed.source()
+    }
+
+    fn spans(&self) -> &[Range<usize>] {
+        self.lexed.spans()
+    }
+
+    /// The lexical errors in the document.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Diagnostics::new(Some(ERROR_LIMIT));
+        self.lexed.errors(&mut diagnostics);
+        diagnostics.into_vec()
+    }
+
+    /// The keyword at the start of a list.
+    fn head(&self, list: usize) -> Option<&str> {
+        match self.lexed.tokens().get(self.lists[list].open + 1)? {
+            Token::Instruction(keyword) => Some(keyword),
+            _ => None,
+        }
+    }
+
+    /// The innermost `func` list around a token, or else its innermost list.
+    fn scope(&self, token: usize) -> Option<Range<usize>> {
+        let mut list = self.parents[token];
+        while let Some(i) = list {
+            if self.head(i) == Some("func") {
+                return Some(self.lists[i].span.clone());
+            }
+            list = self.lists[i].parent;
+        }
+        self.parents[token].map(|i| self.lists[i].span.clone())
+    }
+
+    /// If the token is an `$id` being defined, what it defines.
+    fn definition_at(&self, i: usize) -> Option<Definition> {
+        let Token::Identifier(_) = self.lexed.tokens()[i] else {
+            return None;
+        };
+        let Token::Instruction(keyword) = &self.lexed.tokens()[i.checked_sub(1)?] else {
+            return None;
+        };
+        let namespace = Namespace::of_keyword(keyword)?;
+        let list = match self.lexed.tokens().get(i.wrapping_sub(2)) {
+            Some(Token::LParen) => self.parents[i - 2],
+            // flat block instructions are the only definitions outside of a `(keyword`
+            _ if namespace == Namespace::Label => None,
+            _ => return None,
+        };
+        if let Some(list) = list {
+            let parent = self.lists[list].parent.and_then(|parent| self.head(parent));
+            // `(export "f" (func $f))`, `(start $f)` and `(func (type $t))` refer to things
+            // rather than defining them
+            let type_use = keyword == "type" && !matches!(parent, None | Some("module" | "rec"));
+            if parent == Some("export") || keyword == "start" || type_use {
+                return None;
+            }
+        }
+        let scope = match namespace {
+            Namespace::Local | Namespace::Label => self.scope(i),
+            _ => None,
+        };
+        Some(Definition {
+            namespace,
+            token: i,
+            list,
+            scope,
+        })
+    }
+
+    /// Guesses which namespace an `$id` that isn't being defined refers to, from the keyword
+    /// or instruction before it (skipping other immediates, e.g. in `br_table $a $b`).
+    fn namespace_of_use(&self, i: usize) -> Option<Namespace> {
+        let mut j = i;
+        while j > 0 {
+            j -= 1;
+            match &self.lexed.tokens()[j] {
+                Token::Identifier(_) | Token::NumberLiteral { .. } | Token::Comment { .. } => {}
+                Token::Instruction(name) => {
+                    return match self.lexed.tokens().get(j.wrapping_sub(1)) {
+                        Some(Token::LParen) => {
+                            Namespace::of_keyword(name).or_else(|| Namespace::of_instruction(name))
+                        }
+                        _ => Namespace::of_instruction(name),
+                    };
+                }
+                _ => return None,
+            }
+        }
+        None
+    }
+
+    /// The definition that the `$id` token `i` is or refers to.
+    fn resolve(&self, i: usize) -> Option<&Definition> {
+        let Token::Identifier(name) = &self.lexed.tokens()[i] else {
+            return None;
+        };
+        if let Some(definition) = self.definitions.iter().find(|d| d.token == i) {
+            return Some(definition);
+        }
+        let offset = self.spans()[i].start;
+        let candidates = || {
+            self.definitions.iter().filter(move |d| {
+                self.lexed.tokens()[d.token] == Token::Identifier(name.clone())
+                    && d.scope.as_ref().is_none_or(|scope| scope.contains(&offset))
+            })
+        };
+        let namespace = self.namespace_of_use(i);
+        let found = match namespace {
+            // the innermost label with the name, which is the last one opened before it
+            Some(Namespace::Label) => candidates()
+                .rfind(|d| d.namespace == Namespace::Label && self.spans()[d.token].start < offset),
+            Some(namespace) => candidates().find(|d| d.namespace == namespace),
+            None => None,
+        };
+        found.or_else(|| candidates().next())
+    }
+
+    /// The index of the token at (or just before) an offset.
+    fn token_at(&self, offset: usize) -> Option<usize> {
+        let spans = self.spans();
+        spans
+            .iter()
+            .position(|span| span.start <= offset && offset <= span.end && !span.is_empty())
+            .filter(|&i| {
+                // prefer the token starting at the offset to one ending there
+                !(spans[i].end == offset
+                    && spans.get(i + 1).is_some_and(|next| next.start == offset))
+            })
+            .or_else(|| spans.iter().position(|span| span.start == offset))
+    }
+
+    /// Where the `$id` at an offset is defined.
+    pub fn definition(&self, offset: usize) -> Option<Range<usize>> {
+        let definition = self.resolve(self.token_at(offset)?)?;
+        Some(self.spans()[definition.token].clone())
+    }
+
+    /// Everywhere that the `$id` at an offset (or its definition) is used.
+    pub fn references(&self, offset: usize, include_definition: bool) -> Vec<Range<usize>> {
+        let Some(token) = self.resolve_at(offset) else {
+            return Vec::new();
+        };
+        (0..self.lexed.tokens().len())
+            .filter(|&i| include_definition || i != token)
+            .filter(|&i| self.resolve(i).is_some_and(|d| d.token == token))
+            .map(|i| self.spans()[i].clone())
+            .collect()
+    }
+
+    fn resolve_at(&self, offset: usize) -> Option<usize> {
+        Some(self.resolve(self.token_at(offset)?)?.token)
+    }
+
+    /// A description of the `$id` or instruction at an offset, in markdown.
+    pub fn hover(&self, offset: usize) -> Option<String> {
+        let i = self.token_at(offset)?;
+        match &self.lexed.tokens()[i] {
+            Token::Instruction(name) => {
+                let op = Op::from_name(name)?;
+                let mut hover = format!("```wat\n{}", name);
+                if let Some(ty) = op.type_string() {
+                    hover += &format!(" : {}", ty);
+                }
+                hover += "\n```";
+                if let Some(proposal) = op.proposal() {
+                    hover += &format!("\n\nfrom the {} proposal", proposal.name());
+                }
+                Some(hover)
+            }
+            Token::Identifier(_) => {
+                let definition = self.resolve(i)?;
+                let text = match definition.list {
+                    Some(list) => self.signature(list),
+                    None => self.collapse(
+                        self.spans()[definition.token - 1].start
+                            ..self.spans()[definition.token].end,
+                    ),
+                };
+                Some(format!("```wat\n{}\n```", text))
+            }
+            _ => None,
+        }
+    }
+
+    /// A definition's list, shortened to its keyword, `$id` and type if it's long.
+    fn signature(&self, list: usize) -> String {
+        let span = &self.lists[list].span;
+        let full = self.collapse(span.clone());
+        if full.len() <= 80 && self.head(list) != Some("func") {
+            return full;
+        }
+        // the keyword and $id, and then any params, results and type uses
+        let open = self.lists[list].open;
+        let mut text = self.collapse(self.spans()[open].start..self.spans()[open + 2].end);
+        for (i, child) in self.lists.iter().enumerate() {
+            let signature = matches!(self.head(i), Some("param" | "result" | "type"));
+            if child.parent == Some(list) && signature {
+                text.push(' ');
+                text += &self.collapse(child.span.clone());
+            }
+        }
+        text.push(')');
+        text
+    }
+
+    /// The source in a range, with each run of whitespace (and any comments) replaced with a
+    /// single space.
+    fn collapse(&self, span: Range<usize>) -> String {
+        let mut text = String::new();
+        for (token, token_span) in self.lexed.tokens().iter().zip(self.spans()) {
+            if token_span.start < span.start || token_span.end > span.end {
+                continue;
+            }
+            if matches!(token, Token::Comment { .. }) {
+                continue;
+            }
+            let after_paren = text.ends_with('(') || text.is_empty();
+            if !after_paren && *token != Token::RParen {
+                text.push(' ');
+            }
+            text += &self.src()[token_span.clone()];
+        }
+        text
+    }
+
+    /// The module's fields (or the top level lists, if there's no `module`).
+    pub fn fields(&self) -> Vec<Field> {
+        let module = self.lists.iter().position(|list| list.parent.is_none());
+        let module = module.filter(|&list| self.head(list) == Some("module"));
+        self.lists
+            .iter()
+            .enumerate()
+            .filter(|(_, list)| list.parent == module)
+            .filter_map(|(i, list)| {
+                let kind = self.head(i)?.to_string();
+                let keyword = list.open + 1;
+                let (name, selection) = match self.lexed.tokens().get(keyword + 1) {
+                    Some(Token::Identifier(id)) => (id.clone(), keyword + 1),
+                    Some(Token::StringLiteral { string, .. }) => (string.clone(), keyword + 1),
+                    _ => (kind.clone(), keyword),
+                };
+                Some(Field {
+                    name,
+                    kind,
+                    span: list.span.clone(),
+                    selection: self.spans()[selection].clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// The instruction names and keywords that could complete the word before an offset.
+    pub fn completions(&self, offset: usize) -> Vec<&'static str> {
+        let before = &self.src()[..offset.min(self.src().len())];
+        let start = before
+            .rfind(|ch: char| ch.is_whitespace() || ch == '(' || ch == ')')
+            .map_or(0, |i| i + 1);
+        let prefix = &before[start..];
+        if prefix.starts_with(['$', '"', ';']) {
+            return Vec::new();
+        }
+        let mut names: Vec<&'static str> = KEYWORDS
+            .iter()
+            .copied()
+            .chain(Op::ALL.iter().map(|op| op.name()))
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        names.dedup();
+        names
+    }
+
+    /// Each token's span and its index in [`TOKEN_TYPES`].
+    pub fn semantic_tokens(&self) -> Vec<(Range<usize>, usize)> {
+        self.lexed
+            .tokens()
+            .iter()
+            .zip(self.spans())
+            .filter_map(|(token, span)| {
+                let kind = match token {
+                    Token::Instruction(name) if Op::from_name(name).is_some() => 1,
+                    Token::Instruction(_) => 0,
+                    Token::Identifier(_) => 2,
+                    Token::StringLiteral { .. } => 3,
+                    Token::NumberLiteral { .. } => 4,
+                    Token::Comment { .. } => 5,
+                    Token::Annotation(_) => 6,
+                    _ => return None,
+                };
+                Some((span.clone(), kind))
+            })
+            .collect()
+    }
+}
+
+/// The keywords that aren't instructions.
+const KEYWORDS: &[&str] = &[
+    "module",
+    "type",
+    "func",
+    "param",
+    "result",
+    "local",
+    "import",
+    "export",
+    "table",
+    "memory",
+    "global",
+    "mut",
+    "start",
+    "elem",
+    "data",
+    "offset",
+    "item",
+    "declare",
+    "tag",
+    "rec",
+    "sub",
+    "final",
+    "struct",
+    "array",
+    "field",
+    "ref",
+    "null",
+    "shared",
+    "then",
+    "i32",
+    "i64",
+    "f32",
+    "f64",
+    "v128",
+    "funcref",
+    "externref",
+    "anyref",
+    "exnref",
+];
+
+/// A language server speaking JSON-RPC. The transport (e.g. stdio with `Content-Length`
+/// headers) is up to the caller, which passes each message's body to [`Server::handle`].
+#[derive(Default)]
+pub struct Server {
+    documents: Vec<(String, Document)>,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server::default()
+    }
+
+    /// Whether the client has sent `exit`, and the transport should stop.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles a message from the client, returning the messages to send back.
+    pub fn handle(&mut self, message: &str) -> Vec<String> {
+        let Some(message) = Json::parse(message) else {
+            return vec![response(Json::Null, Err((-32700, "parse error")))];
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let Some(id) = message.get("id").cloned() else {
+            return self.notification(method, params);
+        };
+        let result = match method {
+            "initialize" => Ok(capabilities()),
+            "shutdown" => Ok(Json::Null),
+            _ => match self.document(params) {
+                Some(document) => document_request(document, method, params),
+                None if method.starts_with("textDocument/") => Ok(Json::Null),
+                None => Err((-32601, "method not found")),
+            },
+        };
+        vec![response(id, result)]
+    }
+
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<String> {
+        let document = params.get("textDocument");
+        let Some(uri) = document.and_then(|d| d.get("uri")).and_then(Json::as_str) else {
+            self.exited |= method == "exit";
+            return Vec::new();
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let Some(text) = document.and_then(|d| d.get("text")).and_then(Json::as_str) else {
+                    return Vec::new();
+                };
+                self.documents.retain(|(open, _)| open != uri);
+                self.documents
+                    .push((uri.to_string(), Document::new(text.to_string())));
+            }
+            "textDocument/didChange" => {
+                let Some((_, document)) = self.documents.iter_mut().find(|(open, _)| open == uri)
+                else {
+                    return Vec::new();
+                };
+                let changes = params.get("contentChanges").and_then(Json::as_array);
+                for change in changes.unwrap_or_default() {
+                    let Some(text) = change.get("text").and_then(Json::as_str) else {
+                        continue;
+                    };
+                    // changes without a range replace the whole document
+                    match change
+                        .get("range")
+                        .and_then(|range| span(document.src(), range))
+                    {
+                        Some(span) => document.edit(span, text),
+                        None => *document = Document::new(text.to_string()),
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.retain(|(open, _)| open != uri);
+                return vec![publish_diagnostics(uri, Json::Array(Vec::new()))];
+            }
+            _ => return Vec::new(),
+        }
+        let Some((_, document)) = self.documents.iter().find(|(open, _)| open == uri) else {
+            return Vec::new();
+        };
+        let diagnostics = document
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| lsp_diagnostic(document, diagnostic))
+            .collect();
+        vec![publish_diagnostics(uri, Json::Array(diagnostics))]
+    }
+
+    fn document(&self, params: &Json) -> Option<&Document> {
+        let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+        self.documents
+            .iter()
+            .find(|(open, _)| open == uri)
+            .map(|(_, document)| document)
+    }
+}
+
+fn document_request(
+    document: &Document,
+    method: &str,
+    params: &Json,
+) -> Result<Json, (i32, &'static str)> {
+    let src = document.src();
+    let offset = params
+        .get("position")
+        .and_then(|position| position_offset(src, position))
+        .unwrap_or(0);
+    let uri = params
+        .get("textDocument")
+        .and_then(|d| d.get("uri"))
+        .cloned();
+    let location = |span: Range<usize>| {
+        Json::object([
+            ("uri", uri.clone().unwrap_or(Json::Null)),
+            ("range", range(src, span)),
+        ])
+    };
+    Ok(match method {
+        "textDocument/definition" => document.definition(offset).map_or(Json::Null, location),
+        "textDocument/references" => {
+            let include = params
+                .get("context")
+                .and_then(|context| context.get("includeDeclaration"))
+                == Some(&Json::Bool(true));
+            Json::Array(
+                document
+                    .references(offset, include)
+                    .into_iter()
+                    .map(location)
+                    .collect(),
+            )
+        }
+        "textDocument/hover" => document.hover(offset).map_or(Json::Null, |hover| {
+            let contents = Json::object([("kind", "markdown".into()), ("value", hover.into())]);
+            Json::object([("contents", contents)])
+        }),
+        "textDocument/documentSymbol" => Json::Array(
+            document
+                .fields()
+                .into_iter()
+                .map(|field| {
+                    Json::object([
+                        ("name", field.name.into()),
+                        ("detail", field.kind.as_str().into()),
+                        ("kind", symbol_kind(&field.kind).into()),
+                        ("range", range(src, field.span)),
+                        ("selectionRange", range(src, field.selection)),
+                    ])
+                })
+                .collect(),
+        ),
+        "textDocument/completion" => Json::Array(
+            document
+                .completions(offset)
+                .into_iter()
+                .map(|name| {
+                    // 14 is a keyword, and 24 an operator
+                    let kind: usize = if Op::from_name(name).is_some() {
+                        24
+                    } else {
+                        14
+                    };
+                    Json::object([("label", name.into()), ("kind", kind.into())])
+                })
+                .collect(),
+        ),
+        "textDocument/semanticTokens/full" => {
+            let data = semantic_token_data(src, &document.semantic_tokens());
+            Json::object([(
+                "data",
+                Json::Array(data.into_iter().map(Json::from).collect()),
+            )])
+        }
+        _ => return Err((-32601, "method not found")),
+    })
+}
+
+fn capabilities() -> Json {
+    let legend = Json::object([
+        (
+            "tokenTypes",
+            Json::Array(TOKEN_TYPES.iter().map(|&t| t.into()).collect()),
+        ),
+        ("tokenModifiers", Json::Array(Vec::new())),
+    ]);
+    let completion = Json::object([(
+        "triggerCharacters",
+        Json::Array(vec!["(".into(), ".".into()]),
+    )]);
+    let capabilities = Json::object([
+        // incremental syncs, so that each change only has the edited text
+        ("textDocumentSync", 2.into()),
+        ("definitionProvider", true.into()),
+        ("referencesProvider", true.into()),
+        ("hoverProvider", true.into()),
+        ("documentSymbolProvider", true.into()),
+        ("completionProvider", completion),
+        (
+            "semanticTokensProvider",
+            Json::object([("legend", legend), ("full", true.into())]),
+        ),
+    ]);
+    let info = Json::object([("name", "w2w2".into())]);
+    Json::object([("capabilities", capabilities), ("serverInfo", info)])
+}
+
+fn response(id: Json, result: Result<Json, (i32, &str)>) -> String {
+    let (key, value) = match result {
+        Ok(result) => ("result", result),
+        Err((code, message)) => (
+            "error",
+            Json::object([
+                ("code", Json::Number(code as f64)),
+                ("message", message.into()),
+            ]),
+        ),
+    };
+    Json::object([("jsonrpc", "2.0".into()), ("id", id), (key, value)]).to_string()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Json) -> String {
+    let params = Json::object([("uri", uri.into()), ("diagnostics", diagnostics)]);
+    Json::object([
+        ("jsonrpc", "2.0".into()),
+        ("method", "textDocument/publishDiagnostics".into()),
+        ("params", params),
+    ])
+    .to_string()
+}
+
+fn lsp_diagnostic(document: &Document, diagnostic: &Diagnostic) -> Json {
+    let src = document.src();
+    let span = diagnostic.span().unwrap_or(0..0);
+    let severity: usize = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message += &format!("\nnote: {}", note);
+    }
+    for help in &diagnostic.help {
+        message += &format!("\nhelp: {}", help);
+    }
+    Json::object([
+        ("range", range(src, span)),
+        ("severity", severity.into()),
+        ("source", "w2w2".into()),
+        ("message", message.into()),
+    ])
+}
+
+fn symbol_kind(keyword: &str) -> usize {
+    match keyword {
+        "func" => 12,
+        "global" => 13,
+        "type" | "rec" => 5,
+        "memory" | "table" => 19,
+        "import" | "export" => 2,
+        "tag" => 24,
+        _ => 8,
+    }
+}
+
+/// An LSP position, which counts characters in UTF-16 code units.
+fn position(src: &str, offset: usize) -> Json {
+    let offset = offset.min(src.len());
+    let line_start = src[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line = src[..offset].matches('\n').count();
+    let character: usize = src[line_start..offset].chars().map(char::len_utf16).sum();
+    Json::object([("line", line.into()), ("character", character.into())])
+}
+
+fn range(src: &str, span: Range<usize>) -> Json {
+    Json::object([
+        ("start", position(src, span.start)),
+        ("end", position(src, span.end)),
+    ])
+}
+
+/// The byte offset of an LSP position, clamped to the end of its line.
+fn offset(src: &str, line: usize, character: usize) -> usize {
+    let line_start = match line {
+        0 => 0,
+        _ => match src.match_indices('\n').nth(line - 1) {
+            Some((i, _)) => i + 1,
+            None => return src.len(),
+        },
+    };
+    let mut units = 0;
+    for (i, ch) in src[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return line_start + i;
+        }
+        units += ch.len_utf16();
+    }
+    src.len()
+}
+
+/// The byte offset of an LSP position object.
+fn position_offset(src: &str, position: &Json) -> Option<usize> {
+    let line = position.get("line")?.as_f64()? as usize;
+    let character = position.get("character")?.as_f64()? as usize;
+    Some(offset(src, line, character))
+}
+
+/// The byte range of an LSP range object.
+fn span(src: &str, range: &Json) -> Option<Range<usize>> {
+    let start = position_offset(src, range.get("start")?)?;
+    let end = position_offset(src, range.get("end")?)?;
+    Some(start..end.max(start))
+}
+
+/// Encodes semantic tokens relative to each other, as LSP expects. Tokens that span several
+/// lines (block comments and strings) are split into one token per line.
+fn semantic_token_data(src: &str, tokens: &[(Range<usize>, usize)]) -> Vec<usize> {
+    let mut data = Vec::new();
+    let (mut last_line, mut last_start) = (0, 0);
+    for (span, kind) in tokens {
+        let mut start = span.start;
+        for part in src[span.clone()].split('\n') {
+            let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line = src[..start].matches('\n').count();
+            let character: usize = src[line_start..start].chars().map(char::len_utf16).sum();
+            let length: usize = part
+                .trim_end_matches('\r')
+                .chars()
+                .map(char::len_utf16)
+                .sum();
+            if length != 0 {
+                let delta_start = match line == last_line {
+                    true => character - last_start,
+                    false => character,
+                };
+                data.extend([line - last_line, delta_start, length, *kind, 0]);
+                (last_line, last_start) = (line, character);
+            }
+            start += part.len() + 1;
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "(module
+  (type $t (func (param i32)))
+  (func $f (export \"f\") (param $x i32) (result i32)
+    (local $y i32)
+    block $out
+      local.get $x
+      br_if $out
+    end
+    call $f)
+  ;; a comment
+  (global $g (mut i32) (i32.const 0)))";
+
+    fn at(needle: &str, nth: usize) -> usize {
+        SRC.match_indices(needle).nth(nth).unwrap().0
+    }
+
+    #[test]
+    fn definitions_and_references() {
+        let document = Document::new(SRC.to_string());
+        let x = at("$x", 0);
+        assert_eq!(document.definition(at("$x", 1)), Some(x..x + 2));
+        assert_eq!(
+            document.definition(at("$out", 1) + 2),
+            Some(at("$out", 0)..at("$out", 0) + 4)
+        );
+        assert_eq!(
+            document.definition(at("$f", 1)),
+            Some(at("$f", 0)..at("$f", 0) + 2)
+        );
+        assert_eq!(document.definition(at("i32.const", 0)), None);
+        assert_eq!(
+            document.references(x, true),
+            vec![x..x + 2, at("$x", 1)..at("$x", 1) + 2]
+        );
+        assert_eq!(document.references(x, false).len(), 1);
+        // `(type $t)` in a func refers to the type, rather than defining it
+        let src = "(module (type $t (func)) (func (type $t)))";
+        let document = Document::new(src.to_string());
+        assert_eq!(document.definition(37), Some(14..16));
+    }
+
+    #[test]
+    fn hover() {
+        let document = Document::new(SRC.to_string());
+        assert_eq!(
+            document.hover(at("local.get", 0)).unwrap(),
+            "```wat\nlocal.get : [] -> [t]\n```"
+        );
+        assert_eq!(
+            document.hover(at("$f", 1)).unwrap(),
+            "```wat\n(func $f (param $x i32) (result i32))\n```"
+        );
+        assert_eq!(
+            document.hover(at("$y", 0)).unwrap(),
+            "```wat\n(local $y i32)\n```"
+        );
+        assert_eq!(
+            document.hover(at("$out", 1)).unwrap(),
+            "```wat\nblock $out\n```"
+        );
+        assert_eq!(document.hover(at("i32)", 0)), None);
+    }
+
+    #[test]
+    fn outline_completion_and_tokens() {
+        let document = Document::new(SRC.to_string());
+        let fields: Vec<_> = document
+            .fields()
+            .into_iter()
+            .map(|field| (field.name, field.kind))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("$t".to_string(), "type".to_string()),
+                ("$f".to_string(), "func".to_string()),
+                ("$g".to_string(), "global".to_string()),
+            ]
+        );
+        assert_eq!(
+            document.completions(at("local.get", 0) + 6),
+            ["local.get", "local.set", "local.tee"]
+        );
+        assert_eq!(document.completions(at("$x", 1) + 1), Vec::<&str>::new());
+        let tokens = document.semantic_tokens();
+        assert_eq!(tokens[0], (1..7, 0));
+        assert!(tokens.contains(&(at(";;", 0)..at(";;", 0) + 12, 5)));
+
+        let data = semantic_token_data("(; a\nb ;) \"é\"", &[(0..9, 5), (10..14, 3)]);
+        assert_eq!(data, [0, 0, 4, 5, 0, 1, 0, 4, 5, 0, 0, 5, 3, 3, 0]);
+    }
+
+    #[test]
+    fn edit() {
+        let mut document = Document::new(SRC.to_string());
+        document.edit(at("$y", 0)..at("$y", 0) + 2, "$z");
+        document.edit(
+            at("(global", 0)..at("(global", 0),
+            "(; new ;) (memory $m 1) ",
+        );
+        let expected = Document::new(document.src().to_string());
+        assert!(document.src().contains("(local $z i32)"));
+        assert_eq!(document.semantic_tokens(), expected.semantic_tokens());
+        assert_eq!(document.fields(), expected.fields());
+        assert_eq!(document.fields()[2].name, "$m");
+    }
+
+    #[test]
+    fn edit_matches_indexing() {
+        const PIECES: &[&str] = &[
+            "(",
+            ")",
+            " ",
+            "\n",
+            "$a",
+            "$b",
+            "func",
+            "local",
+            "block",
+            "br",
+            "(func $f",
+            "(local $a i32)",
+            "(block $b)",
+            "call $f",
+            "(; c ;)",
+            "\"",
+        ];
+        let mut seed: u64 = 0x9e3779b97f4a7c15;
+        let mut random = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed as usize % n
+        };
+        let mut document = Document::new(SRC.to_string());
+        for _ in 0..2000 {
+            let src = document.src();
+            let start = random(src.len() + 1);
+            let end = (start + random(4)).min(src.len());
+            let text: String = (0..random(3))
+                .map(|_| PIECES[random(PIECES.len())])
+                .collect();
+            document.edit(start..end, &text);
+            let expected = Document::new(document.src().to_string());
+            assert_eq!(document.lists, expected.lists, "{:?}", document.src());
+            assert_eq!(document.parents, expected.parents);
+            assert_eq!(document.definitions, expected.definitions);
+            if document.src().len() > 600 {
+                document = Document::new(SRC.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let src = "ab\n\u{1F600}c\n";
+        assert_eq!(offset(src, 1, 2), 7);
+        assert_eq!(offset(src, 1, 99), 8);
+        assert_eq!(offset(src, 9, 0), src.len());
+        assert_eq!(position(src, 7).to_string(), "{\"line\":1,\"character\":2}");
+    }
+
+    #[test]
+    fn server() {
+        let mut server = Server::new();
+        let initialize =
+            server.handle(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#);
+        assert!(initialize[0].contains("\"hoverProvider\":true"));
+        let opened = server.handle(
+            r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.wat","languageId":"wat","version":1,"text":"(module 1x)"}}}"#,
+        );
+        assert_eq!(
+            opened,
+            [
+                r#"{"jsonrpc":"2.0","method":"textDocument/publishDiagnostics","params":{"uri":"file:///a.wat","diagnostics":[{"range":{"start":{"line":0,"character":8},"end":{"line":0,"character":10}},"severity":1,"source":"w2w2","message":"unknown token `1x`"}]}}"#
+            ]
+        );
+        server.handle(
+            r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.wat","version":2},"contentChanges":[{"text":"(func $f\n  call $f)"}]}}"#,
+        );
+        assert_eq!(
+            server.handle(
+                r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.wat"},"position":{"line":1,"character":8}}}"#
+            ),
+            [r#"{"jsonrpc":"2.0","id":2,"result":{"uri":"file:///a.wat","range":{"start":{"line":0,"character":6},"end":{"line":0,"character":8}}}}"#]
+        );
+        // an incremental change, turning `call $f` into `return_call $f`
+        server.handle(
+            r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///a.wat","version":3},"contentChanges":[{"range":{"start":{"line":1,"character":2},"end":{"line":1,"character":2}},"text":"return_"}]}}"#,
+        );
+        assert_eq!(
+            server.handle(
+                r#"{"jsonrpc":"2.0","id":4,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.wat"},"position":{"line":1,"character":15}}}"#
+            ),
+            [r#"{"jsonrpc":"2.0","id":4,"result":{"uri":"file:///a.wat","range":{"start":{"line":0,"character":6},"end":{"line":0,"character":8}}}}"#]
+        );
+        assert_eq!(
+            server.handle(r#"{"jsonrpc":"2.0","id":3,"method":"bogus"}"#),
+            [r#"{"jsonrpc":"2.0","id":3,"error":{"code":-32601,"message":"method not found"}}"#]
+        );
+        assert!(!server.exited());
+        server.handle(r#"{"jsonrpc":"2.0","method":"exit"}"#);
+        assert!(server.exited());
+    }
+}
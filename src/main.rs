@@ -0,0 +1,698 @@
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::process::ExitCode;
+use w2w2::decoder::{self, DecodeError, Section};
+use w2w2::diagnostic::{Diagnostic, Diagnostics};
+use w2w2::features::{Features, Proposal};
+use w2w2::formatter::{self, FormatOptions, Indent};
+use w2w2::lexer::TokenList;
+use w2w2::lsp::Server;
+use w2w2::printer::Style;
+
+const USAGE: &str = "usage: w2w2 [command] [options] <input>
+
+Use `-` as the input to read it from stdin.
+
+commands:
+  compile (the default)  compile a text file to a binary module (not supported yet - this
+                         checks the file's tokens, then exits with 3)
+  check                  check that a module is well-formed without writing anything - a
+                         binary module is fully decoded, but a text file only gets its
+                         tokens, parentheses and instruction proposals checked for now
+  print                  print a binary module as text
+  fmt                    reformat a text file, keeping its comments
+  strip                  remove the custom sections from a binary module, in place
+                         unless -o is given
+  objdump                list a binary module's sections and function bodies, with their
+                         offsets and sizes
+  tokens                 list the tokens in a text file
+  lsp                    run a language server for text files over stdin and stdout (this
+                         doesn't take an input)
+
+options:
+  -o <file>             write the output to <file>, or to stdout if it's `-` (compile
+                        defaults to the input with a .wasm extension, or stdout for stdin)
+  --features <preset>   (check, print, objdump) allow the proposals in mvp, 1.0, 2.0 (the
+                        default), 3.0 or all
+  --enable <proposal>   also allow a proposal, e.g. `--enable threads`
+  --disable <proposal>  don't allow a proposal
+  --flat, --folded      (print, fmt) the instruction style - fmt keeps the source's by
+                        default, and print is flat by default
+  --indent <n>          (fmt) indent by n spaces (2 by default)
+  --tabs                (fmt) indent with tabs
+  --color <when>        colour errors always, never or auto (the default, when stderr is a
+                        terminal and NO_COLOR isn't set)
+  --json                print errors as JSON, one per line
+  --error-limit <n>     stop after n errors (0, the default, means no limit)
+  -h, --help            print this message";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Command {
+    Compile,
+    Check,
+    Print,
+    Fmt,
+    Strip,
+    Objdump,
+    Tokens,
+    Lsp,
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    command: Command,
+    input: String,
+    output: Option<String>,
+    features: Features,
+    style: Option<Style>,
+    indent: Indent,
+    /// Whether to colour errors, or `None` to decide based on the terminal.
+    colour: Option<bool>,
+    json: bool,
+    error_limit: Option<usize>,
+}
+
+/// Exit codes, so that scripts can tell bad input apart from being called wrongly, and both
+/// apart from input that's fine but uses something w2w2 can't handle yet.
+const INPUT_ERROR: u8 = 1;
+const USAGE_ERROR: u8 = 2;
+const UNSUPPORTED_ERROR: u8 = 3;
+
+/// Why a command failed.
+enum Failure {
+    /// The input is malformed, and the errors have already been printed.
+    Input,
+    Usage(String),
+    Unsupported(String),
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(USAGE_ERROR);
+        }
+    };
+    match run(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Input) => ExitCode::from(INPUT_ERROR),
+        Err(Failure::Usage(message)) => {
+            eprintln!("error: {}", message);
+            ExitCode::from(USAGE_ERROR)
+        }
+        Err(Failure::Unsupported(message)) => {
+            eprintln!("error: {}", message);
+            ExitCode::from(UNSUPPORTED_ERROR)
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), Failure> {
+    if options.command == Command::Lsp {
+        return serve().map_err(|err| Failure::Usage(format!("language server: {}", err)));
+    }
+    let input = read_input(&options.input)
+        .map_err(|err| Failure::Usage(format!("couldn't read {}: {}", options.input, err)))?;
+    let file = match options.input.as_str() {
+        "-" => "<stdin>",
+        path => path,
+    };
+    let text = || {
+        String::from_utf8(input.clone())
+            .map_err(|_| Failure::Usage(format!("{} isn't valid UTF-8", file)))
+    };
+    let colour = options
+        .colour
+        .unwrap_or_else(|| io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none());
+    let source_errors = |src: &str, errors: Vec<Diagnostic>| {
+        for error in &errors {
+            match options.json {
+                true => eprintln!("{}", error.to_json(file, src)),
+                false => eprintln!("{}", error.render(file, src, colour)),
+            }
+        }
+        Failure::Input
+    };
+    let decode_error = |err: DecodeError| {
+        let error =
+            Diagnostic::error(err.to_string()).with_note(format!("while decoding {}", file));
+        source_errors("", vec![error])
+    };
+
+    let output = match options.command {
+        Command::Compile => {
+            let src = text()?;
+            compile(&src, options.error_limit).map_err(|err| match err {
+                CompileError::Invalid(errors) => source_errors(&src, errors),
+                CompileError::Unsupported(message) => Failure::Unsupported(message.to_string()),
+            })?
+        }
+        Command::Check => {
+            if input.starts_with(b"\0asm") {
+                decoder::decode(&input, options.features).map_err(decode_error)?;
+            } else {
+                let src = text()?;
+                check_text(&src, options.features, options.error_limit)
+                    .map_err(|errors| source_errors(&src, errors))?;
+            }
+            return Ok(());
+        }
+        Command::Print => {
+            let module = decoder::decode(&input, options.features).map_err(decode_error)?;
+            module
+                .print(options.style.unwrap_or(Style::Flat))
+                .to_string()
+                .into_bytes()
+        }
+        Command::Fmt => {
+            let options = FormatOptions {
+                indent: options.indent,
+                style: options.style,
+            };
+            formatter::format(&text()?, options).into_bytes()
+        }
+        Command::Strip => {
+            let sections = decoder::sections(&input).map_err(decode_error)?;
+            let mut stripped = input[..8].to_vec();
+            for section in sections.iter().filter(|s| s.id != 0) {
+                stripped.extend_from_slice(&input[section.range.clone()]);
+            }
+            stripped
+        }
+        Command::Objdump => objdump(&input, options.features)
+            .map_err(decode_error)?
+            .into_bytes(),
+        Command::Lsp => unreachable!("the language server doesn't read an input"),
+        Command::Tokens => {
+            let src = text()?;
+            let tokens = TokenList::from(src.clone());
+            let mut out = String::new();
+            for (token, span) in tokens.tokens().iter().zip(tokens.spans()) {
+                let (line, column) = line_column(&src, span.start);
+                out += &format!("{}:{} {:?}\n", line, column, token);
+            }
+            out.into_bytes()
+        }
+    };
+
+    let path = match (&options.output, options.command, options.input.as_str()) {
+        (Some(path), _, _) => path.clone(),
+        (None, Command::Compile, input) if input != "-" => {
+            let stem = input.strip_suffix(".wat").unwrap_or(input);
+            format!("{}.wasm", stem)
+        }
+        (None, Command::Strip, input) => input.to_string(),
+        (None, _, _) => "-".to_string(),
+    };
+    let written = match path.as_str() {
+        "-" => io::stdout().write_all(&output),
+        path => std::fs::write(path, &output),
+    };
+    written.map_err(|err| Failure::Usage(format!("couldn't write {}: {}", path, err)))
+}
+
+/// The largest message body the language server will read. The `Content-Length` comes from
+/// the client, so it can't be trusted to allocate a buffer for.
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024 * 1024;
+
+/// Runs the language server, reading messages from stdin and writing them to stdout, each
+/// with a `Content-Length` header, until the client says to exit.
+fn serve() -> io::Result<()> {
+    let mut server = Server::new();
+    let mut stdin = io::stdin().lock();
+    let mut stdout = io::stdout().lock();
+    while !server.exited() {
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if stdin.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(value) = line.strip_prefix("Content-Length:") {
+                length = value.trim().parse().ok();
+            }
+        }
+        let Some(length) = length else {
+            continue;
+        };
+        if length > MAX_MESSAGE_LENGTH {
+            // skip it without buffering it, as there's no way to answer it without its id
+            io::copy(&mut (&mut stdin).take(length as u64), &mut io::sink())?;
+            continue;
+        }
+        let mut body = vec![0; length];
+        stdin.read_exact(&mut body)?;
+        for message in server.handle(&String::from_utf8_lossy(&body)) {
+            write!(
+                stdout,
+                "Content-Length: {}\r\n\r\n{}",
+                message.len(),
+                message
+            )?;
+        }
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// Returns `None` if the usage should be printed instead.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut args = args.by_ref().peekable();
+    let command = match args.peek().map(String::as_str) {
+        Some("compile") => Some(Command::Compile),
+        Some("check") => Some(Command::Check),
+        Some("print") => Some(Command::Print),
+        Some("fmt") => Some(Command::Fmt),
+        Some("strip") => Some(Command::Strip),
+        Some("objdump") => Some(Command::Objdump),
+        Some("tokens") => Some(Command::Tokens),
+        Some("lsp") => Some(Command::Lsp),
+        _ => None,
+    };
+    if command.is_some() {
+        args.next();
+    }
+    let command = command.unwrap_or(Command::Compile);
+
+    let mut input = None;
+    let mut output = None;
+    let mut features = Features::default();
+    let mut style = None;
+    let mut indent = Indent::Spaces(2);
+    let mut colour = None;
+    let mut json = false;
+    let mut error_limit = None;
+    while let Some(arg) = args.next() {
+        // these are for the parser and encoder, so they'd be silently ignored until those exist
+        if matches!(
+            arg.as_str(),
+            "--debug-names" | "--print-module" | "--features" | "--enable" | "--disable"
+        ) && command == Command::Compile
+        {
+            return Err(format!(
+                "`{}` isn't supported yet, as compiling the text format isn't",
+                arg
+            ));
+        }
+        let allowed: &[Command] = match arg.as_str() {
+            "-o" => &[
+                Command::Compile,
+                Command::Print,
+                Command::Fmt,
+                Command::Strip,
+                Command::Objdump,
+                Command::Tokens,
+            ],
+            "--features" | "--enable" | "--disable" => {
+                &[Command::Check, Command::Print, Command::Objdump]
+            }
+            "--flat" | "--folded" => &[Command::Print, Command::Fmt],
+            "--indent" | "--tabs" => &[Command::Fmt],
+            _ => &[command],
+        };
+        if !allowed.contains(&command) {
+            return Err(format!(
+                "`{}` can't be used with the {:?} command",
+                arg, command
+            ));
+        }
+        let mut value = |flag: &str| args.next().ok_or(format!("{} needs a value", flag));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" => output = Some(value("-o")?),
+            "--flat" => style = Some(Style::Flat),
+            "--folded" => style = Some(Style::Folded),
+            "--tabs" => indent = Indent::Tabs,
+            "--json" => json = true,
+            "--error-limit" => {
+                let n = value("--error-limit")?;
+                let n = n
+                    .parse()
+                    .map_err(|_| format!("`{}` isn't a number of errors", n))?;
+                error_limit = Some(n).filter(|&n| n != 0);
+            }
+            "--color" => {
+                colour = match value("--color")?.as_str() {
+                    "always" => Some(true),
+                    "never" => Some(false),
+                    "auto" => None,
+                    when => return Err(format!("`{}` isn't always, never or auto", when)),
+                }
+            }
+            "--indent" => {
+                let n = value("--indent")?;
+                let n = n
+                    .parse()
+                    .map_err(|_| format!("`{}` isn't a number of spaces", n))?;
+                indent = Indent::Spaces(n);
+            }
+            "--features" => {
+                features = match value("--features")?.as_str() {
+                    "mvp" => Features::mvp(),
+                    "1.0" => Features::v1(),
+                    "2.0" => Features::v2(),
+                    "3.0" => Features::v3(),
+                    "all" => Features::all(),
+                    preset => return Err(format!("unknown feature preset `{}`", preset)),
+                }
+            }
+            "--enable" => features = features.with(proposal(&value("--enable")?)?),
+            "--disable" => features = features.without(proposal(&value("--disable")?)?),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option `{}`", flag))
+            }
+            _ if command == Command::Lsp => return Err("lsp doesn't take an input".to_string()),
+            _ if input.is_some() => return Err("only one input file can be given".to_string()),
+            _ => input = Some(arg),
+        }
+    }
+    Ok(Some(Options {
+        command,
+        input: match command {
+            Command::Lsp => String::new(),
+            _ => input.ok_or("no input file given")?,
+        },
+        output,
+        features,
+        style,
+        indent,
+        colour,
+        json,
+        error_limit,
+    }))
+}
+
+fn proposal(name: &str) -> Result<Proposal, String> {
+    Proposal::from_name(name).ok_or_else(|| format!("unknown proposal `{}`", name))
+}
+
+fn read_input(path: &str) -> io::Result<Vec<u8>> {
+    if path == "-" {
+        let mut input = Vec::new();
+        io::stdin().read_to_end(&mut input)?;
+        Ok(input)
+    } else {
+        std::fs::read(path)
+    }
+}
+
+/// Lists where each section and function body is. The functions' names are only shown if
+/// the whole module decodes, but the layout is listed even if it doesn't.
+fn objdump(bytes: &[u8], features: Features) -> Result<String, DecodeError> {
+    let sections = decoder::sections(bytes)?;
+    let module = decoder::decode(bytes, features).ok();
+    let mut out = String::from("sections:\n");
+    for section in &sections {
+        let name = match &section.name {
+            Some(name) => format!("custom {:?}", name),
+            None => section_name(section.id).to_string(),
+        };
+        out += &format!(
+            "  {:<16} start=0x{:08x} end=0x{:08x} size={}\n",
+            name,
+            section.range.start,
+            section.range.end,
+            section.contents.len()
+        );
+    }
+    if let Some(code) = sections.iter().find(|s: &&Section| s.id == 10) {
+        let imported = module.as_ref().map_or(0, |m| m.imported_funcs());
+        out += "functions:\n";
+        for (i, body) in decoder::function_bodies(bytes, code)?
+            .into_iter()
+            .enumerate()
+        {
+            let id = module
+                .as_ref()
+                .and_then(|m| m.funcs.get(i)?.id.as_deref())
+                .map(|id| format!(" ${}", id))
+                .unwrap_or_default();
+            out += &format!(
+                "  func[{}]{} start=0x{:08x} end=0x{:08x} size={}\n",
+                imported + i,
+                id,
+                body.start,
+                body.end,
+                body.len()
+            );
+        }
+    }
+    Ok(out)
+}
+
+fn section_name(id: u8) -> &'static str {
+    match id {
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        13 => "tag",
+        _ => "unknown",
+    }
+}
+
+/// Checks that text format source is well-formed. Only the lexer exists so far, so this
+/// reports the tokens that can't appear in the text format, any unbalanced parentheses, and
+/// instructions from proposals that aren't in `features`.
+fn check_text(
+    src: &str,
+    features: Features,
+    error_limit: Option<usize>,
+) -> Result<(), Vec<Diagnostic>> {
+    let mut diagnostics = Diagnostics::new(error_limit);
+    let tokens = TokenList::from(src.to_string());
+    tokens.errors(&mut diagnostics);
+    tokens.check_features(features, &mut diagnostics);
+    match diagnostics.has_errors() {
+        true => Err(diagnostics.into_vec()),
+        false => Ok(()),
+    }
+}
+
+/// Why compiling failed.
+#[derive(Debug)]
+enum CompileError {
+    /// The source is malformed.
+    Invalid(Vec<Diagnostic>),
+    /// The source is well-formed, but compiling it needs something that doesn't exist yet.
+    Unsupported(&'static str),
+}
+
+/// Compiles text format source to a binary module.
+///
+/// This checks the source's tokens and then fails, as there's no parser yet.
+fn compile(src: &str, error_limit: Option<usize>) -> Result<Vec<u8>, CompileError> {
+    check_text(src, Features::default(), error_limit).map_err(CompileError::Invalid)?;
+    Err(CompileError::Unsupported(
+        "compiling the text format isn't supported yet, as w2w2 doesn't have a parser",
+    ))
+}
+
+/// The 1-based line and column (in characters) of a byte offset.
+fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    let line_start = src[..offset].rfind('\n').map_or(0, |i| i + 1);
+    (
+        src[..offset].matches('\n').count() + 1,
+        src[line_start..offset].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Result<Option<Options>, String> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(
+            args("print in.wasm -o - --features 1.0 --enable threads"),
+            Ok(Some(Options {
+                command: Command::Print,
+                input: "in.wasm".to_string(),
+                output: Some("-".to_string()),
+                features: Features::v1().with(Proposal::Threads),
+                style: None,
+                indent: Indent::Spaces(2),
+                colour: None,
+                json: false,
+                error_limit: None,
+            }))
+        );
+        assert_eq!(args("- --help"), Ok(None));
+        assert_eq!(args("-").unwrap().unwrap().input, "-");
+        assert_eq!(args(""), Err("no input file given".to_string()));
+        assert_eq!(args("a.wat -o"), Err("-o needs a value".to_string()));
+        assert_eq!(
+            args("a.wat b.wat"),
+            Err("only one input file can be given".to_string())
+        );
+        assert_eq!(
+            args("check a.wat --enable simd2"),
+            Err("unknown proposal `simd2`".to_string())
+        );
+        assert_eq!(args("a.wat -x"), Err("unknown option `-x`".to_string()));
+        for flag in ["--debug-names", "--print-module", "--enable threads"] {
+            assert_eq!(
+                args(&format!("compile a.wat {}", flag)),
+                Err(format!(
+                    "`{}` isn't supported yet, as compiling the text format isn't",
+                    flag.split(' ').next().unwrap()
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let options = args("fmt --tabs --folded a.wat").unwrap().unwrap();
+        assert_eq!(options.command, Command::Fmt);
+        assert_eq!(options.indent, Indent::Tabs);
+        assert_eq!(options.style, Some(Style::Folded));
+        assert_eq!(
+            args("tokens a.wat").unwrap().unwrap().command,
+            Command::Tokens
+        );
+        assert_eq!(args("lsp").unwrap().unwrap().command, Command::Lsp);
+        assert_eq!(
+            args("lsp a.wat"),
+            Err("lsp doesn't take an input".to_string())
+        );
+        assert_eq!(
+            args("strip --tabs a.wasm"),
+            Err("`--tabs` can't be used with the Strip command".to_string())
+        );
+        assert_eq!(
+            args("check -o out a.wasm"),
+            Err("`-o` can't be used with the Check command".to_string())
+        );
+        let options = args("check --json --color always a.wat").unwrap().unwrap();
+        assert_eq!((options.colour, options.json), (Some(true), true));
+        assert_eq!(
+            args("a.wat --error-limit 3").unwrap().unwrap().error_limit,
+            Some(3)
+        );
+        assert_eq!(
+            args("a.wat --error-limit 0").unwrap().unwrap().error_limit,
+            None
+        );
+        assert_eq!(
+            args("a.wat --color sometimes"),
+            Err("`sometimes` isn't always, never or auto".to_string())
+        );
+        // a file that happens to be called `check` has to come after a command
+        assert_eq!(args("compile check").unwrap().unwrap().input, "check");
+    }
+
+    #[test]
+    fn unsupported() {
+        let compile = |src| compile(src, None);
+        assert!(matches!(
+            compile("(module (func nop))"),
+            Err(CompileError::Unsupported(_))
+        ));
+        assert!(matches!(
+            compile("(module (func 1x))"),
+            Err(CompileError::Invalid(errors)) if errors.len() == 1
+        ));
+    }
+
+    #[test]
+    fn check_text_files() {
+        let dir = std::env::temp_dir();
+        let ok = dir.join(format!("w2w2-check-ok-{}.wat", std::process::id()));
+        let bad = dir.join(format!("w2w2-check-bad-{}.wat", std::process::id()));
+        std::fs::write(&ok, "(module (func nop))").unwrap();
+        std::fs::write(&bad, "(module (func 1x))").unwrap();
+        let check = |path: &std::path::Path| {
+            let mut options = args(&format!("check {}", path.display())).unwrap().unwrap();
+            options.colour = Some(false);
+            run(&options)
+        };
+        assert!(check(&ok).is_ok());
+        assert!(matches!(check(&bad), Err(Failure::Input)));
+        std::fs::remove_file(ok).unwrap();
+        std::fs::remove_file(bad).unwrap();
+    }
+
+    #[test]
+    fn objdump_layout() {
+        let bytes = b"\0asm\x01\0\0\0\
+            \x01\x04\x01\x60\x00\x00\
+            \x03\x02\x01\x00\
+            \x0a\x04\x01\x02\x00\x0b\
+            \x00\x0b\x04name\x01\x04\x01\x00\x01f";
+        assert_eq!(
+            objdump(bytes, Features::default()).unwrap(),
+            "sections:
+  type             start=0x00000008 end=0x0000000e size=4
+  function         start=0x0000000e end=0x00000012 size=2
+  code             start=0x00000012 end=0x00000018 size=4
+  custom \"name\"    start=0x00000018 end=0x00000025 size=6
+functions:
+  func[0] $f start=0x00000016 end=0x00000018 size=2
+"
+        );
+    }
+
+    #[test]
+    fn lexical_errors() {
+        let src = "(module\n  (func\n    nop 1x $ \"abc";
+        let errors = check_text(src, Features::default(), None).unwrap_err();
+        let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "unknown token `1x`",
+                "empty identifier",
+                "unterminated string",
+                "unclosed `(`",
+                "unclosed `(`"
+            ]
+        );
+        let errors = check_text(src, Features::default(), Some(2)).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[2].message, "stopping after 2 errors");
+        assert_eq!(
+            errors[0].render("in.wat", src, false),
+            "error: unknown token `1x`
+ --> in.wat:3:9
+  |
+3 |     nop 1x $ \"abc
+  |         ^^"
+        );
+    }
+
+    #[test]
+    fn disabled_proposals() {
+        let src = "(func\n  atomic.fence)";
+        assert!(check_text(src, Features::all(), None).is_ok());
+        let errors = check_text(src, Features::v3(), None).unwrap_err();
+        assert_eq!(
+            errors[0].render("in.wat", src, false),
+            "error: the `threads` proposal is not enabled
+ --> in.wat:2:3
+  |
+2 |   atomic.fence)
+  |   ^^^^^^^^^^^^ `atomic.fence` is from it"
+        );
+    }
+}
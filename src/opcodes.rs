@@ -0,0 +1,840 @@
+use crate::features::Proposal::{self, *};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// What follows an instruction's opcode in the binary format, and its keyword in the text
+/// format.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImmediateKind {
+    None,
+    BlockType,
+    Label,
+    BrTable,
+    Func,
+    /// A type index then a table index.
+    CallIndirect,
+    Local,
+    Global,
+    Table,
+    Memory,
+    MemArg,
+    MemArgLane,
+    I32,
+    I64,
+    F32,
+    F64,
+    V128,
+    Lane,
+    Shuffle,
+    SelectTypes,
+    HeapType,
+    /// A data index then a memory index.
+    MemoryInit,
+    Data,
+    /// The destination memory index then the source one.
+    MemoryCopy,
+    /// An element index then a table index.
+    TableInit,
+    Elem,
+    /// The destination table index then the source one.
+    TableCopy,
+    /// A single reserved zero byte.
+    AtomicFence,
+    Tag,
+    Type,
+    /// A block type then a vector of catch clauses.
+    TryTable,
+    /// A type index then a field index.
+    TypeField,
+    /// A type index then a data index.
+    TypeData,
+    /// A type index then an element index.
+    TypeElem,
+    /// The destination array type index then the source one.
+    TypeType,
+    /// A type index then the number of elements.
+    ArrayNewFixed,
+    /// A heap type, for `ref.test` and `ref.cast` - whether the reference type is nullable
+    /// is in the opcode.
+    RefType,
+    /// Nullability flags, a label, then the source and target heap types.
+    BrOnCast,
+}
+
+// Each op is `Variant = "name", prefix, code, immediate, signature, proposal;`, where a
+// prefix of 0 means a single-byte opcode. The signature is the operand types popped and
+// pushed, separated by a `:` - `i`, `I`, `f`, `F` and `v` are i32, i64, f32, f64 and v128,
+// `a` is an address (whose type depends on the memory), `x` is a type that depends on the
+// immediates or the operand stack, and `*` means that even the number of operands does.
+macro_rules! ops {
+    ( $( $variant:ident = $name:literal, $prefix:literal, $code:literal, $imm:ident, $sig:literal, $proposal:expr; )* ) => {
+        #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+        pub enum Op {
+            $( $variant, )*
+        }
+
+        impl Op {
+            pub const ALL: &'static [Op] = &[ $( Op::$variant, )* ];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $( Op::$variant => $name, )*
+                }
+            }
+
+            /// The prefix byte (`0xFB`, `0xFC`, `0xFD` or `0xFE`) of a multi-byte opcode.
+            pub fn prefix(self) -> Option<u8> {
+                let prefix: u8 = match self {
+                    $( Op::$variant => $prefix, )*
+                };
+                if prefix == 0 {
+                    None
+                } else {
+                    Some(prefix)
+                }
+            }
+
+            /// The opcode, or for a prefixed opcode the LEB128 u32 after the prefix byte.
+            pub fn code(self) -> u32 {
+                match self {
+                    $( Op::$variant => $code, )*
+                }
+            }
+
+            pub fn immediate(self) -> ImmediateKind {
+                match self {
+                    $( Op::$variant => ImmediateKind::$imm, )*
+                }
+            }
+
+            /// The proposal that added this instruction, if it isn't in the MVP.
+            pub fn proposal(self) -> Option<Proposal> {
+                match self {
+                    $( Op::$variant => $proposal, )*
+                }
+            }
+
+            fn signature(self) -> &'static str {
+                match self {
+                    $( Op::$variant => $sig, )*
+                }
+            }
+
+            pub fn from_code(prefix: Option<u8>, code: u32) -> Option<Op> {
+                match (prefix.unwrap_or(0), code) {
+                    $( ($prefix, $code) => Some(Op::$variant), )*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl Op {
+    /// How many operands the instruction pops and pushes, or `None` for instructions like
+    /// `call`, `br` or `block` where that depends on the types of other things.
+    pub fn arity(self) -> Option<(usize, usize)> {
+        let (params, results) = self.signature().split_once(':')?;
+        Some((params.len(), results.len()))
+    }
+
+    /// The instruction's type in the spec's notation, e.g. `[i32 i32] -> [i32]`, with `at` for an
+    /// address and `t` for a type that depends on the immediates or the operand stack. `None`
+    /// for instructions like `call` where even the number of operands varies.
+    pub fn type_string(self) -> Option<String> {
+        let (params, results) = self.signature().split_once(':')?;
+        let types = |sig: &str| {
+            let names: Vec<_> = sig
+                .chars()
+                .map(|ch| match ch {
+                    'i' => "i32",
+                    'I' => "i64",
+                    'f' => "f32",
+                    'F' => "f64",
+                    'v' => "v128",
+                    'a' => "at",
+                    _ => "t",
+                })
+                .collect();
+            names.join(" ")
+        };
+        Some(format!("[{}] -> [{}]", types(params), types(results)))
+    }
+
+    /// The alignment (as a power of two) that a memory instruction has when `align=` is left out,
+    /// which is the number of bytes it accesses. This is worked out from the name, since that
+    /// always says the size - e.g. `i64.load8_u`, `i32.atomic.rmw16.add_u` or `v128.load32x2_s`.
+    pub fn natural_alignment(self) -> Option<u32> {
+        let mut parts = self.name().split('.');
+        let ty = parts.next()?;
+        let access = parts.find(|part| {
+            ["load", "store", "rmw", "wait", "notify"]
+                .iter()
+                .any(|prefix| part.starts_with(prefix))
+        })?;
+        let digits = access.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+        fn number(s: &str) -> (Option<u32>, &str) {
+            let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            (s[..end].parse().ok(), &s[end..])
+        }
+        let bits = match number(digits) {
+            (Some(bits), rest) => match rest.strip_prefix('x').map(number) {
+                Some((Some(lanes), _)) => bits * lanes,
+                _ => bits,
+            },
+            (None, _) => match ty {
+                "i64" | "f64" => 64,
+                "v128" => 128,
+                _ => 32,
+            },
+        };
+        Some((bits / 8).trailing_zeros())
+    }
+
+    /// Looks an instruction up by its text format name. `select` is [`Op::Select`], since
+    /// [`Op::SelectTyped`] is only told apart by its immediates, and likewise `ref.test` and
+    /// `ref.cast` are the non-nullable ones.
+    pub fn from_name(name: &str) -> Option<Op> {
+        Op::ALL.iter().copied().find(|op| op.name() == name)
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+ops! {
+    Unreachable = "unreachable", 0x00, 0x00, None, "*", None;
+    Nop = "nop", 0x00, 0x01, None, ":", None;
+    Block = "block", 0x00, 0x02, BlockType, "*", None;
+    Loop = "loop", 0x00, 0x03, BlockType, "*", None;
+    If = "if", 0x00, 0x04, BlockType, "*", None;
+    Else = "else", 0x00, 0x05, None, "*", None;
+    Try = "try", 0x00, 0x06, BlockType, "*", Some(LegacyExceptionHandling);
+    Catch = "catch", 0x00, 0x07, Tag, "*", Some(LegacyExceptionHandling);
+    Throw = "throw", 0x00, 0x08, Tag, "*", Some(ExceptionHandling);
+    Rethrow = "rethrow", 0x00, 0x09, Label, "*", Some(LegacyExceptionHandling);
+    ThrowRef = "throw_ref", 0x00, 0x0A, None, "*", Some(ExceptionHandling);
+    End = "end", 0x00, 0x0B, None, "*", None;
+    Br = "br", 0x00, 0x0C, Label, "*", None;
+    BrIf = "br_if", 0x00, 0x0D, Label, "*", None;
+    BrTable = "br_table", 0x00, 0x0E, BrTable, "*", None;
+    Return = "return", 0x00, 0x0F, None, "*", None;
+    Call = "call", 0x00, 0x10, Func, "*", None;
+    CallIndirect = "call_indirect", 0x00, 0x11, CallIndirect, "*", None;
+    ReturnCall = "return_call", 0x00, 0x12, Func, "*", Some(TailCall);
+    ReturnCallIndirect = "return_call_indirect", 0x00, 0x13, CallIndirect, "*", Some(TailCall);
+    CallRef = "call_ref", 0x00, 0x14, Type, "*", Some(FunctionReferences);
+    ReturnCallRef = "return_call_ref", 0x00, 0x15, Type, "*", Some(TailCall);
+    Delegate = "delegate", 0x00, 0x18, Label, "*", Some(LegacyExceptionHandling);
+    CatchAll = "catch_all", 0x00, 0x19, None, "*", Some(LegacyExceptionHandling);
+    Drop = "drop", 0x00, 0x1A, None, "x:", None;
+    Select = "select", 0x00, 0x1B, None, "xxi:x", None;
+    SelectTyped = "select", 0x00, 0x1C, SelectTypes, "xxi:x", Some(ReferenceTypes);
+    TryTable = "try_table", 0x00, 0x1F, TryTable, "*", Some(ExceptionHandling);
+    LocalGet = "local.get", 0x00, 0x20, Local, ":x", None;
+    LocalSet = "local.set", 0x00, 0x21, Local, "x:", None;
+    LocalTee = "local.tee", 0x00, 0x22, Local, "x:x", None;
+    GlobalGet = "global.get", 0x00, 0x23, Global, ":x", None;
+    GlobalSet = "global.set", 0x00, 0x24, Global, "x:", None;
+    TableGet = "table.get", 0x00, 0x25, Table, "i:x", Some(ReferenceTypes);
+    TableSet = "table.set", 0x00, 0x26, Table, "ix:", Some(ReferenceTypes);
+    I32Load = "i32.load", 0x00, 0x28, MemArg, "a:i", None;
+    I64Load = "i64.load", 0x00, 0x29, MemArg, "a:I", None;
+    F32Load = "f32.load", 0x00, 0x2A, MemArg, "a:f", None;
+    F64Load = "f64.load", 0x00, 0x2B, MemArg, "a:F", None;
+    I32Load8S = "i32.load8_s", 0x00, 0x2C, MemArg, "a:i", None;
+    I32Load8U = "i32.load8_u", 0x00, 0x2D, MemArg, "a:i", None;
+    I32Load16S = "i32.load16_s", 0x00, 0x2E, MemArg, "a:i", None;
+    I32Load16U = "i32.load16_u", 0x00, 0x2F, MemArg, "a:i", None;
+    I64Load8S = "i64.load8_s", 0x00, 0x30, MemArg, "a:I", None;
+    I64Load8U = "i64.load8_u", 0x00, 0x31, MemArg, "a:I", None;
+    I64Load16S = "i64.load16_s", 0x00, 0x32, MemArg, "a:I", None;
+    I64Load16U = "i64.load16_u", 0x00, 0x33, MemArg, "a:I", None;
+    I64Load32S = "i64.load32_s", 0x00, 0x34, MemArg, "a:I", None;
+    I64Load32U = "i64.load32_u", 0x00, 0x35, MemArg, "a:I", None;
+    I32Store = "i32.store", 0x00, 0x36, MemArg, "ai:", None;
+    I64Store = "i64.store", 0x00, 0x37, MemArg, "aI:", None;
+    F32Store = "f32.store", 0x00, 0x38, MemArg, "af:", None;
+    F64Store = "f64.store", 0x00, 0x39, MemArg, "aF:", None;
+    I32Store8 = "i32.store8", 0x00, 0x3A, MemArg, "ai:", None;
+    I32Store16 = "i32.store16", 0x00, 0x3B, MemArg, "ai:", None;
+    I64Store8 = "i64.store8", 0x00, 0x3C, MemArg, "aI:", None;
+    I64Store16 = "i64.store16", 0x00, 0x3D, MemArg, "aI:", None;
+    I64Store32 = "i64.store32", 0x00, 0x3E, MemArg, "aI:", None;
+    MemorySize = "memory.size", 0x00, 0x3F, Memory, ":a", None;
+    MemoryGrow = "memory.grow", 0x00, 0x40, Memory, "a:a", None;
+    I32Const = "i32.const", 0x00, 0x41, I32, ":i", None;
+    I64Const = "i64.const", 0x00, 0x42, I64, ":I", None;
+    F32Const = "f32.const", 0x00, 0x43, F32, ":f", None;
+    F64Const = "f64.const", 0x00, 0x44, F64, ":F", None;
+    I32Eqz = "i32.eqz", 0x00, 0x45, None, "i:i", None;
+    I32Eq = "i32.eq", 0x00, 0x46, None, "ii:i", None;
+    I32Ne = "i32.ne", 0x00, 0x47, None, "ii:i", None;
+    I32LtS = "i32.lt_s", 0x00, 0x48, None, "ii:i", None;
+    I32LtU = "i32.lt_u", 0x00, 0x49, None, "ii:i", None;
+    I32GtS = "i32.gt_s", 0x00, 0x4A, None, "ii:i", None;
+    I32GtU = "i32.gt_u", 0x00, 0x4B, None, "ii:i", None;
+    I32LeS = "i32.le_s", 0x00, 0x4C, None, "ii:i", None;
+    I32LeU = "i32.le_u", 0x00, 0x4D, None, "ii:i", None;
+    I32GeS = "i32.ge_s", 0x00, 0x4E, None, "ii:i", None;
+    I32GeU = "i32.ge_u", 0x00, 0x4F, None, "ii:i", None;
+    I64Eqz = "i64.eqz", 0x00, 0x50, None, "I:i", None;
+    I64Eq = "i64.eq", 0x00, 0x51, None, "II:i", None;
+    I64Ne = "i64.ne", 0x00, 0x52, None, "II:i", None;
+    I64LtS = "i64.lt_s", 0x00, 0x53, None, "II:i", None;
+    I64LtU = "i64.lt_u", 0x00, 0x54, None, "II:i", None;
+    I64GtS = "i64.gt_s", 0x00, 0x55, None, "II:i", None;
+    I64GtU = "i64.gt_u", 0x00, 0x56, None, "II:i", None;
+    I64LeS = "i64.le_s", 0x00, 0x57, None, "II:i", None;
+    I64LeU = "i64.le_u", 0x00, 0x58, None, "II:i", None;
+    I64GeS = "i64.ge_s", 0x00, 0x59, None, "II:i", None;
+    I64GeU = "i64.ge_u", 0x00, 0x5A, None, "II:i", None;
+    F32Eq = "f32.eq", 0x00, 0x5B, None, "ff:i", None;
+    F32Ne = "f32.ne", 0x00, 0x5C, None, "ff:i", None;
+    F32Lt = "f32.lt", 0x00, 0x5D, None, "ff:i", None;
+    F32Gt = "f32.gt", 0x00, 0x5E, None, "ff:i", None;
+    F32Le = "f32.le", 0x00, 0x5F, None, "ff:i", None;
+    F32Ge = "f32.ge", 0x00, 0x60, None, "ff:i", None;
+    F64Eq = "f64.eq", 0x00, 0x61, None, "FF:i", None;
+    F64Ne = "f64.ne", 0x00, 0x62, None, "FF:i", None;
+    F64Lt = "f64.lt", 0x00, 0x63, None, "FF:i", None;
+    F64Gt = "f64.gt", 0x00, 0x64, None, "FF:i", None;
+    F64Le = "f64.le", 0x00, 0x65, None, "FF:i", None;
+    F64Ge = "f64.ge", 0x00, 0x66, None, "FF:i", None;
+    I32Clz = "i32.clz", 0x00, 0x67, None, "i:i", None;
+    I32Ctz = "i32.ctz", 0x00, 0x68, None, "i:i", None;
+    I32Popcnt = "i32.popcnt", 0x00, 0x69, None, "i:i", None;
+    I32Add = "i32.add", 0x00, 0x6A, None, "ii:i", None;
+    I32Sub = "i32.sub", 0x00, 0x6B, None, "ii:i", None;
+    I32Mul = "i32.mul", 0x00, 0x6C, None, "ii:i", None;
+    I32DivS = "i32.div_s", 0x00, 0x6D, None, "ii:i", None;
+    I32DivU = "i32.div_u", 0x00, 0x6E, None, "ii:i", None;
+    I32RemS = "i32.rem_s", 0x00, 0x6F, None, "ii:i", None;
+    I32RemU = "i32.rem_u", 0x00, 0x70, None, "ii:i", None;
+    I32And = "i32.and", 0x00, 0x71, None, "ii:i", None;
+    I32Or = "i32.or", 0x00, 0x72, None, "ii:i", None;
+    I32Xor = "i32.xor", 0x00, 0x73, None, "ii:i", None;
+    I32Shl = "i32.shl", 0x00, 0x74, None, "ii:i", None;
+    I32ShrS = "i32.shr_s", 0x00, 0x75, None, "ii:i", None;
+    I32ShrU = "i32.shr_u", 0x00, 0x76, None, "ii:i", None;
+    I32Rotl = "i32.rotl", 0x00, 0x77, None, "ii:i", None;
+    I32Rotr = "i32.rotr", 0x00, 0x78, None, "ii:i", None;
+    I64Clz = "i64.clz", 0x00, 0x79, None, "I:I", None;
+    I64Ctz = "i64.ctz", 0x00, 0x7A, None, "I:I", None;
+    I64Popcnt = "i64.popcnt", 0x00, 0x7B, None, "I:I", None;
+    I64Add = "i64.add", 0x00, 0x7C, None, "II:I", None;
+    I64Sub = "i64.sub", 0x00, 0x7D, None, "II:I", None;
+    I64Mul = "i64.mul", 0x00, 0x7E, None, "II:I", None;
+    I64DivS = "i64.div_s", 0x00, 0x7F, None, "II:I", None;
+    I64DivU = "i64.div_u", 0x00, 0x80, None, "II:I", None;
+    I64RemS = "i64.rem_s", 0x00, 0x81, None, "II:I", None;
+    I64RemU = "i64.rem_u", 0x00, 0x82, None, "II:I", None;
+    I64And = "i64.and", 0x00, 0x83, None, "II:I", None;
+    I64Or = "i64.or", 0x00, 0x84, None, "II:I", None;
+    I64Xor = "i64.xor", 0x00, 0x85, None, "II:I", None;
+    I64Shl = "i64.shl", 0x00, 0x86, None, "II:I", None;
+    I64ShrS = "i64.shr_s", 0x00, 0x87, None, "II:I", None;
+    I64ShrU = "i64.shr_u", 0x00, 0x88, None, "II:I", None;
+    I64Rotl = "i64.rotl", 0x00, 0x89, None, "II:I", None;
+    I64Rotr = "i64.rotr", 0x00, 0x8A, None, "II:I", None;
+    F32Abs = "f32.abs", 0x00, 0x8B, None, "f:f", None;
+    F32Neg = "f32.neg", 0x00, 0x8C, None, "f:f", None;
+    F32Ceil = "f32.ceil", 0x00, 0x8D, None, "f:f", None;
+    F32Floor = "f32.floor", 0x00, 0x8E, None, "f:f", None;
+    F32Trunc = "f32.trunc", 0x00, 0x8F, None, "f:f", None;
+    F32Nearest = "f32.nearest", 0x00, 0x90, None, "f:f", None;
+    F32Sqrt = "f32.sqrt", 0x00, 0x91, None, "f:f", None;
+    F32Add = "f32.add", 0x00, 0x92, None, "ff:f", None;
+    F32Sub = "f32.sub", 0x00, 0x93, None, "ff:f", None;
+    F32Mul = "f32.mul", 0x00, 0x94, None, "ff:f", None;
+    F32Div = "f32.div", 0x00, 0x95, None, "ff:f", None;
+    F32Min = "f32.min", 0x00, 0x96, None, "ff:f", None;
+    F32Max = "f32.max", 0x00, 0x97, None, "ff:f", None;
+    F32Copysign = "f32.copysign", 0x00, 0x98, None, "ff:f", None;
+    F64Abs = "f64.abs", 0x00, 0x99, None, "F:F", None;
+    F64Neg = "f64.neg", 0x00, 0x9A, None, "F:F", None;
+    F64Ceil = "f64.ceil", 0x00, 0x9B, None, "F:F", None;
+    F64Floor = "f64.floor", 0x00, 0x9C, None, "F:F", None;
+    F64Trunc = "f64.trunc", 0x00, 0x9D, None, "F:F", None;
+    F64Nearest = "f64.nearest", 0x00, 0x9E, None, "F:F", None;
+    F64Sqrt = "f64.sqrt", 0x00, 0x9F, None, "F:F", None;
+    F64Add = "f64.add", 0x00, 0xA0, None, "FF:F", None;
+    F64Sub = "f64.sub", 0x00, 0xA1, None, "FF:F", None;
+    F64Mul = "f64.mul", 0x00, 0xA2, None, "FF:F", None;
+    F64Div = "f64.div", 0x00, 0xA3, None, "FF:F", None;
+    F64Min = "f64.min", 0x00, 0xA4, None, "FF:F", None;
+    F64Max = "f64.max", 0x00, 0xA5, None, "FF:F", None;
+    F64Copysign = "f64.copysign", 0x00, 0xA6, None, "FF:F", None;
+    I32WrapI64 = "i32.wrap_i64", 0x00, 0xA7, None, "I:i", None;
+    I32TruncF32S = "i32.trunc_f32_s", 0x00, 0xA8, None, "f:i", None;
+    I32TruncF32U = "i32.trunc_f32_u", 0x00, 0xA9, None, "f:i", None;
+    I32TruncF64S = "i32.trunc_f64_s", 0x00, 0xAA, None, "F:i", None;
+    I32TruncF64U = "i32.trunc_f64_u", 0x00, 0xAB, None, "F:i", None;
+    I64ExtendI32S = "i64.extend_i32_s", 0x00, 0xAC, None, "i:I", None;
+    I64ExtendI32U = "i64.extend_i32_u", 0x00, 0xAD, None, "i:I", None;
+    I64TruncF32S = "i64.trunc_f32_s", 0x00, 0xAE, None, "f:I", None;
+    I64TruncF32U = "i64.trunc_f32_u", 0x00, 0xAF, None, "f:I", None;
+    I64TruncF64S = "i64.trunc_f64_s", 0x00, 0xB0, None, "F:I", None;
+    I64TruncF64U = "i64.trunc_f64_u", 0x00, 0xB1, None, "F:I", None;
+    F32ConvertI32S = "f32.convert_i32_s", 0x00, 0xB2, None, "i:f", None;
+    F32ConvertI32U = "f32.convert_i32_u", 0x00, 0xB3, None, "i:f", None;
+    F32ConvertI64S = "f32.convert_i64_s", 0x00, 0xB4, None, "I:f", None;
+    F32ConvertI64U = "f32.convert_i64_u", 0x00, 0xB5, None, "I:f", None;
+    F32DemoteF64 = "f32.demote_f64", 0x00, 0xB6, None, "F:f", None;
+    F64ConvertI32S = "f64.convert_i32_s", 0x00, 0xB7, None, "i:F", None;
+    F64ConvertI32U = "f64.convert_i32_u", 0x00, 0xB8, None, "i:F", None;
+    F64ConvertI64S = "f64.convert_i64_s", 0x00, 0xB9, None, "I:F", None;
+    F64ConvertI64U = "f64.convert_i64_u", 0x00, 0xBA, None, "I:F", None;
+    F64PromoteF32 = "f64.promote_f32", 0x00, 0xBB, None, "f:F", None;
+    I32ReinterpretF32 = "i32.reinterpret_f32", 0x00, 0xBC, None, "f:i", None;
+    I64ReinterpretF64 = "i64.reinterpret_f64", 0x00, 0xBD, None, "F:I", None;
+    F32ReinterpretI32 = "f32.reinterpret_i32", 0x00, 0xBE, None, "i:f", None;
+    F64ReinterpretI64 = "f64.reinterpret_i64", 0x00, 0xBF, None, "I:F", None;
+    I32Extend8S = "i32.extend8_s", 0x00, 0xC0, None, "i:i", Some(SignExtension);
+    I32Extend16S = "i32.extend16_s", 0x00, 0xC1, None, "i:i", Some(SignExtension);
+    I64Extend8S = "i64.extend8_s", 0x00, 0xC2, None, "I:I", Some(SignExtension);
+    I64Extend16S = "i64.extend16_s", 0x00, 0xC3, None, "I:I", Some(SignExtension);
+    I64Extend32S = "i64.extend32_s", 0x00, 0xC4, None, "I:I", Some(SignExtension);
+    RefNull = "ref.null", 0x00, 0xD0, HeapType, ":x", Some(ReferenceTypes);
+    RefIsNull = "ref.is_null", 0x00, 0xD1, None, "x:i", Some(ReferenceTypes);
+    RefFunc = "ref.func", 0x00, 0xD2, Func, ":x", Some(ReferenceTypes);
+    RefEq = "ref.eq", 0x00, 0xD3, None, "xx:i", Some(Gc);
+    RefAsNonNull = "ref.as_non_null", 0x00, 0xD4, None, "x:x", Some(FunctionReferences);
+    BrOnNull = "br_on_null", 0x00, 0xD5, Label, "*", Some(FunctionReferences);
+    BrOnNonNull = "br_on_non_null", 0x00, 0xD6, Label, "*", Some(FunctionReferences);
+    StructNew = "struct.new", 0xFB, 0x00, Type, "*", Some(Gc);
+    StructNewDefault = "struct.new_default", 0xFB, 0x01, Type, ":x", Some(Gc);
+    StructGet = "struct.get", 0xFB, 0x02, TypeField, "x:x", Some(Gc);
+    StructGetS = "struct.get_s", 0xFB, 0x03, TypeField, "x:i", Some(Gc);
+    StructGetU = "struct.get_u", 0xFB, 0x04, TypeField, "x:i", Some(Gc);
+    StructSet = "struct.set", 0xFB, 0x05, TypeField, "xx:", Some(Gc);
+    ArrayNew = "array.new", 0xFB, 0x06, Type, "xi:x", Some(Gc);
+    ArrayNewDefault = "array.new_default", 0xFB, 0x07, Type, "i:x", Some(Gc);
+    ArrayNewFixed = "array.new_fixed", 0xFB, 0x08, ArrayNewFixed, "*", Some(Gc);
+    ArrayNewData = "array.new_data", 0xFB, 0x09, TypeData, "ii:x", Some(Gc);
+    ArrayNewElem = "array.new_elem", 0xFB, 0x0A, TypeElem, "ii:x", Some(Gc);
+    ArrayGet = "array.get", 0xFB, 0x0B, Type, "xi:x", Some(Gc);
+    ArrayGetS = "array.get_s", 0xFB, 0x0C, Type, "xi:i", Some(Gc);
+    ArrayGetU = "array.get_u", 0xFB, 0x0D, Type, "xi:i", Some(Gc);
+    ArraySet = "array.set", 0xFB, 0x0E, Type, "xix:", Some(Gc);
+    ArrayLen = "array.len", 0xFB, 0x0F, None, "x:i", Some(Gc);
+    ArrayFill = "array.fill", 0xFB, 0x10, Type, "xixi:", Some(Gc);
+    ArrayCopy = "array.copy", 0xFB, 0x11, TypeType, "xixii:", Some(Gc);
+    ArrayInitData = "array.init_data", 0xFB, 0x12, TypeData, "xiii:", Some(Gc);
+    ArrayInitElem = "array.init_elem", 0xFB, 0x13, TypeElem, "xiii:", Some(Gc);
+    RefTest = "ref.test", 0xFB, 0x14, RefType, "x:i", Some(Gc);
+    RefTestNull = "ref.test", 0xFB, 0x15, RefType, "x:i", Some(Gc);
+    RefCast = "ref.cast", 0xFB, 0x16, RefType, "x:x", Some(Gc);
+    RefCastNull = "ref.cast", 0xFB, 0x17, RefType, "x:x", Some(Gc);
+    BrOnCast = "br_on_cast", 0xFB, 0x18, BrOnCast, "*", Some(Gc);
+    BrOnCastFail = "br_on_cast_fail", 0xFB, 0x19, BrOnCast, "*", Some(Gc);
+    AnyConvertExtern = "any.convert_extern", 0xFB, 0x1A, None, "x:x", Some(Gc);
+    ExternConvertAny = "extern.convert_any", 0xFB, 0x1B, None, "x:x", Some(Gc);
+    RefI31 = "ref.i31", 0xFB, 0x1C, None, "i:x", Some(Gc);
+    I31GetS = "i31.get_s", 0xFB, 0x1D, None, "x:i", Some(Gc);
+    I31GetU = "i31.get_u", 0xFB, 0x1E, None, "x:i", Some(Gc);
+    I32TruncSatF32S = "i32.trunc_sat_f32_s", 0xFC, 0x00, None, "f:i", Some(SaturatingFloatToInt);
+    I32TruncSatF32U = "i32.trunc_sat_f32_u", 0xFC, 0x01, None, "f:i", Some(SaturatingFloatToInt);
+    I32TruncSatF64S = "i32.trunc_sat_f64_s", 0xFC, 0x02, None, "F:i", Some(SaturatingFloatToInt);
+    I32TruncSatF64U = "i32.trunc_sat_f64_u", 0xFC, 0x03, None, "F:i", Some(SaturatingFloatToInt);
+    I64TruncSatF32S = "i64.trunc_sat_f32_s", 0xFC, 0x04, None, "f:I", Some(SaturatingFloatToInt);
+    I64TruncSatF32U = "i64.trunc_sat_f32_u", 0xFC, 0x05, None, "f:I", Some(SaturatingFloatToInt);
+    I64TruncSatF64S = "i64.trunc_sat_f64_s", 0xFC, 0x06, None, "F:I", Some(SaturatingFloatToInt);
+    I64TruncSatF64U = "i64.trunc_sat_f64_u", 0xFC, 0x07, None, "F:I", Some(SaturatingFloatToInt);
+    MemoryInit = "memory.init", 0xFC, 0x08, MemoryInit, "aii:", Some(BulkMemory);
+    DataDrop = "data.drop", 0xFC, 0x09, Data, ":", Some(BulkMemory);
+    MemoryCopy = "memory.copy", 0xFC, 0x0A, MemoryCopy, "aaa:", Some(BulkMemory);
+    MemoryFill = "memory.fill", 0xFC, 0x0B, Memory, "aia:", Some(BulkMemory);
+    TableInit = "table.init", 0xFC, 0x0C, TableInit, "iii:", Some(BulkMemory);
+    ElemDrop = "elem.drop", 0xFC, 0x0D, Elem, ":", Some(BulkMemory);
+    TableCopy = "table.copy", 0xFC, 0x0E, TableCopy, "iii:", Some(BulkMemory);
+    TableGrow = "table.grow", 0xFC, 0x0F, Table, "xi:i", Some(ReferenceTypes);
+    TableSize = "table.size", 0xFC, 0x10, Table, ":i", Some(ReferenceTypes);
+    TableFill = "table.fill", 0xFC, 0x11, Table, "ixi:", Some(ReferenceTypes);
+    I64Add128 = "i64.add128", 0xFC, 0x13, None, "IIII:II", Some(WideArithmetic);
+    I64Sub128 = "i64.sub128", 0xFC, 0x14, None, "IIII:II", Some(WideArithmetic);
+    I64MulWideS = "i64.mul_wide_s", 0xFC, 0x15, None, "II:II", Some(WideArithmetic);
+    I64MulWideU = "i64.mul_wide_u", 0xFC, 0x16, None, "II:II", Some(WideArithmetic);
+    V128Load = "v128.load", 0xFD, 0x00, MemArg, "a:v", Some(Simd);
+    V128Load8x8S = "v128.load8x8_s", 0xFD, 0x01, MemArg, "a:v", Some(Simd);
+    V128Load8x8U = "v128.load8x8_u", 0xFD, 0x02, MemArg, "a:v", Some(Simd);
+    V128Load16x4S = "v128.load16x4_s", 0xFD, 0x03, MemArg, "a:v", Some(Simd);
+    V128Load16x4U = "v128.load16x4_u", 0xFD, 0x04, MemArg, "a:v", Some(Simd);
+    V128Load32x2S = "v128.load32x2_s", 0xFD, 0x05, MemArg, "a:v", Some(Simd);
+    V128Load32x2U = "v128.load32x2_u", 0xFD, 0x06, MemArg, "a:v", Some(Simd);
+    V128Load8Splat = "v128.load8_splat", 0xFD, 0x07, MemArg, "a:v", Some(Simd);
+    V128Load16Splat = "v128.load16_splat", 0xFD, 0x08, MemArg, "a:v", Some(Simd);
+    V128Load32Splat = "v128.load32_splat", 0xFD, 0x09, MemArg, "a:v", Some(Simd);
+    V128Load64Splat = "v128.load64_splat", 0xFD, 0x0A, MemArg, "a:v", Some(Simd);
+    V128Store = "v128.store", 0xFD, 0x0B, MemArg, "av:", Some(Simd);
+    V128Const = "v128.const", 0xFD, 0x0C, V128, ":v", Some(Simd);
+    I8x16Shuffle = "i8x16.shuffle", 0xFD, 0x0D, Shuffle, "vv:v", Some(Simd);
+    I8x16Swizzle = "i8x16.swizzle", 0xFD, 0x0E, None, "vv:v", Some(Simd);
+    I8x16Splat = "i8x16.splat", 0xFD, 0x0F, None, "i:v", Some(Simd);
+    I16x8Splat = "i16x8.splat", 0xFD, 0x10, None, "i:v", Some(Simd);
+    I32x4Splat = "i32x4.splat", 0xFD, 0x11, None, "i:v", Some(Simd);
+    I64x2Splat = "i64x2.splat", 0xFD, 0x12, None, "I:v", Some(Simd);
+    F32x4Splat = "f32x4.splat", 0xFD, 0x13, None, "f:v", Some(Simd);
+    F64x2Splat = "f64x2.splat", 0xFD, 0x14, None, "F:v", Some(Simd);
+    I8x16ExtractLaneS = "i8x16.extract_lane_s", 0xFD, 0x15, Lane, "v:i", Some(Simd);
+    I8x16ExtractLaneU = "i8x16.extract_lane_u", 0xFD, 0x16, Lane, "v:i", Some(Simd);
+    I8x16ReplaceLane = "i8x16.replace_lane", 0xFD, 0x17, Lane, "vi:v", Some(Simd);
+    I16x8ExtractLaneS = "i16x8.extract_lane_s", 0xFD, 0x18, Lane, "v:i", Some(Simd);
+    I16x8ExtractLaneU = "i16x8.extract_lane_u", 0xFD, 0x19, Lane, "v:i", Some(Simd);
+    I16x8ReplaceLane = "i16x8.replace_lane", 0xFD, 0x1A, Lane, "vi:v", Some(Simd);
+    I32x4ExtractLane = "i32x4.extract_lane", 0xFD, 0x1B, Lane, "v:i", Some(Simd);
+    I32x4ReplaceLane = "i32x4.replace_lane", 0xFD, 0x1C, Lane, "vi:v", Some(Simd);
+    I64x2ExtractLane = "i64x2.extract_lane", 0xFD, 0x1D, Lane, "v:I", Some(Simd);
+    I64x2ReplaceLane = "i64x2.replace_lane", 0xFD, 0x1E, Lane, "vI:v", Some(Simd);
+    F32x4ExtractLane = "f32x4.extract_lane", 0xFD, 0x1F, Lane, "v:f", Some(Simd);
+    F32x4ReplaceLane = "f32x4.replace_lane", 0xFD, 0x20, Lane, "vf:v", Some(Simd);
+    F64x2ExtractLane = "f64x2.extract_lane", 0xFD, 0x21, Lane, "v:F", Some(Simd);
+    F64x2ReplaceLane = "f64x2.replace_lane", 0xFD, 0x22, Lane, "vF:v", Some(Simd);
+    I8x16Eq = "i8x16.eq", 0xFD, 0x23, None, "vv:v", Some(Simd);
+    I8x16Ne = "i8x16.ne", 0xFD, 0x24, None, "vv:v", Some(Simd);
+    I8x16LtS = "i8x16.lt_s", 0xFD, 0x25, None, "vv:v", Some(Simd);
+    I8x16LtU = "i8x16.lt_u", 0xFD, 0x26, None, "vv:v", Some(Simd);
+    I8x16GtS = "i8x16.gt_s", 0xFD, 0x27, None, "vv:v", Some(Simd);
+    I8x16GtU = "i8x16.gt_u", 0xFD, 0x28, None, "vv:v", Some(Simd);
+    I8x16LeS = "i8x16.le_s", 0xFD, 0x29, None, "vv:v", Some(Simd);
+    I8x16LeU = "i8x16.le_u", 0xFD, 0x2A, None, "vv:v", Some(Simd);
+    I8x16GeS = "i8x16.ge_s", 0xFD, 0x2B, None, "vv:v", Some(Simd);
+    I8x16GeU = "i8x16.ge_u", 0xFD, 0x2C, None, "vv:v", Some(Simd);
+    I16x8Eq = "i16x8.eq", 0xFD, 0x2D, None, "vv:v", Some(Simd);
+    I16x8Ne = "i16x8.ne", 0xFD, 0x2E, None, "vv:v", Some(Simd);
+    I16x8LtS = "i16x8.lt_s", 0xFD, 0x2F, None, "vv:v", Some(Simd);
+    I16x8LtU = "i16x8.lt_u", 0xFD, 0x30, None, "vv:v", Some(Simd);
+    I16x8GtS = "i16x8.gt_s", 0xFD, 0x31, None, "vv:v", Some(Simd);
+    I16x8GtU = "i16x8.gt_u", 0xFD, 0x32, None, "vv:v", Some(Simd);
+    I16x8LeS = "i16x8.le_s", 0xFD, 0x33, None, "vv:v", Some(Simd);
+    I16x8LeU = "i16x8.le_u", 0xFD, 0x34, None, "vv:v", Some(Simd);
+    I16x8GeS = "i16x8.ge_s", 0xFD, 0x35, None, "vv:v", Some(Simd);
+    I16x8GeU = "i16x8.ge_u", 0xFD, 0x36, None, "vv:v", Some(Simd);
+    I32x4Eq = "i32x4.eq", 0xFD, 0x37, None, "vv:v", Some(Simd);
+    I32x4Ne = "i32x4.ne", 0xFD, 0x38, None, "vv:v", Some(Simd);
+    I32x4LtS = "i32x4.lt_s", 0xFD, 0x39, None, "vv:v", Some(Simd);
+    I32x4LtU = "i32x4.lt_u", 0xFD, 0x3A, None, "vv:v", Some(Simd);
+    I32x4GtS = "i32x4.gt_s", 0xFD, 0x3B, None, "vv:v", Some(Simd);
+    I32x4GtU = "i32x4.gt_u", 0xFD, 0x3C, None, "vv:v", Some(Simd);
+    I32x4LeS = "i32x4.le_s", 0xFD, 0x3D, None, "vv:v", Some(Simd);
+    I32x4LeU = "i32x4.le_u", 0xFD, 0x3E, None, "vv:v", Some(Simd);
+    I32x4GeS = "i32x4.ge_s", 0xFD, 0x3F, None, "vv:v", Some(Simd);
+    I32x4GeU = "i32x4.ge_u", 0xFD, 0x40, None, "vv:v", Some(Simd);
+    F32x4Eq = "f32x4.eq", 0xFD, 0x41, None, "vv:v", Some(Simd);
+    F32x4Ne = "f32x4.ne", 0xFD, 0x42, None, "vv:v", Some(Simd);
+    F32x4Lt = "f32x4.lt", 0xFD, 0x43, None, "vv:v", Some(Simd);
+    F32x4Gt = "f32x4.gt", 0xFD, 0x44, None, "vv:v", Some(Simd);
+    F32x4Le = "f32x4.le", 0xFD, 0x45, None, "vv:v", Some(Simd);
+    F32x4Ge = "f32x4.ge", 0xFD, 0x46, None, "vv:v", Some(Simd);
+    F64x2Eq = "f64x2.eq", 0xFD, 0x47, None, "vv:v", Some(Simd);
+    F64x2Ne = "f64x2.ne", 0xFD, 0x48, None, "vv:v", Some(Simd);
+    F64x2Lt = "f64x2.lt", 0xFD, 0x49, None, "vv:v", Some(Simd);
+    F64x2Gt = "f64x2.gt", 0xFD, 0x4A, None, "vv:v", Some(Simd);
+    F64x2Le = "f64x2.le", 0xFD, 0x4B, None, "vv:v", Some(Simd);
+    F64x2Ge = "f64x2.ge", 0xFD, 0x4C, None, "vv:v", Some(Simd);
+    V128Not = "v128.not", 0xFD, 0x4D, None, "v:v", Some(Simd);
+    V128And = "v128.and", 0xFD, 0x4E, None, "vv:v", Some(Simd);
+    V128Andnot = "v128.andnot", 0xFD, 0x4F, None, "vv:v", Some(Simd);
+    V128Or = "v128.or", 0xFD, 0x50, None, "vv:v", Some(Simd);
+    V128Xor = "v128.xor", 0xFD, 0x51, None, "vv:v", Some(Simd);
+    V128Bitselect = "v128.bitselect", 0xFD, 0x52, None, "vvv:v", Some(Simd);
+    V128AnyTrue = "v128.any_true", 0xFD, 0x53, None, "v:i", Some(Simd);
+    V128Load8Lane = "v128.load8_lane", 0xFD, 0x54, MemArgLane, "av:v", Some(Simd);
+    V128Load16Lane = "v128.load16_lane", 0xFD, 0x55, MemArgLane, "av:v", Some(Simd);
+    V128Load32Lane = "v128.load32_lane", 0xFD, 0x56, MemArgLane, "av:v", Some(Simd);
+    V128Load64Lane = "v128.load64_lane", 0xFD, 0x57, MemArgLane, "av:v", Some(Simd);
+    V128Store8Lane = "v128.store8_lane", 0xFD, 0x58, MemArgLane, "av:", Some(Simd);
+    V128Store16Lane = "v128.store16_lane", 0xFD, 0x59, MemArgLane, "av:", Some(Simd);
+    V128Store32Lane = "v128.store32_lane", 0xFD, 0x5A, MemArgLane, "av:", Some(Simd);
+    V128Store64Lane = "v128.store64_lane", 0xFD, 0x5B, MemArgLane, "av:", Some(Simd);
+    V128Load32Zero = "v128.load32_zero", 0xFD, 0x5C, MemArg, "a:v", Some(Simd);
+    V128Load64Zero = "v128.load64_zero", 0xFD, 0x5D, MemArg, "a:v", Some(Simd);
+    F32x4DemoteF64x2Zero = "f32x4.demote_f64x2_zero", 0xFD, 0x5E, None, "v:v", Some(Simd);
+    F64x2PromoteLowF32x4 = "f64x2.promote_low_f32x4", 0xFD, 0x5F, None, "v:v", Some(Simd);
+    I8x16Abs = "i8x16.abs", 0xFD, 0x60, None, "v:v", Some(Simd);
+    I8x16Neg = "i8x16.neg", 0xFD, 0x61, None, "v:v", Some(Simd);
+    I8x16Popcnt = "i8x16.popcnt", 0xFD, 0x62, None, "v:v", Some(Simd);
+    I8x16AllTrue = "i8x16.all_true", 0xFD, 0x63, None, "v:i", Some(Simd);
+    I8x16Bitmask = "i8x16.bitmask", 0xFD, 0x64, None, "v:i", Some(Simd);
+    I8x16NarrowI16x8S = "i8x16.narrow_i16x8_s", 0xFD, 0x65, None, "vv:v", Some(Simd);
+    I8x16NarrowI16x8U = "i8x16.narrow_i16x8_u", 0xFD, 0x66, None, "vv:v", Some(Simd);
+    F32x4Ceil = "f32x4.ceil", 0xFD, 0x67, None, "v:v", Some(Simd);
+    F32x4Floor = "f32x4.floor", 0xFD, 0x68, None, "v:v", Some(Simd);
+    F32x4Trunc = "f32x4.trunc", 0xFD, 0x69, None, "v:v", Some(Simd);
+    F32x4Nearest = "f32x4.nearest", 0xFD, 0x6A, None, "v:v", Some(Simd);
+    I8x16Shl = "i8x16.shl", 0xFD, 0x6B, None, "vi:v", Some(Simd);
+    I8x16ShrS = "i8x16.shr_s", 0xFD, 0x6C, None, "vi:v", Some(Simd);
+    I8x16ShrU = "i8x16.shr_u", 0xFD, 0x6D, None, "vi:v", Some(Simd);
+    I8x16Add = "i8x16.add", 0xFD, 0x6E, None, "vv:v", Some(Simd);
+    I8x16AddSatS = "i8x16.add_sat_s", 0xFD, 0x6F, None, "vv:v", Some(Simd);
+    I8x16AddSatU = "i8x16.add_sat_u", 0xFD, 0x70, None, "vv:v", Some(Simd);
+    I8x16Sub = "i8x16.sub", 0xFD, 0x71, None, "vv:v", Some(Simd);
+    I8x16SubSatS = "i8x16.sub_sat_s", 0xFD, 0x72, None, "vv:v", Some(Simd);
+    I8x16SubSatU = "i8x16.sub_sat_u", 0xFD, 0x73, None, "vv:v", Some(Simd);
+    F64x2Ceil = "f64x2.ceil", 0xFD, 0x74, None, "v:v", Some(Simd);
+    F64x2Floor = "f64x2.floor", 0xFD, 0x75, None, "v:v", Some(Simd);
+    I8x16MinS = "i8x16.min_s", 0xFD, 0x76, None, "vv:v", Some(Simd);
+    I8x16MinU = "i8x16.min_u", 0xFD, 0x77, None, "vv:v", Some(Simd);
+    I8x16MaxS = "i8x16.max_s", 0xFD, 0x78, None, "vv:v", Some(Simd);
+    I8x16MaxU = "i8x16.max_u", 0xFD, 0x79, None, "vv:v", Some(Simd);
+    F64x2Trunc = "f64x2.trunc", 0xFD, 0x7A, None, "v:v", Some(Simd);
+    I8x16AvgrU = "i8x16.avgr_u", 0xFD, 0x7B, None, "vv:v", Some(Simd);
+    I16x8ExtaddPairwiseI8x16S = "i16x8.extadd_pairwise_i8x16_s", 0xFD, 0x7C, None, "v:v", Some(Simd);
+    I16x8ExtaddPairwiseI8x16U = "i16x8.extadd_pairwise_i8x16_u", 0xFD, 0x7D, None, "v:v", Some(Simd);
+    I32x4ExtaddPairwiseI16x8S = "i32x4.extadd_pairwise_i16x8_s", 0xFD, 0x7E, None, "v:v", Some(Simd);
+    I32x4ExtaddPairwiseI16x8U = "i32x4.extadd_pairwise_i16x8_u", 0xFD, 0x7F, None, "v:v", Some(Simd);
+    I16x8Abs = "i16x8.abs", 0xFD, 0x80, None, "v:v", Some(Simd);
+    I16x8Neg = "i16x8.neg", 0xFD, 0x81, None, "v:v", Some(Simd);
+    I16x8Q15mulrSatS = "i16x8.q15mulr_sat_s", 0xFD, 0x82, None, "vv:v", Some(Simd);
+    I16x8AllTrue = "i16x8.all_true", 0xFD, 0x83, None, "v:i", Some(Simd);
+    I16x8Bitmask = "i16x8.bitmask", 0xFD, 0x84, None, "v:i", Some(Simd);
+    I16x8NarrowI32x4S = "i16x8.narrow_i32x4_s", 0xFD, 0x85, None, "vv:v", Some(Simd);
+    I16x8NarrowI32x4U = "i16x8.narrow_i32x4_u", 0xFD, 0x86, None, "vv:v", Some(Simd);
+    I16x8ExtendLowI8x16S = "i16x8.extend_low_i8x16_s", 0xFD, 0x87, None, "v:v", Some(Simd);
+    I16x8ExtendHighI8x16S = "i16x8.extend_high_i8x16_s", 0xFD, 0x88, None, "v:v", Some(Simd);
+    I16x8ExtendLowI8x16U = "i16x8.extend_low_i8x16_u", 0xFD, 0x89, None, "v:v", Some(Simd);
+    I16x8ExtendHighI8x16U = "i16x8.extend_high_i8x16_u", 0xFD, 0x8A, None, "v:v", Some(Simd);
+    I16x8Shl = "i16x8.shl", 0xFD, 0x8B, None, "vi:v", Some(Simd);
+    I16x8ShrS = "i16x8.shr_s", 0xFD, 0x8C, None, "vi:v", Some(Simd);
+    I16x8ShrU = "i16x8.shr_u", 0xFD, 0x8D, None, "vi:v", Some(Simd);
+    I16x8Add = "i16x8.add", 0xFD, 0x8E, None, "vv:v", Some(Simd);
+    I16x8AddSatS = "i16x8.add_sat_s", 0xFD, 0x8F, None, "vv:v", Some(Simd);
+    I16x8AddSatU = "i16x8.add_sat_u", 0xFD, 0x90, None, "vv:v", Some(Simd);
+    I16x8Sub = "i16x8.sub", 0xFD, 0x91, None, "vv:v", Some(Simd);
+    I16x8SubSatS = "i16x8.sub_sat_s", 0xFD, 0x92, None, "vv:v", Some(Simd);
+    I16x8SubSatU = "i16x8.sub_sat_u", 0xFD, 0x93, None, "vv:v", Some(Simd);
+    F64x2Nearest = "f64x2.nearest", 0xFD, 0x94, None, "v:v", Some(Simd);
+    I16x8Mul = "i16x8.mul", 0xFD, 0x95, None, "vv:v", Some(Simd);
+    I16x8MinS = "i16x8.min_s", 0xFD, 0x96, None, "vv:v", Some(Simd);
+    I16x8MinU = "i16x8.min_u", 0xFD, 0x97, None, "vv:v", Some(Simd);
+    I16x8MaxS = "i16x8.max_s", 0xFD, 0x98, None, "vv:v", Some(Simd);
+    I16x8MaxU = "i16x8.max_u", 0xFD, 0x99, None, "vv:v", Some(Simd);
+    I16x8AvgrU = "i16x8.avgr_u", 0xFD, 0x9B, None, "vv:v", Some(Simd);
+    I16x8ExtmulLowI8x16S = "i16x8.extmul_low_i8x16_s", 0xFD, 0x9C, None, "vv:v", Some(Simd);
+    I16x8ExtmulHighI8x16S = "i16x8.extmul_high_i8x16_s", 0xFD, 0x9D, None, "vv:v", Some(Simd);
+    I16x8ExtmulLowI8x16U = "i16x8.extmul_low_i8x16_u", 0xFD, 0x9E, None, "vv:v", Some(Simd);
+    I16x8ExtmulHighI8x16U = "i16x8.extmul_high_i8x16_u", 0xFD, 0x9F, None, "vv:v", Some(Simd);
+    I32x4Abs = "i32x4.abs", 0xFD, 0xA0, None, "v:v", Some(Simd);
+    I32x4Neg = "i32x4.neg", 0xFD, 0xA1, None, "v:v", Some(Simd);
+    I32x4AllTrue = "i32x4.all_true", 0xFD, 0xA3, None, "v:i", Some(Simd);
+    I32x4Bitmask = "i32x4.bitmask", 0xFD, 0xA4, None, "v:i", Some(Simd);
+    I32x4ExtendLowI16x8S = "i32x4.extend_low_i16x8_s", 0xFD, 0xA7, None, "v:v", Some(Simd);
+    I32x4ExtendHighI16x8S = "i32x4.extend_high_i16x8_s", 0xFD, 0xA8, None, "v:v", Some(Simd);
+    I32x4ExtendLowI16x8U = "i32x4.extend_low_i16x8_u", 0xFD, 0xA9, None, "v:v", Some(Simd);
+    I32x4ExtendHighI16x8U = "i32x4.extend_high_i16x8_u", 0xFD, 0xAA, None, "v:v", Some(Simd);
+    I32x4Shl = "i32x4.shl", 0xFD, 0xAB, None, "vi:v", Some(Simd);
+    I32x4ShrS = "i32x4.shr_s", 0xFD, 0xAC, None, "vi:v", Some(Simd);
+    I32x4ShrU = "i32x4.shr_u", 0xFD, 0xAD, None, "vi:v", Some(Simd);
+    I32x4Add = "i32x4.add", 0xFD, 0xAE, None, "vv:v", Some(Simd);
+    I32x4Sub = "i32x4.sub", 0xFD, 0xB1, None, "vv:v", Some(Simd);
+    I32x4Mul = "i32x4.mul", 0xFD, 0xB5, None, "vv:v", Some(Simd);
+    I32x4MinS = "i32x4.min_s", 0xFD, 0xB6, None, "vv:v", Some(Simd);
+    I32x4MinU = "i32x4.min_u", 0xFD, 0xB7, None, "vv:v", Some(Simd);
+    I32x4MaxS = "i32x4.max_s", 0xFD, 0xB8, None, "vv:v", Some(Simd);
+    I32x4MaxU = "i32x4.max_u", 0xFD, 0xB9, None, "vv:v", Some(Simd);
+    I32x4DotI16x8S = "i32x4.dot_i16x8_s", 0xFD, 0xBA, None, "vv:v", Some(Simd);
+    I32x4ExtmulLowI16x8S = "i32x4.extmul_low_i16x8_s", 0xFD, 0xBC, None, "vv:v", Some(Simd);
+    I32x4ExtmulHighI16x8S = "i32x4.extmul_high_i16x8_s", 0xFD, 0xBD, None, "vv:v", Some(Simd);
+    I32x4ExtmulLowI16x8U = "i32x4.extmul_low_i16x8_u", 0xFD, 0xBE, None, "vv:v", Some(Simd);
+    I32x4ExtmulHighI16x8U = "i32x4.extmul_high_i16x8_u", 0xFD, 0xBF, None, "vv:v", Some(Simd);
+    I64x2Abs = "i64x2.abs", 0xFD, 0xC0, None, "v:v", Some(Simd);
+    I64x2Neg = "i64x2.neg", 0xFD, 0xC1, None, "v:v", Some(Simd);
+    I64x2AllTrue = "i64x2.all_true", 0xFD, 0xC3, None, "v:i", Some(Simd);
+    I64x2Bitmask = "i64x2.bitmask", 0xFD, 0xC4, None, "v:i", Some(Simd);
+    I64x2ExtendLowI32x4S = "i64x2.extend_low_i32x4_s", 0xFD, 0xC7, None, "v:v", Some(Simd);
+    I64x2ExtendHighI32x4S = "i64x2.extend_high_i32x4_s", 0xFD, 0xC8, None, "v:v", Some(Simd);
+    I64x2ExtendLowI32x4U = "i64x2.extend_low_i32x4_u", 0xFD, 0xC9, None, "v:v", Some(Simd);
+    I64x2ExtendHighI32x4U = "i64x2.extend_high_i32x4_u", 0xFD, 0xCA, None, "v:v", Some(Simd);
+    I64x2Shl = "i64x2.shl", 0xFD, 0xCB, None, "vi:v", Some(Simd);
+    I64x2ShrS = "i64x2.shr_s", 0xFD, 0xCC, None, "vi:v", Some(Simd);
+    I64x2ShrU = "i64x2.shr_u", 0xFD, 0xCD, None, "vi:v", Some(Simd);
+    I64x2Add = "i64x2.add", 0xFD, 0xCE, None, "vv:v", Some(Simd);
+    I64x2Sub = "i64x2.sub", 0xFD, 0xD1, None, "vv:v", Some(Simd);
+    I64x2Mul = "i64x2.mul", 0xFD, 0xD5, None, "vv:v", Some(Simd);
+    I64x2Eq = "i64x2.eq", 0xFD, 0xD6, None, "vv:v", Some(Simd);
+    I64x2Ne = "i64x2.ne", 0xFD, 0xD7, None, "vv:v", Some(Simd);
+    I64x2LtS = "i64x2.lt_s", 0xFD, 0xD8, None, "vv:v", Some(Simd);
+    I64x2GtS = "i64x2.gt_s", 0xFD, 0xD9, None, "vv:v", Some(Simd);
+    I64x2LeS = "i64x2.le_s", 0xFD, 0xDA, None, "vv:v", Some(Simd);
+    I64x2GeS = "i64x2.ge_s", 0xFD, 0xDB, None, "vv:v", Some(Simd);
+    I64x2ExtmulLowI32x4S = "i64x2.extmul_low_i32x4_s", 0xFD, 0xDC, None, "vv:v", Some(Simd);
+    I64x2ExtmulHighI32x4S = "i64x2.extmul_high_i32x4_s", 0xFD, 0xDD, None, "vv:v", Some(Simd);
+    I64x2ExtmulLowI32x4U = "i64x2.extmul_low_i32x4_u", 0xFD, 0xDE, None, "vv:v", Some(Simd);
+    I64x2ExtmulHighI32x4U = "i64x2.extmul_high_i32x4_u", 0xFD, 0xDF, None, "vv:v", Some(Simd);
+    F32x4Abs = "f32x4.abs", 0xFD, 0xE0, None, "v:v", Some(Simd);
+    F32x4Neg = "f32x4.neg", 0xFD, 0xE1, None, "v:v", Some(Simd);
+    F32x4Sqrt = "f32x4.sqrt", 0xFD, 0xE3, None, "v:v", Some(Simd);
+    F32x4Add = "f32x4.add", 0xFD, 0xE4, None, "vv:v", Some(Simd);
+    F32x4Sub = "f32x4.sub", 0xFD, 0xE5, None, "vv:v", Some(Simd);
+    F32x4Mul = "f32x4.mul", 0xFD, 0xE6, None, "vv:v", Some(Simd);
+    F32x4Div = "f32x4.div", 0xFD, 0xE7, None, "vv:v", Some(Simd);
+    F32x4Min = "f32x4.min", 0xFD, 0xE8, None, "vv:v", Some(Simd);
+    F32x4Max = "f32x4.max", 0xFD, 0xE9, None, "vv:v", Some(Simd);
+    F32x4Pmin = "f32x4.pmin", 0xFD, 0xEA, None, "vv:v", Some(Simd);
+    F32x4Pmax = "f32x4.pmax", 0xFD, 0xEB, None, "vv:v", Some(Simd);
+    F64x2Abs = "f64x2.abs", 0xFD, 0xEC, None, "v:v", Some(Simd);
+    F64x2Neg = "f64x2.neg", 0xFD, 0xED, None, "v:v", Some(Simd);
+    F64x2Sqrt = "f64x2.sqrt", 0xFD, 0xEF, None, "v:v", Some(Simd);
+    F64x2Add = "f64x2.add", 0xFD, 0xF0, None, "vv:v", Some(Simd);
+    F64x2Sub = "f64x2.sub", 0xFD, 0xF1, None, "vv:v", Some(Simd);
+    F64x2Mul = "f64x2.mul", 0xFD, 0xF2, None, "vv:v", Some(Simd);
+    F64x2Div = "f64x2.div", 0xFD, 0xF3, None, "vv:v", Some(Simd);
+    F64x2Min = "f64x2.min", 0xFD, 0xF4, None, "vv:v", Some(Simd);
+    F64x2Max = "f64x2.max", 0xFD, 0xF5, None, "vv:v", Some(Simd);
+    F64x2Pmin = "f64x2.pmin", 0xFD, 0xF6, None, "vv:v", Some(Simd);
+    F64x2Pmax = "f64x2.pmax", 0xFD, 0xF7, None, "vv:v", Some(Simd);
+    I32x4TruncSatF32x4S = "i32x4.trunc_sat_f32x4_s", 0xFD, 0xF8, None, "v:v", Some(Simd);
+    I32x4TruncSatF32x4U = "i32x4.trunc_sat_f32x4_u", 0xFD, 0xF9, None, "v:v", Some(Simd);
+    F32x4ConvertI32x4S = "f32x4.convert_i32x4_s", 0xFD, 0xFA, None, "v:v", Some(Simd);
+    F32x4ConvertI32x4U = "f32x4.convert_i32x4_u", 0xFD, 0xFB, None, "v:v", Some(Simd);
+    I32x4TruncSatF64x2SZero = "i32x4.trunc_sat_f64x2_s_zero", 0xFD, 0xFC, None, "v:v", Some(Simd);
+    I32x4TruncSatF64x2UZero = "i32x4.trunc_sat_f64x2_u_zero", 0xFD, 0xFD, None, "v:v", Some(Simd);
+    F64x2ConvertLowI32x4S = "f64x2.convert_low_i32x4_s", 0xFD, 0xFE, None, "v:v", Some(Simd);
+    F64x2ConvertLowI32x4U = "f64x2.convert_low_i32x4_u", 0xFD, 0xFF, None, "v:v", Some(Simd);
+    MemoryAtomicNotify = "memory.atomic.notify", 0xFE, 0x00, MemArg, "ai:i", Some(Threads);
+    MemoryAtomicWait32 = "memory.atomic.wait32", 0xFE, 0x01, MemArg, "aiI:i", Some(Threads);
+    MemoryAtomicWait64 = "memory.atomic.wait64", 0xFE, 0x02, MemArg, "aII:i", Some(Threads);
+    AtomicFence = "atomic.fence", 0xFE, 0x03, AtomicFence, ":", Some(Threads);
+    I32AtomicLoad = "i32.atomic.load", 0xFE, 0x10, MemArg, "a:i", Some(Threads);
+    I64AtomicLoad = "i64.atomic.load", 0xFE, 0x11, MemArg, "a:I", Some(Threads);
+    I32AtomicLoad8U = "i32.atomic.load8_u", 0xFE, 0x12, MemArg, "a:i", Some(Threads);
+    I32AtomicLoad16U = "i32.atomic.load16_u", 0xFE, 0x13, MemArg, "a:i", Some(Threads);
+    I64AtomicLoad8U = "i64.atomic.load8_u", 0xFE, 0x14, MemArg, "a:I", Some(Threads);
+    I64AtomicLoad16U = "i64.atomic.load16_u", 0xFE, 0x15, MemArg, "a:I", Some(Threads);
+    I64AtomicLoad32U = "i64.atomic.load32_u", 0xFE, 0x16, MemArg, "a:I", Some(Threads);
+    I32AtomicStore = "i32.atomic.store", 0xFE, 0x17, MemArg, "ai:", Some(Threads);
+    I64AtomicStore = "i64.atomic.store", 0xFE, 0x18, MemArg, "aI:", Some(Threads);
+    I32AtomicStore8 = "i32.atomic.store8", 0xFE, 0x19, MemArg, "ai:", Some(Threads);
+    I32AtomicStore16 = "i32.atomic.store16", 0xFE, 0x1A, MemArg, "ai:", Some(Threads);
+    I64AtomicStore8 = "i64.atomic.store8", 0xFE, 0x1B, MemArg, "aI:", Some(Threads);
+    I64AtomicStore16 = "i64.atomic.store16", 0xFE, 0x1C, MemArg, "aI:", Some(Threads);
+    I64AtomicStore32 = "i64.atomic.store32", 0xFE, 0x1D, MemArg, "aI:", Some(Threads);
+    I32AtomicRmwAdd = "i32.atomic.rmw.add", 0xFE, 0x1E, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmwAdd = "i64.atomic.rmw.add", 0xFE, 0x1F, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmw8AddU = "i32.atomic.rmw8.add_u", 0xFE, 0x20, MemArg, "ai:i", Some(Threads);
+    I32AtomicRmw16AddU = "i32.atomic.rmw16.add_u", 0xFE, 0x21, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmw8AddU = "i64.atomic.rmw8.add_u", 0xFE, 0x22, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw16AddU = "i64.atomic.rmw16.add_u", 0xFE, 0x23, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw32AddU = "i64.atomic.rmw32.add_u", 0xFE, 0x24, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmwSub = "i32.atomic.rmw.sub", 0xFE, 0x25, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmwSub = "i64.atomic.rmw.sub", 0xFE, 0x26, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmw8SubU = "i32.atomic.rmw8.sub_u", 0xFE, 0x27, MemArg, "ai:i", Some(Threads);
+    I32AtomicRmw16SubU = "i32.atomic.rmw16.sub_u", 0xFE, 0x28, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmw8SubU = "i64.atomic.rmw8.sub_u", 0xFE, 0x29, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw16SubU = "i64.atomic.rmw16.sub_u", 0xFE, 0x2A, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw32SubU = "i64.atomic.rmw32.sub_u", 0xFE, 0x2B, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmwAnd = "i32.atomic.rmw.and", 0xFE, 0x2C, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmwAnd = "i64.atomic.rmw.and", 0xFE, 0x2D, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmw8AndU = "i32.atomic.rmw8.and_u", 0xFE, 0x2E, MemArg, "ai:i", Some(Threads);
+    I32AtomicRmw16AndU = "i32.atomic.rmw16.and_u", 0xFE, 0x2F, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmw8AndU = "i64.atomic.rmw8.and_u", 0xFE, 0x30, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw16AndU = "i64.atomic.rmw16.and_u", 0xFE, 0x31, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw32AndU = "i64.atomic.rmw32.and_u", 0xFE, 0x32, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmwOr = "i32.atomic.rmw.or", 0xFE, 0x33, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmwOr = "i64.atomic.rmw.or", 0xFE, 0x34, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmw8OrU = "i32.atomic.rmw8.or_u", 0xFE, 0x35, MemArg, "ai:i", Some(Threads);
+    I32AtomicRmw16OrU = "i32.atomic.rmw16.or_u", 0xFE, 0x36, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmw8OrU = "i64.atomic.rmw8.or_u", 0xFE, 0x37, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw16OrU = "i64.atomic.rmw16.or_u", 0xFE, 0x38, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw32OrU = "i64.atomic.rmw32.or_u", 0xFE, 0x39, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmwXor = "i32.atomic.rmw.xor", 0xFE, 0x3A, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmwXor = "i64.atomic.rmw.xor", 0xFE, 0x3B, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmw8XorU = "i32.atomic.rmw8.xor_u", 0xFE, 0x3C, MemArg, "ai:i", Some(Threads);
+    I32AtomicRmw16XorU = "i32.atomic.rmw16.xor_u", 0xFE, 0x3D, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmw8XorU = "i64.atomic.rmw8.xor_u", 0xFE, 0x3E, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw16XorU = "i64.atomic.rmw16.xor_u", 0xFE, 0x3F, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw32XorU = "i64.atomic.rmw32.xor_u", 0xFE, 0x40, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmwXchg = "i32.atomic.rmw.xchg", 0xFE, 0x41, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmwXchg = "i64.atomic.rmw.xchg", 0xFE, 0x42, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmw8XchgU = "i32.atomic.rmw8.xchg_u", 0xFE, 0x43, MemArg, "ai:i", Some(Threads);
+    I32AtomicRmw16XchgU = "i32.atomic.rmw16.xchg_u", 0xFE, 0x44, MemArg, "ai:i", Some(Threads);
+    I64AtomicRmw8XchgU = "i64.atomic.rmw8.xchg_u", 0xFE, 0x45, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw16XchgU = "i64.atomic.rmw16.xchg_u", 0xFE, 0x46, MemArg, "aI:I", Some(Threads);
+    I64AtomicRmw32XchgU = "i64.atomic.rmw32.xchg_u", 0xFE, 0x47, MemArg, "aI:I", Some(Threads);
+    I32AtomicRmwCmpxchg = "i32.atomic.rmw.cmpxchg", 0xFE, 0x48, MemArg, "aii:i", Some(Threads);
+    I64AtomicRmwCmpxchg = "i64.atomic.rmw.cmpxchg", 0xFE, 0x49, MemArg, "aII:I", Some(Threads);
+    I32AtomicRmw8CmpxchgU = "i32.atomic.rmw8.cmpxchg_u", 0xFE, 0x4A, MemArg, "aii:i", Some(Threads);
+    I32AtomicRmw16CmpxchgU = "i32.atomic.rmw16.cmpxchg_u", 0xFE, 0x4B, MemArg, "aii:i", Some(Threads);
+    I64AtomicRmw8CmpxchgU = "i64.atomic.rmw8.cmpxchg_u", 0xFE, 0x4C, MemArg, "aII:I", Some(Threads);
+    I64AtomicRmw16CmpxchgU = "i64.atomic.rmw16.cmpxchg_u", 0xFE, 0x4D, MemArg, "aII:I", Some(Threads);
+    I64AtomicRmw32CmpxchgU = "i64.atomic.rmw32.cmpxchg_u", 0xFE, 0x4E, MemArg, "aII:I", Some(Threads);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        for op in Op::ALL {
+            assert_eq!(Op::from_code(op.prefix(), op.code()), Some(*op));
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        assert_eq!(Op::from_code(None, 0x6A), Some(Op::I32Add));
+        assert_eq!(Op::from_code(Some(0xFC), 0x0A), Some(Op::MemoryCopy));
+        assert_eq!(Op::from_code(Some(0xFD), 0x9A), None);
+        assert_eq!(Op::from_code(None, 0x27), None);
+        assert_eq!(Op::I8x16ExtractLaneS.name(), "i8x16.extract_lane_s");
+        assert_eq!(Op::SelectTyped.name(), "select");
+        assert_eq!(Op::from_name("select"), Some(Op::Select));
+        assert_eq!(
+            Op::from_name("i64.atomic.rmw.add"),
+            Some(Op::I64AtomicRmwAdd)
+        );
+        assert_eq!(Op::from_name("i32.bogus"), None);
+    }
+
+    #[test]
+    fn arity() {
+        assert_eq!(Op::I32Add.arity(), Some((2, 1)));
+        assert_eq!(Op::I64Store32.arity(), Some((2, 0)));
+        assert_eq!(Op::V128Bitselect.arity(), Some((3, 1)));
+        assert_eq!(Op::Nop.arity(), Some((0, 0)));
+        assert_eq!(Op::Call.arity(), None);
+        assert_eq!(Op::I32Add.type_string().unwrap(), "[i32 i32] -> [i32]");
+        assert_eq!(Op::I64Store32.type_string().unwrap(), "[at i64] -> []");
+        assert_eq!(Op::Drop.type_string().unwrap(), "[t] -> []");
+        assert_eq!(Op::Call.type_string(), None);
+        assert_eq!(Op::I64Add128.arity(), Some((4, 2)));
+        assert_eq!(
+            Op::I64MulWideU.type_string().unwrap(),
+            "[i64 i64] -> [i64 i64]"
+        );
+    }
+
+    #[test]
+    fn proposals() {
+        assert_eq!(Op::I32Extend8S.proposal(), Some(SignExtension));
+        assert_eq!(Op::I32AtomicRmw8CmpxchgU.proposal(), Some(Threads));
+        assert_eq!(Op::I32Load.proposal(), None);
+        assert_eq!(Op::TryTable.proposal(), Some(ExceptionHandling));
+        assert_eq!(Op::Delegate.proposal(), Some(LegacyExceptionHandling));
+        assert_eq!(Op::I64Sub128.proposal(), Some(WideArithmetic));
+    }
+
+    #[test]
+    fn natural_alignments() {
+        for (op, align) in [
+            (Op::I32Load, 2),
+            (Op::I64Store, 3),
+            (Op::I32Load16S, 1),
+            (Op::V128Load, 4),
+            (Op::V128Load32x2S, 3),
+            (Op::V128Load8Splat, 0),
+            (Op::V128Store16Lane, 1),
+            (Op::I64AtomicRmw32CmpxchgU, 2),
+            (Op::MemoryAtomicNotify, 2),
+            (Op::MemoryAtomicWait64, 3),
+        ] {
+            assert_eq!(op.natural_alignment(), Some(align), "{}", op);
+        }
+        assert_eq!(Op::I32Add.natural_alignment(), None);
+    }
+}
@@ -0,0 +1,1769 @@
+use crate::ast::*;
+use crate::opcodes::{ImmediateKind, Op};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+/// How instructions in function bodies are laid out.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Style {
+    /// One instruction per line, with `block`/`end` nesting shown by indentation.
+    Flat,
+    /// S-expressions, with each instruction's operands nested inside it wherever the
+    /// operand count is known, e.g. `(i32.add (local.get 0) (i32.const 1))`.
+    Folded,
+}
+
+/// A module being printed as text - see [`Module::print`].
+pub struct Printer<'a> {
+    module: &'a Module,
+    style: Style,
+}
+
+impl Module {
+    /// Prints the module in the text format, using its `id`s as names wherever they're
+    /// usable and numeric indices everywhere else, e.g. `module.print(Style::Folded).to_string()`.
+    pub fn print(&self, style: Style) -> Printer<'_> {
+        Printer {
+            module: self,
+            style,
+        }
+    }
+}
+
+/// Prints the module in the flat style.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.print(Style::Flat).fmt(f)
+    }
+}
+
+impl fmt::Display for Printer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Names::new(self.module);
+        Writer {
+            module: self.module,
+            style: self.style,
+            names: &names,
+            locals: BTreeMap::new(),
+            branch_hints: None,
+            out: f,
+        }
+        .module()
+    }
+}
+
+/// The ids that can be printed for each index space. An id is left out (so that the index
+/// is printed instead) if it isn't valid in the text format or if an earlier item in the same
+/// space already has it, since either would make the output unparseable.
+struct Names<'a> {
+    types: Vec<Option<&'a str>>,
+    funcs: Vec<Option<&'a str>>,
+    tables: Vec<Option<&'a str>>,
+    memories: Vec<Option<&'a str>>,
+    tags: Vec<Option<&'a str>>,
+    globals: Vec<Option<&'a str>>,
+    elems: Vec<Option<&'a str>>,
+    datas: Vec<Option<&'a str>>,
+}
+
+impl<'a> Names<'a> {
+    fn new(module: &'a Module) -> Names<'a> {
+        let imported = |kind: fn(&ImportKind) -> bool| {
+            module
+                .imports
+                .iter()
+                .filter(move |i| kind(&i.kind))
+                .map(|i| i.id.as_deref())
+        };
+        Names {
+            types: space(module.types.iter().map(|t| t.id.as_deref())),
+            funcs: space(
+                imported(|k| matches!(k, ImportKind::Func(_)))
+                    .chain(module.funcs.iter().map(|f| f.id.as_deref())),
+            ),
+            tables: space(
+                imported(|k| matches!(k, ImportKind::Table(_)))
+                    .chain(module.tables.iter().map(|t| t.id.as_deref())),
+            ),
+            memories: space(
+                imported(|k| matches!(k, ImportKind::Memory(_)))
+                    .chain(module.memories.iter().map(|m| m.id.as_deref())),
+            ),
+            tags: space(
+                imported(|k| matches!(k, ImportKind::Tag(_)))
+                    .chain(module.tags.iter().map(|t| t.id.as_deref())),
+            ),
+            globals: space(
+                imported(|k| matches!(k, ImportKind::Global(_)))
+                    .chain(module.globals.iter().map(|g| g.id.as_deref())),
+            ),
+            elems: space(module.elems.iter().map(|e| e.id.as_deref())),
+            datas: space(module.datas.iter().map(|d| d.id.as_deref())),
+        }
+    }
+}
+
+fn space<'a>(ids: impl Iterator<Item = Option<&'a str>>) -> Vec<Option<&'a str>> {
+    let mut seen = BTreeSet::new();
+    ids.map(|id| id.filter(|id| is_id(id) && seen.insert(*id)))
+        .collect()
+}
+
+/// Whether `$` followed by `id` lexes as a single identifier.
+fn is_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c))
+}
+
+/// An instruction sequence regrouped into s-expressions. Unfolding one of these always gives
+/// back the same sequence, so the grouping only has to look nice, not be semantically aware.
+enum Expr {
+    Plain {
+        head: String,
+        operands: Vec<Expr>,
+        /// How many values this leaves on the stack, if that's known.
+        results: Option<usize>,
+    },
+    Block {
+        head: String,
+        body: Vec<Expr>,
+        results: Option<usize>,
+    },
+    If {
+        head: String,
+        condition: Vec<Expr>,
+        then: Vec<Expr>,
+        otherwise: Option<Vec<Expr>>,
+        results: Option<usize>,
+    },
+    /// A legacy `try`, whose handlers are each a `catch` or `catch_all` and its body, or a
+    /// `delegate` with an empty one.
+    Try {
+        head: String,
+        body: Vec<Expr>,
+        handlers: Vec<(String, Vec<Expr>)>,
+        results: Option<usize>,
+    },
+    /// A `br_if` or `if` with a branch hint, which goes just before it.
+    Hinted { taken: bool, expr: Box<Expr> },
+}
+
+impl Expr {
+    fn results(&self) -> Option<usize> {
+        match self {
+            Expr::Plain { results, .. }
+            | Expr::Block { results, .. }
+            | Expr::If { results, .. }
+            | Expr::Try { results, .. } => *results,
+            Expr::Hinted { expr, .. } => expr.results(),
+        }
+    }
+}
+
+struct Writer<'a, 'b, 'f> {
+    module: &'a Module,
+    style: Style,
+    names: &'b Names<'a>,
+    /// The names of the current function's locals.
+    locals: BTreeMap<u32, &'a str>,
+    /// The current function's branch hints, while its body is being written.
+    branch_hints: Option<&'a BTreeMap<u32, bool>>,
+    out: &'b mut fmt::Formatter<'f>,
+}
+
+impl<'a> Writer<'a, '_, '_> {
+    fn module(&mut self) -> fmt::Result {
+        let module = self.module;
+        self.out.write_str("(module")?;
+        if let Some(id) = module.id.as_deref().filter(|id| is_id(id)) {
+            write!(self.out, " ${}", id)?;
+        }
+        let mut rec_groups = module.rec_groups.iter().peekable();
+        let mut group_end = None;
+        for (i, ty) in module.types.iter().enumerate() {
+            let index = i as u32;
+            while rec_groups
+                .next_if(|g| g.is_empty() && g.start <= index)
+                .is_some()
+            {
+                self.out.write_str("\n  (rec)")?;
+            }
+            if let Some(group) = rec_groups.next_if(|g| g.start == index) {
+                self.out.write_str("\n  (rec")?;
+                group_end = Some(group.end);
+            }
+            self.out.write_str(match group_end {
+                Some(_) => "\n    ",
+                None => "\n  ",
+            })?;
+            self.type_def(ty, i)?;
+            if group_end == Some(index + 1) {
+                self.out.write_char(')')?;
+                group_end = None;
+            }
+        }
+        for _ in rec_groups {
+            self.out.write_str("\n  (rec)")?;
+        }
+        let mut counts = [0; 5];
+        for import in &module.imports {
+            self.out.write_str("\n  (import ")?;
+            self.string(import.module.as_bytes())?;
+            self.out.write_char(' ')?;
+            self.string(import.name.as_bytes())?;
+            match &import.kind {
+                ImportKind::Func(ty) => {
+                    self.out.write_str(" (func")?;
+                    self.definition(&self.names.funcs, counts[0])?;
+                    self.type_use(*ty)?;
+                    counts[0] += 1;
+                }
+                ImportKind::Table(ty) => {
+                    self.out.write_str(" (table")?;
+                    self.definition(&self.names.tables, counts[1])?;
+                    self.table_type(ty)?;
+                    counts[1] += 1;
+                }
+                ImportKind::Memory(ty) => {
+                    self.out.write_str(" (memory")?;
+                    self.definition(&self.names.memories, counts[2])?;
+                    self.memory_type(ty)?;
+                    counts[2] += 1;
+                }
+                ImportKind::Global(ty) => {
+                    self.out.write_str(" (global")?;
+                    self.definition(&self.names.globals, counts[3])?;
+                    self.global_type(ty)?;
+                    counts[3] += 1;
+                }
+                ImportKind::Tag(ty) => {
+                    self.out.write_str(" (tag")?;
+                    self.definition(&self.names.tags, counts[4])?;
+                    self.type_use(*ty)?;
+                    counts[4] += 1;
+                }
+            }
+            self.out.write_str("))")?;
+        }
+        for (i, func) in module.funcs.iter().enumerate() {
+            self.func(func, counts[0] + i)?;
+        }
+        for (i, table) in module.tables.iter().enumerate() {
+            self.out.write_str("\n  (table")?;
+            self.definition(&self.names.tables, counts[1] + i)?;
+            self.table_type(&table.ty)?;
+            self.out.write_char(')')?;
+        }
+        for (i, memory) in module.memories.iter().enumerate() {
+            self.out.write_str("\n  (memory")?;
+            self.definition(&self.names.memories, counts[2] + i)?;
+            self.memory_type(&memory.ty)?;
+            self.out.write_char(')')?;
+        }
+        for (i, tag) in module.tags.iter().enumerate() {
+            self.out.write_str("\n  (tag")?;
+            self.definition(&self.names.tags, counts[4] + i)?;
+            self.type_use(tag.type_index)?;
+            self.out.write_char(')')?;
+        }
+        for (i, global) in module.globals.iter().enumerate() {
+            self.out.write_str("\n  (global")?;
+            self.definition(&self.names.globals, counts[3] + i)?;
+            self.global_type(&global.ty)?;
+            self.out.write_char(' ')?;
+            self.const_expr(&global.init)?;
+            self.out.write_char(')')?;
+        }
+        for export in &module.exports {
+            self.out.write_str("\n  (export ")?;
+            self.string(export.name.as_bytes())?;
+            let (keyword, space) = match export.kind {
+                ExportKind::Func => ("func", &self.names.funcs),
+                ExportKind::Table => ("table", &self.names.tables),
+                ExportKind::Memory => ("memory", &self.names.memories),
+                ExportKind::Global => ("global", &self.names.globals),
+                ExportKind::Tag => ("tag", &self.names.tags),
+            };
+            write!(self.out, " ({} ", keyword)?;
+            self.index(space, export.index)?;
+            self.out.write_str("))")?;
+        }
+        if let Some(start) = module.start {
+            self.out.write_str("\n  (start ")?;
+            self.index(&self.names.funcs, start)?;
+            self.out.write_char(')')?;
+        }
+        for (i, elem) in module.elems.iter().enumerate() {
+            self.elem(elem, i)?;
+        }
+        for (i, data) in module.datas.iter().enumerate() {
+            self.out.write_str("\n  (data")?;
+            self.definition(&self.names.datas, i)?;
+            if let DataMode::Active { memory, offset } = &data.mode {
+                if *memory != 0 {
+                    self.out.write_str(" (memory ")?;
+                    self.index(&self.names.memories, *memory)?;
+                    self.out.write_char(')')?;
+                }
+                self.out.write_char(' ')?;
+                self.offset(offset)?;
+            }
+            self.out.write_char(' ')?;
+            self.string(&data.bytes)?;
+            self.out.write_char(')')?;
+        }
+        for custom in &module.customs {
+            self.out.write_str("\n  (@custom ")?;
+            self.string(custom.name.as_bytes())?;
+            match section_name(custom.after) {
+                Some(section) => write!(self.out, " (after {}) ", section)?,
+                None => self.out.write_str(" (before first) ")?,
+            }
+            self.string(&custom.data)?;
+            self.out.write_char(')')?;
+        }
+        self.out.write_str(")\n")
+    }
+
+    fn type_def(&mut self, ty: &TypeDef, index: usize) -> fmt::Result {
+        self.out.write_str("(type")?;
+        self.definition(&self.names.types, index)?;
+        let sub = !ty.is_final || !ty.supertypes.is_empty();
+        if sub {
+            self.out.write_str(" (sub")?;
+            if ty.is_final {
+                self.out.write_str(" final")?;
+            }
+            for supertype in &ty.supertypes {
+                self.out.write_char(' ')?;
+                self.index(&self.names.types, *supertype)?;
+            }
+        }
+        match &ty.composite {
+            CompositeType::Func(func) => {
+                self.out.write_str(" (func")?;
+                self.params(&func.params, false)?;
+                self.results(&func.results)?;
+            }
+            CompositeType::Struct(fields) => {
+                self.out.write_str(" (struct")?;
+                for field in fields {
+                    self.out.write_str(" (field ")?;
+                    self.field_type(field)?;
+                    self.out.write_char(')')?;
+                }
+            }
+            CompositeType::Array(field) => {
+                self.out.write_str(" (array ")?;
+                self.field_type(field)?;
+            }
+        }
+        if sub {
+            self.out.write_char(')')?;
+        }
+        self.out.write_str("))")
+    }
+
+    fn field_type(&mut self, field: &FieldType) -> fmt::Result {
+        let storage = match field.storage {
+            StorageType::Val(ty) => val_type(ty, &self.names.types),
+            StorageType::I8 => String::from("i8"),
+            StorageType::I16 => String::from("i16"),
+        };
+        match field.mutable {
+            true => write!(self.out, "(mut {})", storage),
+            false => self.out.write_str(&storage),
+        }
+    }
+
+    fn func(&mut self, func: &'a Func, index: usize) -> fmt::Result {
+        let module = self.module;
+        self.locals = BTreeMap::new();
+        let mut seen = BTreeSet::new();
+        for (i, id) in &func.local_ids {
+            if is_id(id) && seen.insert(id.as_str()) {
+                self.locals.insert(*i, id.as_str());
+            }
+        }
+
+        self.out.write_str("\n  (func")?;
+        self.definition(&self.names.funcs, index)?;
+        self.type_use(func.type_index)?;
+        let mut next = match module
+            .types
+            .get(func.type_index as usize)
+            .and_then(TypeDef::func_type)
+        {
+            Some(ty) => ty.params.len() as u32,
+            None => 0,
+        };
+        let mut first = true;
+        for (count, ty) in &func.locals {
+            // a run of locals only gets split up if some of them have names
+            let named = self.locals.range(next..next.saturating_add(*count)).count() > 0;
+            if named {
+                for i in next..next.saturating_add(*count) {
+                    self.out.write_str(if first { "\n    " } else { " " })?;
+                    first = false;
+                    self.out.write_str("(local")?;
+                    if let Some(id) = self.locals.get(&i) {
+                        write!(self.out, " ${}", id)?;
+                    }
+                    write!(self.out, " {})", val_type(*ty, &self.names.types))?;
+                }
+            } else if *count > 0 {
+                self.out.write_str(if first { "\n    " } else { " " })?;
+                first = false;
+                self.out.write_str("(local")?;
+                for _ in 0..*count {
+                    write!(self.out, " {}", val_type(*ty, &self.names.types))?;
+                }
+                self.out.write_char(')')?;
+            }
+            next = next.saturating_add(*count);
+        }
+        self.branch_hints = Some(&func.branch_hints);
+        match self.style {
+            Style::Flat => self.flat(&func.body, 2)?,
+            Style::Folded => {
+                for expr in self.fold(&func.body) {
+                    self.folded(&expr, 2)?;
+                }
+            }
+        }
+        self.branch_hints = None;
+        self.out.write_char(')')
+    }
+
+    fn elem(&mut self, elem: &Elem, index: usize) -> fmt::Result {
+        self.out.write_str("\n  (elem")?;
+        self.definition(&self.names.elems, index)?;
+        match &elem.mode {
+            ElemMode::Passive => {}
+            ElemMode::Declarative => self.out.write_str(" declare")?,
+            ElemMode::Active { table, offset } => {
+                if *table != 0 {
+                    self.out.write_str(" (table ")?;
+                    self.index(&self.names.tables, *table)?;
+                    self.out.write_char(')')?;
+                }
+                self.out.write_char(' ')?;
+                self.offset(offset)?;
+            }
+        }
+        match &elem.items {
+            ElemItems::Functions(funcs) => {
+                self.out.write_str(" func")?;
+                for func in funcs {
+                    self.out.write_char(' ')?;
+                    self.index(&self.names.funcs, *func)?;
+                }
+            }
+            ElemItems::Expressions(exprs) => {
+                write!(self.out, " {}", val_type(elem.element, &self.names.types))?;
+                for expr in exprs {
+                    let folded = self.fold(expr);
+                    if let [expr] = &folded[..] {
+                        self.out.write_char(' ')?;
+                        self.inline(expr)?;
+                    } else {
+                        self.out.write_str(" (item")?;
+                        for expr in &folded {
+                            self.out.write_char(' ')?;
+                            self.inline(expr)?;
+                        }
+                        self.out.write_char(')')?;
+                    }
+                }
+            }
+        }
+        self.out.write_char(')')
+    }
+
+    /// Writes a segment's offset, which can be abbreviated to the expression if it's a single
+    /// (folded) instruction.
+    fn offset(&mut self, offset: &[Instruction]) -> fmt::Result {
+        let folded = self.fold(offset);
+        if let [expr] = &folded[..] {
+            return self.inline(expr);
+        }
+        self.out.write_str("(offset")?;
+        for expr in &folded {
+            self.out.write_char(' ')?;
+            self.inline(expr)?;
+        }
+        self.out.write_char(')')
+    }
+
+    fn const_expr(&mut self, expr: &[Instruction]) -> fmt::Result {
+        let folded = self.fold(expr);
+        for (i, expr) in folded.iter().enumerate() {
+            if i > 0 {
+                self.out.write_char(' ')?;
+            }
+            self.inline(expr)?;
+        }
+        Ok(())
+    }
+
+    /// Writes ` $id`, or a `(;index;)` comment if there isn't a usable id.
+    fn definition(&mut self, space: &[Option<&str>], index: usize) -> fmt::Result {
+        match space.get(index).copied().flatten() {
+            Some(id) => write!(self.out, " ${}", id),
+            None => write!(self.out, " (;{};)", index),
+        }
+    }
+
+    fn index(&mut self, space: &[Option<&str>], index: u32) -> fmt::Result {
+        match space.get(index as usize).copied().flatten() {
+            Some(id) => write!(self.out, "${}", id),
+            None => write!(self.out, "{}", index),
+        }
+    }
+
+    fn type_use(&mut self, index: u32) -> fmt::Result {
+        self.out.write_str(" (type ")?;
+        self.index(&self.names.types, index)?;
+        self.out.write_char(')')?;
+        let module = self.module;
+        if let Some(ty) = module
+            .types
+            .get(index as usize)
+            .and_then(TypeDef::func_type)
+        {
+            self.params(&ty.params, true)?;
+            self.results(&ty.results)?;
+        }
+        Ok(())
+    }
+
+    /// Writes `(param ...)`s, with the named ones split out if `names` is set.
+    fn params(&mut self, params: &[ValType], names: bool) -> fmt::Result {
+        let mut open = false;
+        for (i, ty) in params.iter().enumerate() {
+            let id = match names {
+                true => self.locals.get(&(i as u32)).copied(),
+                false => None,
+            };
+            if let Some(id) = id {
+                if open {
+                    self.out.write_char(')')?;
+                    open = false;
+                }
+                write!(
+                    self.out,
+                    " (param ${} {})",
+                    id,
+                    val_type(*ty, &self.names.types)
+                )?;
+            } else {
+                if !open {
+                    self.out.write_str(" (param")?;
+                    open = true;
+                }
+                write!(self.out, " {}", val_type(*ty, &self.names.types))?;
+            }
+        }
+        if open {
+            self.out.write_char(')')?;
+        }
+        Ok(())
+    }
+
+    fn results(&mut self, results: &[ValType]) -> fmt::Result {
+        if !results.is_empty() {
+            self.out.write_str(" (result")?;
+            for ty in results {
+                write!(self.out, " {}", val_type(*ty, &self.names.types))?;
+            }
+            self.out.write_char(')')?;
+        }
+        Ok(())
+    }
+
+    fn limits(&mut self, limits: &Limits) -> fmt::Result {
+        write!(self.out, " {}", limits.min)?;
+        if let Some(max) = limits.max {
+            write!(self.out, " {}", max)?;
+        }
+        Ok(())
+    }
+
+    fn table_type(&mut self, ty: &TableType) -> fmt::Result {
+        if ty.table64 {
+            self.out.write_str(" i64")?;
+        }
+        self.limits(&ty.limits)?;
+        write!(self.out, " {}", val_type(ty.element, &self.names.types))
+    }
+
+    fn memory_type(&mut self, ty: &MemoryType) -> fmt::Result {
+        if ty.memory64 {
+            self.out.write_str(" i64")?;
+        }
+        self.limits(&ty.limits)?;
+        if ty.shared {
+            self.out.write_str(" shared")?;
+        }
+        if let Some(log2) = ty.page_size_log2 {
+            write!(self.out, " (pagesize {})", 1u64 << log2)?;
+        }
+        Ok(())
+    }
+
+    fn global_type(&mut self, ty: &GlobalType) -> fmt::Result {
+        match ty.mutable {
+            true => write!(self.out, " (mut {})", val_type(ty.value, &self.names.types)),
+            false => write!(self.out, " {}", val_type(ty.value, &self.names.types)),
+        }
+    }
+
+    /// Writes a string literal, escaping anything that isn't printable ASCII (or is a quote
+    /// or backslash) as hex bytes.
+    fn string(&mut self, bytes: &[u8]) -> fmt::Result {
+        self.out.write_char('"')?;
+        for byte in bytes {
+            match byte {
+                b'"' | b'\\' => write!(self.out, "\\{}", *byte as char)?,
+                0x20..=0x7e => self.out.write_char(*byte as char)?,
+                _ => write!(self.out, "\\{:02x}", byte)?,
+            }
+        }
+        self.out.write_char('"')
+    }
+
+    fn flat(&mut self, body: &[Instruction], mut depth: usize) -> fmt::Result {
+        for (index, instruction) in body.iter().enumerate() {
+            if matches!(
+                instruction.op,
+                Op::End | Op::Else | Op::Catch | Op::CatchAll | Op::Delegate
+            ) {
+                depth = depth.saturating_sub(1).max(2);
+            }
+            self.out.write_char('\n')?;
+            for _ in 0..depth {
+                self.out.write_str("  ")?;
+            }
+            if let Some(taken) = self.branch_hint(index) {
+                write!(self.out, "{} ", hint_annotation(taken))?;
+            }
+            let text = self.instruction(instruction);
+            self.out.write_str(&text)?;
+            if matches!(
+                instruction.op,
+                Op::Block
+                    | Op::Loop
+                    | Op::If
+                    | Op::Else
+                    | Op::Try
+                    | Op::TryTable
+                    | Op::Catch
+                    | Op::CatchAll
+            ) {
+                depth += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes an expression over several lines, one operand (or block instruction) per line.
+    fn folded(&mut self, expr: &Expr, depth: usize) -> fmt::Result {
+        self.out.write_char('\n')?;
+        for _ in 0..depth {
+            self.out.write_str("  ")?;
+        }
+        match expr {
+            Expr::Plain { head, operands, .. } => {
+                write!(self.out, "({}", head)?;
+                for operand in operands {
+                    self.folded(operand, depth + 1)?;
+                }
+            }
+            Expr::Block { head, body, .. } => {
+                write!(self.out, "({}", head)?;
+                for expr in body {
+                    self.folded(expr, depth + 1)?;
+                }
+            }
+            Expr::If {
+                head,
+                condition,
+                then,
+                otherwise,
+                ..
+            } => {
+                write!(self.out, "({}", head)?;
+                for expr in condition {
+                    self.folded(expr, depth + 1)?;
+                }
+                self.out.write_char('\n')?;
+                for _ in 0..=depth {
+                    self.out.write_str("  ")?;
+                }
+                self.out.write_str("(then")?;
+                for expr in then {
+                    self.folded(expr, depth + 2)?;
+                }
+                self.out.write_char(')')?;
+                if let Some(otherwise) = otherwise {
+                    self.out.write_char('\n')?;
+                    for _ in 0..=depth {
+                        self.out.write_str("  ")?;
+                    }
+                    self.out.write_str("(else")?;
+                    for expr in otherwise {
+                        self.folded(expr, depth + 2)?;
+                    }
+                    self.out.write_char(')')?;
+                }
+            }
+            Expr::Try {
+                head,
+                body,
+                handlers,
+                ..
+            } => {
+                write!(self.out, "({}", head)?;
+                self.out.write_char('\n')?;
+                for _ in 0..=depth {
+                    self.out.write_str("  ")?;
+                }
+                self.out.write_str("(do")?;
+                for expr in body {
+                    self.folded(expr, depth + 2)?;
+                }
+                self.out.write_char(')')?;
+                for (head, body) in handlers {
+                    self.out.write_char('\n')?;
+                    for _ in 0..=depth {
+                        self.out.write_str("  ")?;
+                    }
+                    write!(self.out, "({}", head)?;
+                    for expr in body {
+                        self.folded(expr, depth + 2)?;
+                    }
+                    self.out.write_char(')')?;
+                }
+            }
+            Expr::Hinted { taken, expr } => {
+                self.out.write_str(hint_annotation(*taken))?;
+                return self.folded(expr, depth);
+            }
+        }
+        self.out.write_char(')')
+    }
+
+    /// Writes an expression on a single line, for constant expressions.
+    fn inline(&mut self, expr: &Expr) -> fmt::Result {
+        let (head, children): (_, &[Expr]) = match expr {
+            Expr::Plain { head, operands, .. } => (head, operands),
+            Expr::Block { head, body, .. } => (head, body),
+            // constant expressions can't have `if`s or `try`s in them, but printing them
+            // unfolded is still better than panicking
+            Expr::If { .. } | Expr::Try { .. } | Expr::Hinted { .. } => {
+                return self.folded(expr, 0)
+            }
+        };
+        write!(self.out, "({}", head)?;
+        for child in children {
+            self.out.write_char(' ')?;
+            self.inline(child)?;
+        }
+        self.out.write_char(')')
+    }
+
+    fn fold(&self, body: &[Instruction]) -> Vec<Expr> {
+        let mut pos = 0;
+        let mut exprs = Vec::new();
+        while pos < body.len() {
+            let (mut more, end) = self.fold_block(body, &mut pos);
+            exprs.append(&mut more);
+            // a stray `end` or `else` - the decoder never produces these, but an AST that's
+            // been built by hand might
+            if end.is_some() {
+                exprs.push(Expr::Plain {
+                    head: self.instruction(&body[pos - 1]),
+                    operands: Vec::new(),
+                    results: None,
+                });
+            }
+        }
+        exprs
+    }
+
+    /// Folds instructions up to the `end`, `else`, `catch`, `catch_all` or `delegate` of the
+    /// current block (which is consumed and returned too).
+    fn fold_block(&self, body: &[Instruction], pos: &mut usize) -> (Vec<Expr>, Option<Op>) {
+        let mut exprs: Vec<Expr> = Vec::new();
+        while let Some(instruction) = body.get(*pos) {
+            let index = *pos;
+            *pos += 1;
+            let op = instruction.op;
+            match op {
+                Op::End | Op::Else | Op::Catch | Op::CatchAll | Op::Delegate => {
+                    return (exprs, Some(op))
+                }
+                Op::Block | Op::Loop | Op::TryTable => {
+                    let (inner, _) = self.fold_block(body, pos);
+                    exprs.push(Expr::Block {
+                        head: self.instruction(instruction),
+                        body: inner,
+                        results: self.block_results(&instruction.immediate),
+                    });
+                }
+                Op::If => {
+                    let condition = match exprs.last().map(Expr::results) {
+                        Some(Some(1)) => exprs.pop().into_iter().collect(),
+                        _ => Vec::new(),
+                    };
+                    let (then, end) = self.fold_block(body, pos);
+                    let otherwise = match end {
+                        Some(Op::Else) => Some(self.fold_block(body, pos).0),
+                        _ => None,
+                    };
+                    let expr = Expr::If {
+                        head: self.instruction(instruction),
+                        condition,
+                        then,
+                        otherwise,
+                        results: self.block_results(&instruction.immediate),
+                    };
+                    exprs.push(self.hinted(index, expr));
+                }
+                Op::Try => {
+                    let (inner, mut end) = self.fold_block(body, pos);
+                    let mut handlers = Vec::new();
+                    while let Some(op @ (Op::Catch | Op::CatchAll | Op::Delegate)) = end {
+                        let head = self.instruction(&body[*pos - 1]);
+                        if op == Op::Delegate {
+                            handlers.push((head, Vec::new()));
+                            break;
+                        }
+                        let (handler, next) = self.fold_block(body, pos);
+                        handlers.push((head, handler));
+                        end = next;
+                    }
+                    exprs.push(Expr::Try {
+                        head: self.instruction(instruction),
+                        body: inner,
+                        handlers,
+                        results: self.block_results(&instruction.immediate),
+                    });
+                }
+                _ => {
+                    let arity = self.arity(instruction);
+                    let operands = match arity {
+                        Some((params, _))
+                            if params <= exprs.len()
+                                && exprs[exprs.len() - params..]
+                                    .iter()
+                                    .all(|e| e.results() == Some(1)) =>
+                        {
+                            exprs.split_off(exprs.len() - params)
+                        }
+                        _ => Vec::new(),
+                    };
+                    let expr = Expr::Plain {
+                        head: self.instruction(instruction),
+                        operands,
+                        results: arity.map(|(_, results)| results),
+                    };
+                    exprs.push(self.hinted(index, expr));
+                }
+            }
+        }
+        (exprs, None)
+    }
+
+    fn branch_hint(&self, index: usize) -> Option<bool> {
+        self.branch_hints?.get(&(index as u32)).copied()
+    }
+
+    /// Wraps the expression for the instruction at `index` in its branch hint, if it has one.
+    fn hinted(&self, index: usize, expr: Expr) -> Expr {
+        match self.branch_hint(index) {
+            Some(taken) => Expr::Hinted {
+                taken,
+                expr: Box::new(expr),
+            },
+            None => expr,
+        }
+    }
+
+    /// The number of values a block leaves behind, if it doesn't take any parameters.
+    fn block_results(&self, immediate: &Immediate) -> Option<usize> {
+        let block_type = match immediate {
+            Immediate::BlockType(block_type) => block_type,
+            Immediate::TryTable { block_type, .. } => block_type,
+            _ => return None,
+        };
+        match block_type {
+            BlockType::Empty => Some(0),
+            BlockType::Value(_) => Some(1),
+            BlockType::Type(index) => {
+                let ty = self.module.types.get(*index as usize)?.func_type()?;
+                ty.params.is_empty().then_some(ty.results.len())
+            }
+        }
+    }
+
+    /// Like [`Op::arity`], but also working out calls from the module's types.
+    fn arity(&self, instruction: &Instruction) -> Option<(usize, usize)> {
+        let module = self.module;
+        let func_type = |index: u32| module.types.get(index as usize)?.func_type();
+        match (instruction.op, &instruction.immediate) {
+            (Op::Call, Immediate::Index(func)) => {
+                let ty = module.func_type(*func)?;
+                Some((ty.params.len(), ty.results.len()))
+            }
+            (Op::CallIndirect, Immediate::Indices(ty, _)) => {
+                let ty = func_type(*ty)?;
+                Some((ty.params.len() + 1, ty.results.len()))
+            }
+            (op, _) => op.arity(),
+        }
+    }
+
+    /// An instruction and its immediates, e.g. `i32.load offset=8`.
+    fn instruction(&self, instruction: &Instruction) -> String {
+        let mut text = String::from(instruction.op.name());
+        // writing to a `String` can't fail
+        let _ = self.immediates(instruction, &mut text);
+        text
+    }
+
+    fn immediates(&self, instruction: &Instruction, text: &mut String) -> fmt::Result {
+        let names = self.names;
+        let index = |text: &mut String, space: &[Option<&str>], index: u32| match space
+            .get(index as usize)
+            .copied()
+            .flatten()
+        {
+            Some(id) => write!(text, " ${}", id),
+            None => write!(text, " {}", index),
+        };
+        let op = instruction.op;
+        match (op.immediate(), &instruction.immediate) {
+            (_, Immediate::None) => {}
+            (_, Immediate::BlockType(BlockType::Empty)) => {}
+            (_, Immediate::BlockType(BlockType::Value(ty))) => {
+                write!(text, " (result {})", val_type(*ty, &self.names.types))?
+            }
+            (_, Immediate::BlockType(BlockType::Type(ty))) => {
+                text.push_str(" (type");
+                index(text, &names.types, *ty)?;
+                text.push(')');
+            }
+            (ImmediateKind::Local, Immediate::Index(i)) => match self.locals.get(i) {
+                Some(id) => write!(text, " ${}", id)?,
+                None => write!(text, " {}", i)?,
+            },
+            (ImmediateKind::Func, Immediate::Index(i)) => index(text, &names.funcs, *i)?,
+            (ImmediateKind::Global, Immediate::Index(i)) => index(text, &names.globals, *i)?,
+            (ImmediateKind::Table, Immediate::Index(i)) => index(text, &names.tables, *i)?,
+            (ImmediateKind::Memory, Immediate::Index(i)) => {
+                if *i != 0 {
+                    index(text, &names.memories, *i)?
+                }
+            }
+            (ImmediateKind::Data, Immediate::Index(i)) => index(text, &names.datas, *i)?,
+            (ImmediateKind::Elem, Immediate::Index(i)) => index(text, &names.elems, *i)?,
+            (ImmediateKind::Tag, Immediate::Index(i)) => index(text, &names.tags, *i)?,
+            (ImmediateKind::Type, Immediate::Index(i)) => index(text, &names.types, *i)?,
+            (_, Immediate::Index(i)) => write!(text, " {}", i)?,
+            (_, Immediate::BrTable { labels, default }) => {
+                for label in labels {
+                    write!(text, " {}", label)?;
+                }
+                write!(text, " {}", default)?;
+            }
+            (ImmediateKind::CallIndirect, Immediate::Indices(ty, table)) => {
+                if *table != 0 {
+                    index(text, &names.tables, *table)?;
+                }
+                text.push_str(" (type");
+                index(text, &names.types, *ty)?;
+                text.push(')');
+            }
+            (ImmediateKind::MemoryInit, Immediate::Indices(data, memory)) => {
+                if *memory != 0 {
+                    index(text, &names.memories, *memory)?;
+                }
+                index(text, &names.datas, *data)?;
+            }
+            (ImmediateKind::MemoryCopy, Immediate::Indices(dst, src)) => {
+                if *dst != 0 || *src != 0 {
+                    index(text, &names.memories, *dst)?;
+                    index(text, &names.memories, *src)?;
+                }
+            }
+            (ImmediateKind::TableInit, Immediate::Indices(elem, table)) => {
+                if *table != 0 {
+                    index(text, &names.tables, *table)?;
+                }
+                index(text, &names.elems, *elem)?;
+            }
+            (ImmediateKind::TableCopy, Immediate::Indices(dst, src)) => {
+                if *dst != 0 || *src != 0 {
+                    index(text, &names.tables, *dst)?;
+                    index(text, &names.tables, *src)?;
+                }
+            }
+            (
+                ImmediateKind::TypeField | ImmediateKind::ArrayNewFixed,
+                Immediate::Indices(ty, n),
+            ) => {
+                index(text, &names.types, *ty)?;
+                write!(text, " {}", n)?;
+            }
+            (ImmediateKind::TypeData, Immediate::Indices(ty, data)) => {
+                index(text, &names.types, *ty)?;
+                index(text, &names.datas, *data)?;
+            }
+            (ImmediateKind::TypeElem, Immediate::Indices(ty, elem)) => {
+                index(text, &names.types, *ty)?;
+                index(text, &names.elems, *elem)?;
+            }
+            (ImmediateKind::TypeType, Immediate::Indices(dst, src)) => {
+                index(text, &names.types, *dst)?;
+                index(text, &names.types, *src)?;
+            }
+            (_, Immediate::Indices(first, second)) => write!(text, " {} {}", first, second)?,
+            (_, Immediate::MemArg(mem_arg)) => self.mem_arg(op, mem_arg, text)?,
+            (_, Immediate::MemArgLane(mem_arg, lane)) => {
+                self.mem_arg(op, mem_arg, text)?;
+                write!(text, " {}", lane)?;
+            }
+            (_, Immediate::I32(value)) => write!(text, " {}", value)?,
+            (_, Immediate::I64(value)) => write!(text, " {}", value)?,
+            (_, Immediate::F32(bits)) => {
+                text.push(' ');
+                float(text, *bits as u64, 8, 23)?;
+            }
+            (_, Immediate::F64(bits)) => {
+                text.push(' ');
+                float(text, *bits, 11, 52)?;
+            }
+            (_, Immediate::V128(bytes)) => {
+                text.push_str(" i32x4");
+                for lane in bytes.chunks(4) {
+                    let lane = u32::from_le_bytes([lane[0], lane[1], lane[2], lane[3]]);
+                    write!(text, " 0x{:08x}", lane)?;
+                }
+            }
+            (_, Immediate::Lane(lane)) => write!(text, " {}", lane)?,
+            (_, Immediate::Shuffle(lanes)) => {
+                for lane in lanes {
+                    write!(text, " {}", lane)?;
+                }
+            }
+            (_, Immediate::ValTypes(types)) => {
+                text.push_str(" (result");
+                for ty in types {
+                    write!(text, " {}", val_type(*ty, &self.names.types))?;
+                }
+                text.push(')');
+            }
+            (ImmediateKind::RefType, Immediate::HeapType(heap)) => {
+                let nullable = matches!(op, Op::RefTestNull | Op::RefCastNull);
+                let ty = RefType {
+                    nullable,
+                    heap: *heap,
+                };
+                write!(text, " {}", ref_type(ty, &names.types))?;
+            }
+            (_, Immediate::HeapType(heap)) => write!(text, " {}", heap_type(*heap, &names.types))?,
+            (_, Immediate::BrOnCast { label, from, to }) => write!(
+                text,
+                " {} {} {}",
+                label,
+                ref_type(*from, &names.types),
+                ref_type(*to, &names.types)
+            )?,
+            (
+                _,
+                Immediate::TryTable {
+                    block_type,
+                    catches,
+                },
+            ) => {
+                self.immediates(
+                    &Instruction {
+                        op: Op::Block,
+                        immediate: Immediate::BlockType(*block_type),
+                    },
+                    text,
+                )?;
+                for catch in catches {
+                    text.push_str(match (catch.tag, catch.reference) {
+                        (Some(_), false) => " (catch",
+                        (Some(_), true) => " (catch_ref",
+                        (None, false) => " (catch_all",
+                        (None, true) => " (catch_all_ref",
+                    });
+                    if let Some(tag) = catch.tag {
+                        index(text, &names.tags, tag)?;
+                    }
+                    write!(text, " {})", catch.label)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a memarg, leaving out the parts that are the defaults.
+    fn mem_arg(&self, op: Op, mem_arg: &MemArg, text: &mut String) -> fmt::Result {
+        if mem_arg.memory != 0 {
+            match self
+                .names
+                .memories
+                .get(mem_arg.memory as usize)
+                .copied()
+                .flatten()
+            {
+                Some(id) => write!(text, " ${}", id)?,
+                None => write!(text, " {}", mem_arg.memory)?,
+            }
+        }
+        if mem_arg.offset != 0 {
+            write!(text, " offset={}", mem_arg.offset)?;
+        }
+        if Some(mem_arg.align) != op.natural_alignment() {
+            // the alignment is stored as a power of two, but written out as bytes
+            match 1u64.checked_shl(mem_arg.align) {
+                Some(align) => write!(text, " align={}", align)?,
+                None => write!(text, " (; align=2**{} ;)", mem_arg.align)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes a float from its bits, keeping NaN payloads. Finite values use the shortest
+/// decimal that parses back to the same bits.
+fn float(text: &mut String, bits: u64, exponent_bits: u32, mantissa_bits: u32) -> fmt::Result {
+    let sign = bits >> (exponent_bits + mantissa_bits) != 0;
+    let exponent = (bits >> mantissa_bits) & ((1 << exponent_bits) - 1);
+    let mantissa = bits & ((1 << mantissa_bits) - 1);
+    if exponent == (1 << exponent_bits) - 1 {
+        if sign {
+            text.push('-');
+        }
+        return if mantissa == 0 {
+            write!(text, "inf")
+        } else if mantissa == 1 << (mantissa_bits - 1) {
+            write!(text, "nan")
+        } else {
+            write!(text, "nan:0x{:x}", mantissa)
+        };
+    }
+    if mantissa_bits == 23 {
+        write!(text, "{:?}", f32::from_bits(bits as u32))
+    } else {
+        write!(text, "{:?}", f64::from_bits(bits))
+    }
+}
+
+fn val_type(ty: ValType, types: &[Option<&str>]) -> String {
+    let ty = match ty {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::FuncRef => "funcref",
+        ValType::ExternRef => "externref",
+        ValType::ExnRef => "exnref",
+        ValType::Ref(ty) => return ref_type(ty, types),
+    };
+    String::from(ty)
+}
+
+/// Writes a reference type, using the shorthand like `anyref` if there is one.
+fn ref_type(ty: RefType, types: &[Option<&str>]) -> String {
+    let shorthand = match ty.heap {
+        _ if !ty.nullable => None,
+        HeapType::Func => Some("funcref"),
+        HeapType::Extern => Some("externref"),
+        HeapType::Exn => Some("exnref"),
+        HeapType::Any => Some("anyref"),
+        HeapType::Eq => Some("eqref"),
+        HeapType::I31 => Some("i31ref"),
+        HeapType::Struct => Some("structref"),
+        HeapType::Array => Some("arrayref"),
+        HeapType::None => Some("nullref"),
+        HeapType::NoFunc => Some("nullfuncref"),
+        HeapType::NoExtern => Some("nullexternref"),
+        HeapType::NoExn => Some("nullexnref"),
+        HeapType::Type(_) => None,
+    };
+    match shorthand {
+        Some(shorthand) => String::from(shorthand),
+        None if ty.nullable => format!("(ref null {})", heap_type(ty.heap, types)),
+        None => format!("(ref {})", heap_type(ty.heap, types)),
+    }
+}
+
+fn heap_type(heap: HeapType, types: &[Option<&str>]) -> String {
+    let heap = match heap {
+        HeapType::Func => "func",
+        HeapType::Extern => "extern",
+        HeapType::Exn => "exn",
+        HeapType::Any => "any",
+        HeapType::Eq => "eq",
+        HeapType::I31 => "i31",
+        HeapType::Struct => "struct",
+        HeapType::Array => "array",
+        HeapType::None => "none",
+        HeapType::NoFunc => "nofunc",
+        HeapType::NoExtern => "noextern",
+        HeapType::NoExn => "noexn",
+        HeapType::Type(index) => {
+            return match types.get(index as usize).copied().flatten() {
+                Some(id) => format!("${}", id),
+                None => format!("{}", index),
+            }
+        }
+    };
+    String::from(heap)
+}
+
+/// The text format's name for a (non-custom) section id, for `@custom` annotations.
+/// The annotation for a branch hint, whose value is 1 for likely taken or 0 for not.
+fn hint_annotation(taken: bool) -> &'static str {
+    match taken {
+        true => "(@metadata.code.branch_hint \"\\01\")",
+        false => "(@metadata.code.branch_hint \"\\00\")",
+    }
+}
+
+fn section_name(id: u8) -> Option<&'static str> {
+    Some(match id {
+        1 => "type",
+        2 => "import",
+        3 => "func",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "elem",
+        10 => "code",
+        11 => "data",
+        12 => "datacount",
+        13 => "tag",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcodes::Op;
+    use alloc::string::ToString;
+
+    fn instruction(op: Op, immediate: Immediate) -> Instruction {
+        Instruction { op, immediate }
+    }
+
+    fn add() -> Module {
+        let mut local_ids = BTreeMap::new();
+        local_ids.insert(0, "a".to_string());
+        local_ids.insert(1, "b".to_string());
+        Module {
+            types: vec![TypeDef::func(FuncType {
+                params: vec![ValType::I32, ValType::I32],
+                results: vec![ValType::I32],
+            })],
+            funcs: vec![Func {
+                id: Some("add".to_string()),
+                type_index: 0,
+                locals: vec![(2, ValType::F64)],
+                local_ids,
+                body: vec![
+                    instruction(Op::LocalGet, Immediate::Index(0)),
+                    instruction(Op::LocalGet, Immediate::Index(1)),
+                    instruction(Op::I32Add, Immediate::None),
+                ],
+                branch_hints: BTreeMap::new(),
+            }],
+            exports: vec![Export {
+                name: "add".to_string(),
+                kind: ExportKind::Func,
+                index: 0,
+            }],
+            ..Module::default()
+        }
+    }
+
+    #[test]
+    fn flat() {
+        assert_eq!(
+            add().to_string(),
+            "(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (func $add (type 0) (param $a i32) (param $b i32) (result i32)
+    (local f64 f64)
+    local.get $a
+    local.get $b
+    i32.add)
+  (export \"add\" (func $add)))
+"
+        );
+    }
+
+    #[test]
+    fn folded() {
+        assert_eq!(
+            add().print(Style::Folded).to_string(),
+            "(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (func $add (type 0) (param $a i32) (param $b i32) (result i32)
+    (local f64 f64)
+    (i32.add
+      (local.get $a)
+      (local.get $b)))
+  (export \"add\" (func $add)))
+"
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let mut module = add();
+        module.funcs[0].locals.clear();
+        module.funcs[0].body = vec![
+            instruction(Op::Block, Immediate::BlockType(BlockType::Empty)),
+            instruction(Op::Br, Immediate::Index(0)),
+            instruction(Op::End, Immediate::None),
+            instruction(Op::LocalGet, Immediate::Index(0)),
+            instruction(Op::If, Immediate::BlockType(BlockType::Value(ValType::I32))),
+            instruction(Op::I32Const, Immediate::I32(1)),
+            instruction(Op::Else, Immediate::None),
+            instruction(Op::I32Const, Immediate::I32(-2)),
+            instruction(Op::End, Immediate::None),
+        ];
+        module.exports.clear();
+        assert_eq!(
+            module.to_string(),
+            "(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (func $add (type 0) (param $a i32) (param $b i32) (result i32)
+    block
+      br 0
+    end
+    local.get $a
+    if (result i32)
+      i32.const 1
+    else
+      i32.const -2
+    end))
+"
+        );
+        assert_eq!(
+            module.print(Style::Folded).to_string(),
+            "(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (func $add (type 0) (param $a i32) (param $b i32) (result i32)
+    (block
+      (br 0))
+    (if (result i32)
+      (local.get $a)
+      (then
+        (i32.const 1))
+      (else
+        (i32.const -2)))))
+"
+        );
+    }
+
+    #[test]
+    fn unusable_names() {
+        let mut module = add();
+        module.funcs.push(module.funcs[0].clone());
+        module.funcs[0].id = Some("has space".to_string());
+        module.funcs[0].local_ids.insert(1, "a".to_string());
+        module.funcs[0].body = vec![instruction(Op::Call, Immediate::Index(1))];
+        module.funcs[1].body = vec![instruction(Op::Call, Immediate::Index(0))];
+        let text = module.print(Style::Folded).to_string();
+        assert!(text.contains("(func (;0;) (type 0) (param $a i32) (param i32) (result i32)"));
+        assert!(text.contains("(func $add (type 0)"));
+        assert!(text.contains("(call 0)"));
+        assert!(text.contains("(call $add)"));
+    }
+
+    #[test]
+    fn segments_and_customs() {
+        let offset = |value| vec![instruction(Op::I32Const, Immediate::I32(value))];
+        let module = Module {
+            memories: vec![Memory {
+                id: Some("m".to_string()),
+                ty: MemoryType {
+                    limits: Limits {
+                        min: 1,
+                        max: Some(2),
+                    },
+                    shared: true,
+                    memory64: false,
+                    page_size_log2: Some(0),
+                },
+            }],
+            tables: vec![Table {
+                id: None,
+                ty: TableType {
+                    element: ValType::FuncRef,
+                    limits: Limits { min: 0, max: None },
+                    table64: false,
+                },
+            }],
+            elems: vec![Elem {
+                id: None,
+                mode: ElemMode::Declarative,
+                element: ValType::FuncRef,
+                items: ElemItems::Expressions(vec![vec![instruction(
+                    Op::RefNull,
+                    Immediate::HeapType(HeapType::Func),
+                )]]),
+            }],
+            datas: vec![
+                Data {
+                    id: Some("d".to_string()),
+                    mode: DataMode::Active {
+                        memory: 0,
+                        offset: offset(8),
+                    },
+                    bytes: b"hi\n\"".to_vec(),
+                },
+                Data {
+                    id: None,
+                    mode: DataMode::Passive,
+                    bytes: Vec::new(),
+                },
+            ],
+            customs: vec![Custom {
+                name: "producers".to_string(),
+                after: 11,
+                data: vec![0],
+            }],
+            ..Module::default()
+        };
+        assert_eq!(
+            module.to_string(),
+            "(module
+  (table (;0;) 0 funcref)
+  (memory $m 1 2 shared (pagesize 1))
+  (elem (;0;) declare funcref (ref.null func))
+  (data $d (i32.const 8) \"hi\\0a\\\"\")
+  (data (;1;) \"\")
+  (@custom \"producers\" (after data) \"\\00\"))
+"
+        );
+    }
+
+    #[test]
+    fn immediates() {
+        let module = Module {
+            types: vec![TypeDef::func(FuncType::default())],
+            ..Module::default()
+        };
+        let text = |op, immediate| {
+            let mut module = module.clone();
+            module.funcs.push(Func {
+                id: None,
+                type_index: 0,
+                locals: Vec::new(),
+                local_ids: BTreeMap::new(),
+                body: vec![instruction(op, immediate)],
+                branch_hints: BTreeMap::new(),
+            });
+            let text = module.to_string();
+            let line = text.lines().last().unwrap().trim();
+            line.strip_suffix("))").unwrap().to_string()
+        };
+        let mem_arg = |align, offset| {
+            Immediate::MemArg(MemArg {
+                align,
+                offset,
+                memory: 0,
+            })
+        };
+        assert_eq!(text(Op::I32Load, mem_arg(2, 0)), "i32.load");
+        assert_eq!(
+            text(Op::I32Load, mem_arg(0, 4)),
+            "i32.load offset=4 align=1"
+        );
+        assert_eq!(text(Op::I64Load8U, mem_arg(0, 0)), "i64.load8_u");
+        assert_eq!(
+            text(Op::F32Const, Immediate::F32(0x7fc0_0000)),
+            "f32.const nan"
+        );
+        assert_eq!(
+            text(Op::F32Const, Immediate::F32(0xff80_0001)),
+            "f32.const -nan:0x1"
+        );
+        assert_eq!(
+            text(Op::F64Const, Immediate::F64(0xfff0 << 48)),
+            "f64.const -inf"
+        );
+        assert_eq!(
+            text(Op::F64Const, Immediate::F64(0.1f64.to_bits())),
+            "f64.const 0.1"
+        );
+        assert_eq!(text(Op::F32Const, Immediate::F32(1)), "f32.const 1e-45");
+        assert_eq!(
+            text(Op::CallIndirect, Immediate::Indices(0, 1)),
+            "call_indirect 1 (type 0)"
+        );
+        assert_eq!(
+            text(Op::Block, Immediate::BlockType(BlockType::Type(0))),
+            "block (type 0)"
+        );
+        assert_eq!(
+            text(
+                Op::BrTable,
+                Immediate::BrTable {
+                    labels: vec![1, 2],
+                    default: 0
+                }
+            ),
+            "br_table 1 2 0"
+        );
+        assert_eq!(
+            text(Op::SelectTyped, Immediate::ValTypes(vec![ValType::V128])),
+            "select (result v128)"
+        );
+        assert_eq!(
+            text(Op::MemoryCopy, Immediate::Indices(0, 0)),
+            "memory.copy"
+        );
+        assert_eq!(
+            text(Op::ReturnCallRef, Immediate::Index(0)),
+            "return_call_ref 0"
+        );
+    }
+
+    #[test]
+    fn branch_hints() {
+        let module = Module {
+            types: vec![TypeDef::func(FuncType::default())],
+            funcs: vec![Func {
+                id: None,
+                type_index: 0,
+                locals: Vec::new(),
+                local_ids: BTreeMap::new(),
+                body: vec![
+                    instruction(Op::I32Const, Immediate::I32(0)),
+                    instruction(Op::BrIf, Immediate::Index(0)),
+                    instruction(Op::I32Const, Immediate::I32(1)),
+                    instruction(Op::If, Immediate::BlockType(BlockType::Empty)),
+                    instruction(Op::End, Immediate::None),
+                ],
+                branch_hints: BTreeMap::from([(1, true), (3, false)]),
+            }],
+            ..Module::default()
+        };
+        assert_eq!(
+            module.to_string(),
+            "(module
+  (type (;0;) (func))
+  (func (;0;) (type 0)
+    i32.const 0
+    (@metadata.code.branch_hint \"\\01\") br_if 0
+    i32.const 1
+    (@metadata.code.branch_hint \"\\00\") if
+    end))
+"
+        );
+        assert_eq!(
+            module.print(Style::Folded).to_string(),
+            "(module
+  (type (;0;) (func))
+  (func (;0;) (type 0)
+    (i32.const 0)
+    (@metadata.code.branch_hint \"\\01\")
+    (br_if 0)
+    (@metadata.code.branch_hint \"\\00\")
+    (if
+      (i32.const 1)
+      (then))))
+"
+        );
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut module = add();
+        module.exports.clear();
+        module.types.push(TypeDef::func(FuncType::default()));
+        module.tags.push(Tag {
+            id: Some("e".to_string()),
+            type_index: 1,
+        });
+        module.funcs[0].locals.clear();
+        module.funcs[0].body = vec![
+            instruction(
+                Op::TryTable,
+                Immediate::TryTable {
+                    block_type: BlockType::Empty,
+                    catches: vec![
+                        Catch {
+                            tag: Some(0),
+                            reference: false,
+                            label: 0,
+                        },
+                        Catch {
+                            tag: None,
+                            reference: true,
+                            label: 1,
+                        },
+                    ],
+                },
+            ),
+            instruction(Op::Throw, Immediate::Index(0)),
+            instruction(Op::End, Immediate::None),
+            instruction(Op::Try, Immediate::BlockType(BlockType::Empty)),
+            instruction(Op::Nop, Immediate::None),
+            instruction(Op::Catch, Immediate::Index(0)),
+            instruction(Op::Rethrow, Immediate::Index(0)),
+            instruction(Op::End, Immediate::None),
+            instruction(Op::Try, Immediate::BlockType(BlockType::Empty)),
+            instruction(Op::Delegate, Immediate::Index(0)),
+        ];
+        assert_eq!(
+            module.to_string(),
+            "(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (type (;1;) (func))
+  (func $add (type 0) (param $a i32) (param $b i32) (result i32)
+    try_table (catch $e 0) (catch_all_ref 1)
+      throw $e
+    end
+    try
+      nop
+    catch $e
+      rethrow 0
+    end
+    try
+    delegate 0)
+  (tag $e (type 1)))
+"
+        );
+        assert_eq!(
+            module.print(Style::Folded).to_string(),
+            "(module
+  (type (;0;) (func (param i32 i32) (result i32)))
+  (type (;1;) (func))
+  (func $add (type 0) (param $a i32) (param $b i32) (result i32)
+    (try_table (catch $e 0) (catch_all_ref 1)
+      (throw $e))
+    (try
+      (do
+        (nop))
+      (catch $e
+        (rethrow 0)))
+    (try
+      (do)
+      (delegate 0)))
+  (tag $e (type 1)))
+"
+        );
+    }
+
+    #[test]
+    fn gc_types() {
+        let reference = |nullable, index| {
+            ValType::Ref(RefType {
+                nullable,
+                heap: HeapType::Type(index),
+            })
+        };
+        let module = Module {
+            types: vec![
+                TypeDef {
+                    id: Some("s".to_string()),
+                    composite: CompositeType::Struct(vec![
+                        FieldType {
+                            storage: StorageType::I8,
+                            mutable: true,
+                        },
+                        FieldType {
+                            storage: StorageType::Val(reference(true, 1)),
+                            mutable: false,
+                        },
+                    ]),
+                    supertypes: vec![],
+                    is_final: false,
+                },
+                TypeDef {
+                    id: None,
+                    composite: CompositeType::Struct(vec![]),
+                    supertypes: vec![0],
+                    is_final: true,
+                },
+                TypeDef::func(FuncType {
+                    params: vec![],
+                    results: vec![reference(false, 0)],
+                }),
+                TypeDef::func(FuncType::default()),
+            ],
+            rec_groups: vec![0..2, 3..3],
+            funcs: vec![Func {
+                id: None,
+                type_index: 2,
+                locals: vec![],
+                local_ids: BTreeMap::new(),
+                body: vec![
+                    instruction(Op::StructNewDefault, Immediate::Index(0)),
+                    instruction(
+                        Op::BrOnCast,
+                        Immediate::BrOnCast {
+                            label: 0,
+                            from: RefType {
+                                nullable: true,
+                                heap: HeapType::Any,
+                            },
+                            to: RefType {
+                                nullable: false,
+                                heap: HeapType::Type(0),
+                            },
+                        },
+                    ),
+                    instruction(Op::RefCastNull, Immediate::HeapType(HeapType::Eq)),
+                    instruction(Op::StructGetS, Immediate::Indices(0, 0)),
+                ],
+                branch_hints: BTreeMap::new(),
+            }],
+            ..Module::default()
+        };
+        assert_eq!(
+            module.to_string(),
+            "(module
+  (rec
+    (type $s (sub (struct (field (mut i8)) (field (ref null 1)))))
+    (type (;1;) (sub final $s (struct))))
+  (type (;2;) (func (result (ref $s))))
+  (rec)
+  (type (;3;) (func))
+  (func (;0;) (type 2) (result (ref $s))
+    struct.new_default $s
+    br_on_cast 0 anyref (ref $s)
+    ref.cast eqref
+    struct.get_s $s 0))
+"
+        );
+    }
+
+    #[test]
+    fn output_lexes() {
+        let text = add().print(Style::Folded).to_string();
+        let tokens = crate::lexer::TokenList::from(text).list();
+        assert!(!tokens
+            .iter()
+            .any(|t| matches!(t, crate::lexer::Token::Reserved(_))));
+    }
+}
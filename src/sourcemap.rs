@@ -0,0 +1,249 @@
+//! Source maps from byte offsets in a binary module back to the text it was compiled from, in
+//! the Source Map v3 format that browsers' devtools read for WebAssembly. The whole binary is
+//! treated as a single generated line, with each instruction's byte offset as its column.
+
+use crate::json::Json;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// A point in the binary and where it came from. Lines and columns start at 0, and columns
+/// count UTF-16 code units, as they do in JavaScript.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Mapping {
+    /// The byte offset in the binary module.
+    pub offset: usize,
+    /// The index of the source, from [`SourceMap::add_source`].
+    pub source: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+struct Source {
+    name: String,
+    src: String,
+    /// The byte offset of the start of each line, for finding lines and columns quickly.
+    line_starts: Vec<usize>,
+}
+
+/// A source map, built up by the encoder as it emits each instruction.
+///
+/// ```
+/// # use w2w2::sourcemap::SourceMap;
+/// let mut map = SourceMap::new();
+/// let src = "(module\n  (func\n    nop))";
+/// let source = map.add_source("input.wat", src);
+/// // the `nop` at byte 20 of the source was encoded at byte 0x17 of the binary
+/// map.add(0x17, source, 20);
+/// assert_eq!(
+///     map.to_json(false),
+///     r#"{"version":3,"sources":["input.wat"],"names":[],"mappings":"uBAEI"}"#
+/// );
+/// ```
+#[derive(Default)]
+pub struct SourceMap {
+    sources: Vec<Source>,
+    mappings: Vec<Mapping>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Adds a source file, returning its index.
+    pub fn add_source(&mut self, name: &str, src: &str) -> usize {
+        let line_starts = core::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.sources.push(Source {
+            name: String::from(name),
+            src: String::from(src),
+            line_starts,
+        });
+        self.sources.len() - 1
+    }
+
+    /// Records that the bytes from `offset` in the binary came from byte `position` of a
+    /// source, which is usually the start of the instruction's token. A `position` in the
+    /// middle of a character maps to the start of that character.
+    pub fn add(&mut self, offset: usize, source: usize, position: usize) {
+        let Source {
+            src, line_starts, ..
+        } = &self.sources[source];
+        let mut position = position.min(src.len());
+        while !src.is_char_boundary(position) {
+            position -= 1;
+        }
+        let line = line_starts.partition_point(|&start| start <= position) - 1;
+        let column = src[line_starts[line]..position]
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+        self.mappings.push(Mapping {
+            offset,
+            source,
+            line,
+            column,
+        });
+    }
+
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// The source map as JSON, optionally including the sources' text so that it works
+    /// without them being served alongside the binary.
+    pub fn to_json(&self, include_sources: bool) -> String {
+        let mut fields = vec![
+            ("version", Json::from(3)),
+            (
+                "sources",
+                Json::Array(
+                    self.sources
+                        .iter()
+                        .map(|s| s.name.as_str().into())
+                        .collect(),
+                ),
+            ),
+        ];
+        if include_sources {
+            let contents = self.sources.iter().map(|s| s.src.as_str().into());
+            fields.push(("sourcesContent", Json::Array(contents.collect())));
+        }
+        fields.push(("names", Json::Array(Vec::new())));
+        fields.push(("mappings", self.encode_mappings().into()));
+        Json::object(fields).to_string()
+    }
+
+    /// The mappings as base64 VLQ segments, each relative to the one before.
+    fn encode_mappings(&self) -> String {
+        let mut mappings = self.mappings.clone();
+        mappings.sort_by_key(|mapping| mapping.offset);
+        let mut out = String::new();
+        let mut last = Mapping {
+            offset: 0,
+            source: 0,
+            line: 0,
+            column: 0,
+        };
+        for (i, mapping) in mappings.iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            vlq(&mut out, mapping.offset as i64 - last.offset as i64);
+            vlq(&mut out, mapping.source as i64 - last.source as i64);
+            vlq(&mut out, mapping.line as i64 - last.line as i64);
+            vlq(&mut out, mapping.column as i64 - last.column as i64);
+            last = *mapping;
+        }
+        out
+    }
+}
+
+/// Appends a base64 VLQ, which has the sign in the lowest bit and 5 bits per digit.
+fn vlq(out: &mut String, value: i64) {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut value = (value.unsigned_abs() << 1) | (value < 0) as u64;
+    loop {
+        let mut digit = value & 0b11111;
+        value >>= 5;
+        if value != 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// Appends a `sourceMappingURL` custom section to a binary module, which tells devtools where to
+/// find its source map.
+pub fn add_url_section(binary: &mut Vec<u8>, url: &str) {
+    const NAME: &str = "sourceMappingURL";
+    let mut contents = Vec::new();
+    leb128(&mut contents, NAME.len());
+    contents.extend_from_slice(NAME.as_bytes());
+    leb128(&mut contents, url.len());
+    contents.extend_from_slice(url.as_bytes());
+    binary.push(0);
+    leb128(binary, contents.len());
+    binary.extend(contents);
+}
+
+fn leb128(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder;
+
+    #[test]
+    fn vlqs() {
+        let encode = |value| {
+            let mut out = String::new();
+            vlq(&mut out, value);
+            out
+        };
+        assert_eq!(encode(0), "A");
+        assert_eq!(encode(1), "C");
+        assert_eq!(encode(-1), "D");
+        assert_eq!(encode(15), "e");
+        assert_eq!(encode(16), "gB");
+        assert_eq!(encode(123), "2H");
+        assert_eq!(encode(-1000), "x+B");
+    }
+
+    #[test]
+    fn mappings() {
+        let mut map = SourceMap::new();
+        let a = map.add_source("a.wat", "(func\n  nop\n  \u{1F600} drop)");
+        let b = map.add_source("b.wat", "unreachable");
+        // added out of order, since fields aren't always encoded in the order they're written
+        map.add(40, a, 8);
+        map.add(30, a, 0);
+        map.add(41, a, 19);
+        map.add(50, b, 0);
+        assert_eq!(
+            map.mappings()[2],
+            Mapping {
+                offset: 41,
+                source: a,
+                line: 2,
+                column: 5
+            }
+        );
+        assert_eq!(
+            map.to_json(true),
+            r#"{"version":3,"sources":["a.wat","b.wat"],"sourcesContent":["(func\n  nop\n  😀 drop)","unreachable"],"names":[],"mappings":"8BAAA,UACE,CACG,SCFL"}"#
+        );
+    }
+
+    #[test]
+    fn inside_a_character() {
+        let mut map = SourceMap::new();
+        let a = map.add_source("a.wat", "(func\n  \u{1F600} drop)");
+        map.add(0, a, 10);
+        assert_eq!((map.mappings()[0].line, map.mappings()[0].column), (1, 2));
+    }
+
+    #[test]
+    fn url_section() {
+        let mut binary = b"\0asm\x01\0\0\0".to_vec();
+        add_url_section(&mut binary, "out.wasm.map");
+        let sections = decoder::sections(&binary).unwrap();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].name.as_deref(), Some("sourceMappingURL"));
+        assert_eq!(&binary[sections[0].contents.clone()], b"\x0cout.wasm.map");
+    }
+}
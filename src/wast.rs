@@ -0,0 +1,1207 @@
+use crate::lexer::{Token, TokenList};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use Token::*;
+
+/// A `.wast` spec test script, parsed from the lexer's tokens.
+///
+/// Modules are not parsed any further than working out which form they're written in -
+/// a text module keeps the tokens of its fields so that they can be handed on as-is.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Script {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Module(Module),
+    /// `(module instance $I $M)`, instantiating a `(module definition $M ...)`.
+    ModuleInstance {
+        instance: Option<String>,
+        module: Option<String>,
+    },
+    Register {
+        name: String,
+        module: Option<String>,
+    },
+    Action(Action),
+    AssertReturn {
+        action: Action,
+        results: Vec<Expected>,
+    },
+    AssertTrap {
+        exec: Execute,
+        message: String,
+    },
+    AssertExhaustion {
+        action: Action,
+        message: String,
+    },
+    AssertException {
+        action: Action,
+    },
+    AssertInvalid {
+        module: Module,
+        message: String,
+    },
+    AssertMalformed {
+        module: Module,
+        message: String,
+    },
+    AssertUnlinkable {
+        module: Module,
+        message: String,
+    },
+    Thread {
+        name: String,
+        shared: Vec<String>,
+        commands: Vec<Command>,
+    },
+    Wait {
+        thread: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Module {
+    pub id: Option<String>,
+    /// Whether this is a `(module definition ...)`, which isn't instantiated straight away.
+    pub definition: bool,
+    pub source: ModuleSource,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ModuleSource {
+    /// The tokens of the module's fields, not including the `(module $id` header or the
+    /// closing paren.
+    Text(Vec<Token>),
+    Binary(Vec<u8>),
+    /// The concatenated contents of a `(module quote ...)`. This isn't necessarily valid
+    /// UTF-8, as some `assert_malformed` tests check exactly that.
+    Quote(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Action {
+    Invoke {
+        module: Option<String>,
+        name: String,
+        args: Vec<Value>,
+    },
+    Get {
+        module: Option<String>,
+        name: String,
+    },
+}
+
+/// The thing that `assert_trap` expects to trap - either an action, or instantiating a
+/// module (i.e. running its start function).
+#[derive(Debug, PartialEq, Clone)]
+pub enum Execute {
+    Action(Action),
+    Module(Module),
+}
+
+/// A constant argument. Floats are kept as their bit patterns so that NaN payloads survive.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(u32),
+    F64(u64),
+    V128([u8; 16]),
+    RefNull(String),
+    RefExtern(u32),
+    RefHost(u32),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FloatPattern {
+    Bits(u64),
+    CanonicalNan,
+    ArithmeticNan,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum V128Pattern {
+    Bits([u8; 16]),
+    F32x4([FloatPattern; 4]),
+    F64x2([FloatPattern; 2]),
+}
+
+/// An expected result of an `assert_return`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Expected {
+    I32(i32),
+    I64(i64),
+    F32(FloatPattern),
+    F64(FloatPattern),
+    V128(V128Pattern),
+    /// `(ref.null)` matches any null reference; `(ref.null func)` only a null of that type.
+    RefNull(Option<String>),
+    /// A non-null reference such as `(ref.func)`, `(ref.i31)` or `(ref.extern 1)`, where
+    /// `kind` is the part after `ref.`.
+    Ref {
+        kind: String,
+        index: Option<u32>,
+    },
+    Either(Vec<Expected>),
+}
+
+/// An error in a script, at `token`, an index into the script's (unfiltered) tokens.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WastError {
+    pub message: String,
+    pub token: usize,
+}
+
+impl fmt::Display for WastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at token {})", self.message, self.token)
+    }
+}
+
+impl TryFrom<TokenList> for Script {
+    type Error = WastError;
+
+    fn try_from(token_list: TokenList) -> Result<Script, WastError> {
+        let tokens = token_list.list();
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+        };
+        let commands = if parser.is_inline_module() {
+            let start = parser.pos;
+            parser.pos = tokens.len();
+            vec![Command::Module(Module {
+                id: None,
+                definition: false,
+                source: ModuleSource::Text(text_tokens(&tokens[start..])),
+            })]
+        } else {
+            parser.commands()?
+        };
+        if parser.peek().is_some() {
+            return Err(parser.error("expected a command"));
+        }
+        Ok(Script { commands })
+    }
+}
+
+/// Keywords that can start a top-level module field, for scripts that are just a module
+/// without the `(module ...)` around it.
+const MODULE_FIELDS: [&str; 11] = [
+    "type", "rec", "import", "func", "table", "memory", "global", "export", "start", "elem", "data",
+];
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_comments(&mut self) {
+        while let Some(Comment { .. }) = self.tokens.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<&'a Token> {
+        self.skip_comments();
+        self.tokens.get(self.pos)
+    }
+
+    /// The keyword after the next token, if the next token is an `(`.
+    fn peek_form(&mut self) -> Option<&'a str> {
+        self.skip_comments();
+        if let Some(LParen) = self.tokens.get(self.pos) {
+            let mut i = self.pos + 1;
+            while let Some(Comment { .. }) = self.tokens.get(i) {
+                i += 1;
+            }
+            if let Some(Instruction(keyword)) = self.tokens.get(i) {
+                return Some(keyword);
+            }
+        }
+        None
+    }
+
+    fn error(&self, message: &str) -> WastError {
+        WastError {
+            message: message.to_string(),
+            token: self.pos,
+        }
+    }
+
+    fn lparen(&mut self) -> Result<(), WastError> {
+        match self.peek() {
+            Some(LParen) => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error("expected `(`")),
+        }
+    }
+
+    fn rparen(&mut self) -> Result<(), WastError> {
+        match self.peek() {
+            Some(RParen) => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error("expected `)`")),
+        }
+    }
+
+    fn keyword(&mut self) -> Result<&'a str, WastError> {
+        match self.peek() {
+            Some(Instruction(keyword)) => {
+                self.pos += 1;
+                Ok(keyword)
+            }
+            _ => Err(self.error("expected a keyword")),
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Instruction(k)) if k == keyword => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn id(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Identifier(id)) => {
+                self.pos += 1;
+                Some(id.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn required_id(&mut self) -> Result<String, WastError> {
+        self.id()
+            .ok_or_else(|| self.error("expected an identifier"))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, WastError> {
+        match self.peek() {
+            Some(StringLiteral { string, .. }) => {
+                let bytes = string_bytes(string).map_err(|message| self.error(message))?;
+                self.pos += 1;
+                Ok(bytes)
+            }
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, WastError> {
+        let pos = self.pos;
+        String::from_utf8(self.bytes()?).map_err(|_| WastError {
+            message: "malformed UTF-8 encoding".to_string(),
+            token: pos,
+        })
+    }
+
+    fn all_bytes(&mut self) -> Result<Vec<u8>, WastError> {
+        let mut bytes = Vec::new();
+        while let Some(StringLiteral { .. }) = self.peek() {
+            bytes.append(&mut self.bytes()?);
+        }
+        Ok(bytes)
+    }
+
+    /// The text of a number (or `nan:canonical`-like keyword) token.
+    fn number(&mut self) -> Result<&'a str, WastError> {
+        match self.peek() {
+            Some(NumberLiteral { string, .. }) | Some(Instruction(string)) => {
+                self.pos += 1;
+                Ok(string)
+            }
+            _ => Err(self.error("expected a number")),
+        }
+    }
+
+    fn is_inline_module(&mut self) -> bool {
+        matches!(self.peek_form(), Some(keyword) if MODULE_FIELDS.contains(&keyword))
+    }
+
+    fn commands(&mut self) -> Result<Vec<Command>, WastError> {
+        let mut commands = Vec::new();
+        while let Some(LParen) = self.peek() {
+            commands.push(self.command()?);
+        }
+        Ok(commands)
+    }
+
+    fn command(&mut self) -> Result<Command, WastError> {
+        let command = match self.peek_form() {
+            Some("module") => {
+                if let Some(instance) = self.module_instance()? {
+                    return Ok(instance);
+                }
+                return Ok(Command::Module(self.module()?));
+            }
+            Some("invoke" | "get") => return Ok(Command::Action(self.action()?)),
+            _ => {
+                self.lparen()?;
+                let start = self.pos;
+                match self.keyword()? {
+                    "register" => Command::Register {
+                        name: self.string()?,
+                        module: self.id(),
+                    },
+                    "assert_return" => {
+                        let action = self.action()?;
+                        let mut results = Vec::new();
+                        while let Some(LParen) = self.peek() {
+                            results.push(self.expected()?);
+                        }
+                        Command::AssertReturn { action, results }
+                    }
+                    "assert_trap" => {
+                        let exec = match self.peek_form() {
+                            Some("module") => Execute::Module(self.module()?),
+                            _ => Execute::Action(self.action()?),
+                        };
+                        Command::AssertTrap {
+                            exec,
+                            message: self.string()?,
+                        }
+                    }
+                    "assert_exhaustion" => Command::AssertExhaustion {
+                        action: self.action()?,
+                        message: self.string()?,
+                    },
+                    "assert_exception" => Command::AssertException {
+                        action: self.action()?,
+                    },
+                    "assert_invalid" => Command::AssertInvalid {
+                        module: self.module()?,
+                        message: self.string()?,
+                    },
+                    "assert_malformed" => Command::AssertMalformed {
+                        module: self.module()?,
+                        message: self.string()?,
+                    },
+                    "assert_unlinkable" => Command::AssertUnlinkable {
+                        module: self.module()?,
+                        message: self.string()?,
+                    },
+                    "thread" => {
+                        let name = self.required_id()?;
+                        let mut shared = Vec::new();
+                        while let Some("shared") = self.peek_form() {
+                            self.lparen()?;
+                            self.keyword()?;
+                            self.lparen()?;
+                            if !self.eat_keyword("module") {
+                                return Err(self.error("expected `module`"));
+                            }
+                            shared.push(self.required_id()?);
+                            self.rparen()?;
+                            self.rparen()?;
+                        }
+                        Command::Thread {
+                            name,
+                            shared,
+                            commands: self.commands()?,
+                        }
+                    }
+                    "wait" => Command::Wait {
+                        thread: self.required_id()?,
+                    },
+                    _ => {
+                        return Err(WastError {
+                            message: "unknown command".to_string(),
+                            token: start,
+                        })
+                    }
+                }
+            }
+        };
+        self.rparen()?;
+        Ok(command)
+    }
+
+    /// `(module instance $I $M)`, if that's what comes next.
+    fn module_instance(&mut self) -> Result<Option<Command>, WastError> {
+        let start = self.pos;
+        self.lparen()?;
+        self.keyword()?;
+        if !self.eat_keyword("instance") {
+            self.pos = start;
+            return Ok(None);
+        }
+        let instance = self.id();
+        let module = self.id();
+        self.rparen()?;
+        Ok(Some(Command::ModuleInstance { instance, module }))
+    }
+
+    fn module(&mut self) -> Result<Module, WastError> {
+        self.lparen()?;
+        if !self.eat_keyword("module") {
+            return Err(self.error("expected `module`"));
+        }
+        let definition = self.eat_keyword("definition");
+        let id = self.id();
+        let source = if self.eat_keyword("binary") {
+            ModuleSource::Binary(self.all_bytes()?)
+        } else if self.eat_keyword("quote") {
+            ModuleSource::Quote(self.all_bytes()?)
+        } else {
+            let start = self.pos;
+            let mut depth = 0;
+            loop {
+                match self.tokens.get(self.pos) {
+                    Some(LParen) => depth += 1,
+                    Some(RParen) if depth == 0 => break,
+                    Some(RParen) => depth -= 1,
+                    Some(_) => {}
+                    None => return Err(self.error("unclosed module")),
+                }
+                self.pos += 1;
+            }
+            ModuleSource::Text(text_tokens(&self.tokens[start..self.pos]))
+        };
+        self.rparen()?;
+        Ok(Module {
+            id,
+            definition,
+            source,
+        })
+    }
+
+    fn action(&mut self) -> Result<Action, WastError> {
+        self.lparen()?;
+        let action = match self.keyword()? {
+            "invoke" => {
+                let module = self.id();
+                let name = self.string()?;
+                let mut args = Vec::new();
+                while let Some(LParen) = self.peek() {
+                    args.push(self.value()?);
+                }
+                Action::Invoke { module, name, args }
+            }
+            "get" => Action::Get {
+                module: self.id(),
+                name: self.string()?,
+            },
+            _ => return Err(self.error("expected `invoke` or `get`")),
+        };
+        self.rparen()?;
+        Ok(action)
+    }
+
+    fn value(&mut self) -> Result<Value, WastError> {
+        self.lparen()?;
+        let value = match self.keyword()? {
+            "i32.const" => Value::I32(self.int(32)? as i32),
+            "i64.const" => Value::I64(self.int(64)? as i64),
+            "f32.const" => Value::F32(self.float(F32)? as u32),
+            "f64.const" => Value::F64(self.float(F64)?),
+            "v128.const" => Value::V128(self.v128()?),
+            "ref.null" => Value::RefNull(self.keyword()?.to_string()),
+            "ref.extern" => Value::RefExtern(self.int(32)? as u32),
+            "ref.host" => Value::RefHost(self.int(32)? as u32),
+            _ => return Err(self.error("expected a constant")),
+        };
+        self.rparen()?;
+        Ok(value)
+    }
+
+    fn expected(&mut self) -> Result<Expected, WastError> {
+        self.lparen()?;
+        let keyword = self.keyword()?;
+        let expected = match keyword {
+            "i32.const" => Expected::I32(self.int(32)? as i32),
+            "i64.const" => Expected::I64(self.int(64)? as i64),
+            "f32.const" => Expected::F32(self.float_pattern(F32)?),
+            "f64.const" => Expected::F64(self.float_pattern(F64)?),
+            "v128.const" => Expected::V128(if self.eat_keyword("f32x4") {
+                let mut lanes = [FloatPattern::Bits(0); 4];
+                for lane in lanes.iter_mut() {
+                    *lane = self.float_pattern(F32)?;
+                }
+                V128Pattern::F32x4(lanes)
+            } else if self.eat_keyword("f64x2") {
+                let mut lanes = [FloatPattern::Bits(0); 2];
+                for lane in lanes.iter_mut() {
+                    *lane = self.float_pattern(F64)?;
+                }
+                V128Pattern::F64x2(lanes)
+            } else {
+                V128Pattern::Bits(self.v128()?)
+            }),
+            "ref.null" => Expected::RefNull(match self.peek() {
+                Some(Instruction(heap_type)) => {
+                    self.pos += 1;
+                    Some(heap_type.clone())
+                }
+                _ => None,
+            }),
+            "either" => {
+                let mut alternatives = Vec::new();
+                while let Some(LParen) = self.peek() {
+                    alternatives.push(self.expected()?);
+                }
+                Expected::Either(alternatives)
+            }
+            _ => match keyword.strip_prefix("ref.") {
+                Some(kind) => Expected::Ref {
+                    kind: kind.to_string(),
+                    index: match self.peek() {
+                        Some(NumberLiteral { .. }) => Some(self.int(32)? as u32),
+                        _ => None,
+                    },
+                },
+                None => return Err(self.error("expected a result")),
+            },
+        };
+        self.rparen()?;
+        Ok(expected)
+    }
+
+    fn int(&mut self, bits: u32) -> Result<u64, WastError> {
+        let pos = self.pos;
+        let text = self.number()?;
+        parse_int(text, bits).ok_or(WastError {
+            message: "constant out of range".to_string(),
+            token: pos,
+        })
+    }
+
+    fn float(&mut self, format: FloatFormat) -> Result<u64, WastError> {
+        let pos = self.pos;
+        let text = self.number()?;
+        parse_float(text, format).ok_or(WastError {
+            message: "constant out of range".to_string(),
+            token: pos,
+        })
+    }
+
+    fn float_pattern(&mut self, format: FloatFormat) -> Result<FloatPattern, WastError> {
+        if self.eat_keyword("nan:canonical") {
+            Ok(FloatPattern::CanonicalNan)
+        } else if self.eat_keyword("nan:arithmetic") {
+            Ok(FloatPattern::ArithmeticNan)
+        } else {
+            Ok(FloatPattern::Bits(self.float(format)?))
+        }
+    }
+
+    /// The shape and lanes of a `v128.const`, as little-endian bytes.
+    fn v128(&mut self) -> Result<[u8; 16], WastError> {
+        let (lanes, width, format) = match self.keyword()? {
+            "i8x16" => (16, 1, None),
+            "i16x8" => (8, 2, None),
+            "i32x4" => (4, 4, None),
+            "i64x2" => (2, 8, None),
+            "f32x4" => (4, 4, Some(F32)),
+            "f64x2" => (2, 8, Some(F64)),
+            _ => return Err(self.error("expected a vector shape")),
+        };
+        let mut bytes = [0; 16];
+        for lane in 0..lanes {
+            let value = match format {
+                Some(format) => self.float(format)?,
+                None => self.int(width as u32 * 8)?,
+            };
+            bytes[lane * width..(lane + 1) * width].copy_from_slice(&value.to_le_bytes()[..width]);
+        }
+        Ok(bytes)
+    }
+}
+
+/// Drops the comments from a text module's tokens, since nothing after the script parser
+/// needs them.
+fn text_tokens(tokens: &[Token]) -> Vec<Token> {
+    tokens
+        .iter()
+        .filter(|t| !matches!(t, Comment { .. }))
+        .cloned()
+        .collect()
+}
+
+/// Decodes the escapes in a string literal token (which still has its quotes on).
+fn string_bytes(literal: &str) -> Result<Vec<u8>, &'static str> {
+    let inner = &literal[1..literal.len() - 1];
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('t') => bytes.push(b'\t'),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('"') => bytes.push(b'"'),
+            Some('\'') => bytes.push(b'\''),
+            Some('\\') => bytes.push(b'\\'),
+            Some('u') => {
+                if chars.next() != Some('{') {
+                    return Err("malformed unicode escape");
+                }
+                let mut code = 0u32;
+                let mut digits = 0;
+                loop {
+                    match chars.next() {
+                        Some('}') if digits > 0 => break,
+                        Some('_') if digits > 0 => {}
+                        Some(c) if c.is_ascii_hexdigit() => {
+                            code = code
+                                .checked_mul(16)
+                                .and_then(|code| code.checked_add(c.to_digit(16).unwrap()))
+                                .ok_or("malformed unicode escape")?;
+                            digits += 1;
+                        }
+                        _ => return Err("malformed unicode escape"),
+                    }
+                }
+                let ch = char::from_u32(code).ok_or("malformed unicode escape")?;
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            }
+            Some(hi) => {
+                let lo = chars.next();
+                match (hi.to_digit(16), lo.and_then(|lo| lo.to_digit(16))) {
+                    (Some(hi), Some(lo)) => bytes.push((hi * 16 + lo) as u8),
+                    _ => return Err("unknown escape"),
+                }
+            }
+            None => return Err("unknown escape"),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Parses digits (with `_` separators between them) in the given base.
+fn parse_digits(digits: &str, radix: u32) -> Option<u64> {
+    if digits.is_empty() || digits.starts_with('_') || digits.ends_with('_') {
+        return None;
+    }
+    let mut value: u64 = 0;
+    let mut last_underscore = false;
+    for c in digits.chars() {
+        if c == '_' {
+            if last_underscore {
+                return None;
+            }
+            last_underscore = true;
+            continue;
+        }
+        last_underscore = false;
+        value = value
+            .checked_mul(radix as u64)?
+            .checked_add(c.to_digit(radix)? as u64)?;
+    }
+    Some(value)
+}
+
+/// Parses an integer literal into the bit pattern of a `bits`-wide integer, accepting
+/// anything in the signed or unsigned range like the text format does.
+fn parse_int(text: &str, bits: u32) -> Option<u64> {
+    let (sign, unsigned) = match text.as_bytes().first() {
+        Some(b'+') => (Some(false), &text[1..]),
+        Some(b'-') => (Some(true), &text[1..]),
+        _ => (None, text),
+    };
+    let magnitude = match unsigned.strip_prefix("0x") {
+        Some(hex) => parse_digits(hex, 16)?,
+        None => parse_digits(unsigned, 10)?,
+    };
+    let mask = if bits == 64 {
+        u64::MAX
+    } else {
+        (1 << bits) - 1
+    };
+    let half = 1u64 << (bits - 1);
+    match sign {
+        None if magnitude <= mask => Some(magnitude),
+        Some(false) if magnitude < half => Some(magnitude),
+        Some(true) if magnitude <= half => Some(magnitude.wrapping_neg() & mask),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct FloatFormat {
+    mantissa_bits: u32,
+    exponent_bits: u32,
+}
+
+const F32: FloatFormat = FloatFormat {
+    mantissa_bits: 23,
+    exponent_bits: 8,
+};
+
+const F64: FloatFormat = FloatFormat {
+    mantissa_bits: 52,
+    exponent_bits: 11,
+};
+
+/// Parses a float literal into the bit pattern of the given format, rounding to nearest.
+fn parse_float(text: &str, format: FloatFormat) -> Option<u64> {
+    let (negative, unsigned) = match text.as_bytes().first() {
+        Some(b'+') => (false, &text[1..]),
+        Some(b'-') => (true, &text[1..]),
+        _ => (false, text),
+    };
+    let sign = (negative as u64) << (format.mantissa_bits + format.exponent_bits);
+    let infinity = ((1 << format.exponent_bits) - 1) << format.mantissa_bits;
+    if unsigned == "inf" {
+        return Some(sign | infinity);
+    }
+    if unsigned == "nan" {
+        return Some(sign | infinity | 1 << (format.mantissa_bits - 1));
+    }
+    if let Some(payload) = unsigned.strip_prefix("nan:0x") {
+        let payload = parse_digits(payload, 16)?;
+        if payload == 0 || payload >= 1 << format.mantissa_bits {
+            return None;
+        }
+        return Some(sign | infinity | payload);
+    }
+    let bits = match unsigned.strip_prefix("0x") {
+        Some(hex) => parse_hex_float(hex, format)?,
+        None => parse_dec_float(unsigned, format)?,
+    };
+    if bits >= infinity {
+        return None;
+    }
+    Some(sign | bits)
+}
+
+fn parse_dec_float(text: &str, format: FloatFormat) -> Option<u64> {
+    // underscores have to be between digits, and so can't be next to the `.` or `e`
+    for part in text.split(['.', 'e', 'E']) {
+        let part = part.strip_prefix(['+', '-']).unwrap_or(part);
+        if !part.is_empty() {
+            parse_digits(part, 10)?;
+        }
+    }
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let text: String = text.chars().filter(|c| *c != '_').collect();
+    if format == F32 {
+        text.parse::<f32>().ok().map(|f| f.to_bits() as u64)
+    } else {
+        text.parse::<f64>().ok().map(f64::to_bits)
+    }
+}
+
+fn parse_hex_float(text: &str, format: FloatFormat) -> Option<u64> {
+    let (mantissa, exponent) = match text.find(['p', 'P']) {
+        Some(i) => (&text[..i], Some(&text[i + 1..])),
+        None => (text, None),
+    };
+    let (int_part, frac_part) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+        None => (mantissa, ""),
+    };
+    parse_digits(int_part, 16)?;
+    if !frac_part.is_empty() {
+        parse_digits(frac_part, 16)?;
+    }
+    let exponent: i64 = match exponent {
+        Some(exponent) => {
+            let (negative, digits) = match exponent.as_bytes().first() {
+                Some(b'+') => (false, &exponent[1..]),
+                Some(b'-') => (true, &exponent[1..]),
+                _ => (false, exponent),
+            };
+            // anything this big over/underflows whatever the mantissa is
+            let magnitude = parse_digits(digits, 10)?.min(1 << 20) as i64;
+            if negative {
+                -magnitude
+            } else {
+                magnitude
+            }
+        }
+        None => 0,
+    };
+
+    // the value is `significand * 2^exponent`, with any digits that didn't fit into the
+    // significand ORed into `sticky` so that rounding still sees them
+    let mut significand: u64 = 0;
+    let mut exponent = exponent;
+    let mut sticky = false;
+    for (c, fractional) in int_part
+        .chars()
+        .map(|c| (c, false))
+        .chain(frac_part.chars().map(|c| (c, true)))
+    {
+        let Some(digit) = c.to_digit(16) else {
+            continue;
+        };
+        if significand >> 60 == 0 {
+            significand = significand << 4 | digit as u64;
+            if fractional {
+                exponent -= 4;
+            }
+        } else {
+            sticky |= digit != 0;
+            if !fractional {
+                exponent += 4;
+            }
+        }
+    }
+    if significand == 0 {
+        return Some(0);
+    }
+
+    let bias = (1i64 << (format.exponent_bits - 1)) - 1;
+    let mantissa_bits = format.mantissa_bits as i64;
+    let leading_zeros = significand.leading_zeros() as i64;
+    let significand = (significand as u128) << leading_zeros;
+    let exponent = exponent - leading_zeros;
+    // the exponent of the value's top bit
+    let top = exponent + 63;
+    if top > bias {
+        return None;
+    }
+    let lsb = if top >= 1 - bias {
+        top - mantissa_bits
+    } else {
+        1 - bias - mantissa_bits
+    };
+    let shift = lsb - exponent;
+    if shift >= 128 {
+        return Some(0);
+    }
+    let mut q = significand >> shift;
+    let rem = significand & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if rem > half || (rem == half && (sticky || q & 1 == 1)) {
+        q += 1;
+    }
+    let q = q as u64;
+    let bits = if top >= 1 - bias {
+        (((top + bias - 1) as u64) << format.mantissa_bits) + q
+    } else {
+        q
+    };
+    Some(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    fn script(src: &str) -> Script {
+        Script::try_from(TokenList::from(src.to_string())).unwrap()
+    }
+
+    fn command(src: &str) -> Command {
+        let mut script = script(src);
+        assert_eq!(script.commands.len(), 1);
+        script.commands.remove(0)
+    }
+
+    #[test]
+    fn text_module() {
+        let c = command("(module $M (func (export \"f\") (result i32) (i32.const 1)))");
+        assert_matches!(c, Command::Module(Module {
+            id: Some(id),
+            definition: false,
+            source: ModuleSource::Text(tokens),
+        }) if id == "$M" && tokens.len() == 15);
+    }
+
+    #[test]
+    fn binary_module() {
+        let c = command(r#"(module binary "\00asm" "\01\00\00\00")"#);
+        assert_matches!(c, Command::Module(Module {
+            source: ModuleSource::Binary(bytes),
+            ..
+        }) if bytes == b"\0asm\x01\0\0\0");
+    }
+
+    #[test]
+    fn quote_module() {
+        let c = command(r#"(module quote "(func" " (nop))")"#);
+        assert_matches!(c, Command::Module(Module {
+            source: ModuleSource::Quote(bytes),
+            ..
+        }) if bytes == b"(func (nop))");
+    }
+
+    #[test]
+    fn module_definition_and_instance() {
+        let s = script("(module definition $M (memory 1)) (module instance $I $M)");
+        assert_matches!(
+            &s.commands[0],
+            Command::Module(Module { definition: true, id: Some(id), .. }) if id == "$M"
+        );
+        assert_eq!(
+            s.commands[1],
+            Command::ModuleInstance {
+                instance: Some("$I".to_string()),
+                module: Some("$M".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn inline_module() {
+        let c = command("(func) ;; comment\n(memory 1)");
+        assert_matches!(c, Command::Module(Module {
+            source: ModuleSource::Text(tokens),
+            ..
+        }) if tokens.len() == 7);
+    }
+
+    #[test]
+    fn register() {
+        assert_eq!(
+            command(r#"(register "M" $M)"#),
+            Command::Register {
+                name: "M".to_string(),
+                module: Some("$M".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn invoke_and_get() {
+        let s = script(
+            r#"(invoke $M "add" (i32.const -1) (i64.const 0xffff_ffff_ffff_ffff)
+                (f32.const -0x1p-1) (f64.const nan:0x4) (ref.null extern) (ref.extern 2))
+               (get "g")"#,
+        );
+        assert_eq!(
+            s.commands[0],
+            Command::Action(Action::Invoke {
+                module: Some("$M".to_string()),
+                name: "add".to_string(),
+                args: vec![
+                    Value::I32(-1),
+                    Value::I64(-1),
+                    Value::F32(0xbf00_0000),
+                    Value::F64(0x7ff0_0000_0000_0004),
+                    Value::RefNull("extern".to_string()),
+                    Value::RefExtern(2),
+                ],
+            })
+        );
+        assert_eq!(
+            s.commands[1],
+            Command::Action(Action::Get {
+                module: None,
+                name: "g".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn assert_return_patterns() {
+        let c = command(
+            r#"(assert_return (invoke "f")
+                (f32.const nan:canonical) (f64.const nan:arithmetic) (f64.const -inf)
+                (v128.const f32x4 1 nan:canonical -0 0x1p127)
+                (v128.const i16x8 -1 0 1 2 3 4 5 0xffff)
+                (ref.null) (ref.func) (ref.extern 1)
+                (either (i32.const 1) (i32.const 2)))"#,
+        );
+        let Command::AssertReturn { results, .. } = c else {
+            panic!("expected assert_return");
+        };
+        assert_eq!(
+            results,
+            vec![
+                Expected::F32(FloatPattern::CanonicalNan),
+                Expected::F64(FloatPattern::ArithmeticNan),
+                Expected::F64(FloatPattern::Bits(0xfff0_0000_0000_0000)),
+                Expected::V128(V128Pattern::F32x4([
+                    FloatPattern::Bits(0x3f80_0000),
+                    FloatPattern::CanonicalNan,
+                    FloatPattern::Bits(0x8000_0000),
+                    FloatPattern::Bits(0x7f00_0000),
+                ])),
+                Expected::V128(V128Pattern::Bits([
+                    0xff, 0xff, 0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 0xff, 0xff
+                ])),
+                Expected::RefNull(None),
+                Expected::Ref {
+                    kind: "func".to_string(),
+                    index: None,
+                },
+                Expected::Ref {
+                    kind: "extern".to_string(),
+                    index: Some(1),
+                },
+                Expected::Either(vec![Expected::I32(1), Expected::I32(2)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn assertions_with_messages() {
+        let s = script(
+            r#"(assert_trap (invoke "div" (i32.const 1) (i32.const 0)) "integer divide by zero")
+               (assert_trap (module (func $f unreachable) (start $f)) "unreachable")
+               (assert_exhaustion (invoke "loop") "call stack exhausted")
+               (assert_exception (invoke "throw"))
+               (assert_invalid (module (func (result i32))) "type mismatch")
+               (assert_malformed (module quote "(func") "unclosed")
+               (assert_unlinkable (module (import "m" "f" (func))) "unknown import")"#,
+        );
+        assert_eq!(s.commands.len(), 7);
+        assert_matches!(
+            &s.commands[0],
+            Command::AssertTrap { exec: Execute::Action(_), message } if message == "integer divide by zero"
+        );
+        assert_matches!(
+            &s.commands[1],
+            Command::AssertTrap {
+                exec: Execute::Module(_),
+                ..
+            }
+        );
+        assert_matches!(&s.commands[2], Command::AssertExhaustion { .. });
+        assert_matches!(&s.commands[3], Command::AssertException { .. });
+        assert_matches!(&s.commands[4], Command::AssertInvalid { message, .. } if message == "type mismatch");
+        assert_matches!(
+            &s.commands[5],
+            Command::AssertMalformed {
+                module: Module {
+                    source: ModuleSource::Quote(_),
+                    ..
+                },
+                ..
+            }
+        );
+        assert_matches!(&s.commands[6], Command::AssertUnlinkable { .. });
+    }
+
+    #[test]
+    fn threads() {
+        let s = script(
+            r#"(module $Mem (memory 1 1 shared))
+               (thread $T1 (shared (module $Mem))
+                 (register "mem" $Mem)
+                 (invoke "run"))
+               (wait $T1)"#,
+        );
+        assert_eq!(
+            s.commands[1],
+            Command::Thread {
+                name: "$T1".to_string(),
+                shared: vec!["$Mem".to_string()],
+                commands: vec![
+                    Command::Register {
+                        name: "mem".to_string(),
+                        module: Some("$Mem".to_string()),
+                    },
+                    Command::Action(Action::Invoke {
+                        module: None,
+                        name: "run".to_string(),
+                        args: vec![],
+                    }),
+                ],
+            }
+        );
+        assert_eq!(
+            s.commands[2],
+            Command::Wait {
+                thread: "$T1".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = Script::try_from(TokenList::from("(assert_bogus)".to_string())).unwrap_err();
+        assert_eq!(err.message, "unknown command");
+        assert_eq!(err.token, 1);
+        let err = Script::try_from(TokenList::from(
+            "(invoke \"f\" (i32.const 0x1_0000_0000))".to_string(),
+        ))
+        .unwrap_err();
+        assert_eq!(err.message, "constant out of range");
+        assert!(Script::try_from(TokenList::from("(module".to_string())).is_err());
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            string_bytes(r#""a\t\n\r\"\'\\\7f""#).unwrap(),
+            b"a\t\n\r\"'\\\x7f"
+        );
+        assert_eq!(
+            string_bytes(r#""\u{1F600}""#).unwrap(),
+            "\u{1F600}".as_bytes()
+        );
+        assert!(string_bytes(r#""\u{110000}""#).is_err());
+        assert!(string_bytes(r#""\q""#).is_err());
+    }
+
+    #[test]
+    fn ints() {
+        assert_eq!(parse_int("4294967295", 32), Some(0xffff_ffff));
+        assert_eq!(parse_int("-2147483648", 32), Some(0x8000_0000));
+        assert_eq!(parse_int("+2147483648", 32), None);
+        assert_eq!(parse_int("4294967296", 32), None);
+        assert_eq!(
+            parse_int("0x7fff_ffff_ffff_ffff", 64),
+            Some(i64::MAX as u64)
+        );
+        assert_eq!(
+            parse_int("-0x8000_0000_0000_0000", 64),
+            Some(i64::MIN as u64)
+        );
+        assert_eq!(parse_int("1__0", 32), None);
+        assert_eq!(parse_int("_1", 32), None);
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(parse_float("0x1p-149", F32), Some(1));
+        assert_eq!(parse_float("0x1p-150", F32), Some(0));
+        assert_eq!(parse_float("0x1.8p-149", F32), Some(2));
+        assert_eq!(parse_float("0x1.fffffep127", F32), Some(0x7f7f_ffff));
+        assert_eq!(parse_float("0x1.ffffffp127", F32), None);
+        assert_eq!(
+            parse_float("0x1.fffffefffffffffp127", F32),
+            Some(0x7f7f_ffff)
+        );
+        assert_eq!(parse_float("0x1.000001p0", F32), Some(0x3f80_0000));
+        assert_eq!(
+            parse_float("0x1.00000100000000001p0", F32),
+            Some(0x3f80_0001)
+        );
+        assert_eq!(parse_float("0x1.000003p0", F32), Some(0x3f80_0002));
+        assert_eq!(
+            parse_float("0x0.0000_0000_0000_0000_0000_8p0", F64),
+            Some(0x3ae0_0000_0000_0000)
+        );
+        assert_eq!(parse_float("0x1p-1074", F64), Some(1));
+        assert_eq!(parse_float("0x1p-1022", F64), Some(0x0010_0000_0000_0000));
+        assert_eq!(
+            parse_float("0x0.fffffffffffff8p-1022", F64),
+            Some(0x0010_0000_0000_0000)
+        );
+        assert_eq!(parse_float("1e400", F64), None);
+        assert_eq!(parse_float("1_000.5e-1", F64), Some(100.05f64.to_bits()));
+        assert_eq!(parse_float("3.4028235e38", F32), Some(0x7f7f_ffff));
+        assert_eq!(parse_float("-nan", F32), Some(0xffc0_0000));
+        assert_eq!(parse_float("nan:0x7f_ffff", F32), Some(0x7fff_ffff));
+        assert_eq!(parse_float("nan:0x80_0000", F32), None);
+        assert_eq!(parse_float("nan:0x0", F64), None);
+        assert_eq!(parse_float("1._5", F64), None);
+        assert_eq!(parse_float("0x.8p1", F64), None);
+    }
+}
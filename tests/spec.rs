@@ -0,0 +1,135 @@
+//! Runs the `.wast` scripts in `tests/spec` against the decoder. Every binary `module` has
+//! to decode, and every binary `assert_malformed` has to be rejected with the expected
+//! message (or one that starts with it, like the reference interpreter allows).
+//!
+//! Text and quoted modules need the parser, and `assert_invalid` needs the validator, so
+//! they're counted as skipped until those exist. Run with `--nocapture` to see the summary
+//! for each file.
+
+use std::fs;
+use std::path::Path;
+use w2w2::decoder::decode;
+use w2w2::features::Features;
+use w2w2::lexer::TokenList;
+use w2w2::wast::{Command, Module, ModuleSource, Script};
+
+/// Cases that are known to fail, as the file name and the index of the command within it
+/// (counting the commands inside `thread`s), with the reason.
+const KNOWN_FAILURES: &[(&str, usize, &str)] = &[
+    (
+        "binary.wast",
+        4,
+        "a truncated magic number is reported as a bad one, not as an unexpected end",
+    ),
+    (
+        "binary.wast",
+        5,
+        "a truncated magic number is reported as a bad one, not as an unexpected end",
+    ),
+    (
+        "binary.wast",
+        6,
+        "a truncated magic number is reported as a bad one, not as an unexpected end",
+    ),
+    (
+        "binary.wast",
+        22,
+        "the decoder reports unknown opcodes by their bytes rather than as illegal",
+    ),
+];
+
+enum Outcome {
+    Pass,
+    Fail(String),
+    Skip,
+}
+
+fn check(command: &Command) -> Outcome {
+    match command {
+        Command::Module(Module {
+            source: ModuleSource::Binary(bytes),
+            ..
+        }) => match decode(bytes, Features::all()) {
+            Ok(_) => Outcome::Pass,
+            Err(err) => Outcome::Fail(format!("failed to decode: {}", err.message)),
+        },
+        Command::AssertMalformed {
+            module:
+                Module {
+                    source: ModuleSource::Binary(bytes),
+                    ..
+                },
+            message,
+        } => match decode(bytes, Features::all()) {
+            Ok(_) => Outcome::Fail(format!("decoded, but should fail with \"{}\"", message)),
+            Err(err) if err.message.starts_with(message.as_str()) => Outcome::Pass,
+            Err(err) => Outcome::Fail(format!(
+                "failed with \"{}\" rather than \"{}\"",
+                err.message, message
+            )),
+        },
+        _ => Outcome::Skip,
+    }
+}
+
+fn flatten<'a>(commands: &'a [Command], out: &mut Vec<&'a Command>) {
+    for command in commands {
+        match command {
+            Command::Thread { commands, .. } => flatten(commands, out),
+            _ => out.push(command),
+        }
+    }
+}
+
+#[test]
+fn spec() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec");
+    let mut paths: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wast"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no scripts in {}", dir.display());
+
+    let mut unexpected = Vec::new();
+    for path in paths {
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let src = fs::read_to_string(&path).unwrap();
+        let script = Script::try_from(TokenList::from(src))
+            .unwrap_or_else(|err| panic!("{}: {}", name, err));
+        let mut commands = Vec::new();
+        flatten(&script.commands, &mut commands);
+
+        let (mut passed, mut failed, mut known, mut skipped) = (0, 0, 0, 0);
+        for (index, command) in commands.into_iter().enumerate() {
+            let is_known = KNOWN_FAILURES
+                .iter()
+                .any(|&(file, i, _)| file == name && i == index);
+            match check(command) {
+                Outcome::Pass if is_known => {
+                    passed += 1;
+                    unexpected.push(format!(
+                        "{} #{}: passes, so it can come off the known failures",
+                        name, index
+                    ));
+                }
+                Outcome::Pass => passed += 1,
+                Outcome::Fail(_) if is_known => {
+                    failed += 1;
+                    known += 1;
+                }
+                Outcome::Fail(reason) => {
+                    failed += 1;
+                    unexpected.push(format!("{} #{}: {}", name, index, reason));
+                }
+                Outcome::Skip => skipped += 1,
+            }
+        }
+        println!(
+            "{}: {} passed, {} failed ({} known), {} skipped",
+            name, passed, failed, known, skipped
+        );
+    }
+    assert!(unexpected.is_empty(), "\n{}", unexpected.join("\n"));
+}
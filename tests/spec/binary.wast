@@ -0,0 +1,90 @@
+;; Hand-written cases in the style of the official testsuite's binary.wast, covering the
+;; module header and section layout.
+
+(module binary "\00asm" "\01\00\00\00")
+(module binary "\00asm\01\00\00\00")
+(module $M1 binary "\00asm\01\00\00\00")
+(module $M2 binary "\00asm" "\01\00\00\00")
+
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\01") "unexpected end")
+(assert_malformed (module binary "\00as") "unexpected end")
+(assert_malformed (module binary "asm\00") "magic header not detected")
+(assert_malformed (module binary "msa\00") "magic header not detected")
+(assert_malformed (module binary "\00asm") "unexpected end")
+(assert_malformed (module binary "\00asm\01") "unexpected end")
+(assert_malformed (module binary "\00asm\01\00\00") "unexpected end")
+(assert_malformed (module binary "\00asm\00\00\00\00") "unknown binary version")
+(assert_malformed (module binary "\00asm\0d\00\00\00") "unknown binary version")
+(assert_malformed (module binary "\00asm\00\00\01\00") "unknown binary version")
+
+;; Section ids and sizes.
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\0e\01\00")
+  "malformed section id"
+)
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\7f\01\00")
+  "malformed section id"
+)
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\01\05\01\60\00\00")
+  "length out of bounds"
+)
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\01\05\01\60\00\00\00")
+  "section size mismatch"
+)
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\03\02\01\00"                       ;; function section
+    "\01\04\01\60\00\00"                 ;; type section
+  )
+  "section out of order"
+)
+
+;; A function and its body.
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\04\01\60\00\00"                   ;; type section: [] -> []
+  "\03\02\01\00"                         ;; function section
+  "\0a\04\01\02\00\0b"                   ;; code section
+)
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"
+    "\03\02\01\00"
+  )
+  "function and code section have inconsistent lengths"
+)
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"
+    "\03\02\01\00"
+    "\0a\05\01\03\00\ff\0b"              ;; 0xff isn't an opcode
+  )
+  "illegal opcode"
+)
+
+;; Memory limits, with integers that are too long or too large.
+(module binary "\00asm" "\01\00\00\00" "\05\04\01\01\00\01")
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\05\08\01\00\82\80\80\80\80\00")
+  "integer representation too long"
+)
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\05\07\01\00\82\80\80\80\70")
+  "integer too large"
+)
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\05\03\01\10\00")
+  "malformed limits flags"
+)
+
+;; Text modules need the parser and validator, which don't exist yet, so these are skipped.
+(module (func (result i32) (i32.const 0)))
+(assert_invalid (module (func (result i32) (i64.const 0))) "type mismatch")
+(assert_malformed (module quote "(func (i32.const))") "unexpected token")
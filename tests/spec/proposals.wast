@@ -0,0 +1,57 @@
+;; Binary modules using post-MVP proposals, which the harness decodes with every proposal
+;; enabled.
+
+;; sign-extension-ops and nontrapping-float-to-int-conversions
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\04\01\60\00\00"
+  "\03\02\01\00"
+  "\0a\10\01\0e\00"
+  "\41\00\c0\1a"                         ;; i32.const 0 i32.extend8_s drop
+  "\43\00\00\00\00\fc\00\1a"             ;; f32.const 0 i32.trunc_sat_f32_s drop
+  "\0b"
+)
+
+;; mutable globals, imported and exported
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\02\0a\01\03env\01g\03\7f\01"        ;; (import "env" "g" (global (mut i32)))
+  "\07\05\01\01g\03\00"                  ;; (export "g" (global 0))
+)
+
+;; extended-const
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\06\09\01\7f\00\41\01\41\02\6a\0b"    ;; (global i32 (i32.add (i32.const 1) (i32.const 2)))
+)
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\06\05\01\7f\00\01\0b"              ;; (global i32 (nop))
+  )
+  "constant expression required"
+)
+
+;; custom-page-sizes
+(module binary "\00asm" "\01\00\00\00" "\05\04\01\08\01\00")
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\05\04\01\08\01\03")
+  "invalid custom page size"
+)
+
+;; threads
+(module binary "\00asm" "\01\00\00\00" "\05\04\01\03\01\02")
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\05\03\01\02\01")
+  "shared memory must have maximum"
+)
+
+;; wide-arithmetic
+(module binary
+  "\00asm" "\01\00\00\00"
+  "\01\04\01\60\00\00"
+  "\03\02\01\00"
+  "\0a\0c\01\0a\00"
+  "\42\00\42\00\fc\15"                   ;; i64.const 0 i64.const 0 i64.mul_wide_s
+  "\1a\1a\0b"                            ;; drop drop
+)